@@ -0,0 +1,188 @@
+//! Push/pop throughput of the bounded and unbounded queues.
+//!
+//! Run with `cargo bench --bench throughput`, or narrow it down with a filter, e.g.
+//! `cargo bench --bench throughput -- unbounded`. Numbers from a machine with fewer cores
+//! than producers mostly measure the scheduler.
+
+use std::hint::black_box;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use lock_free_mpsc::mpsc::{bounded_mpsc, unbounded_mpsc};
+
+/// Values moved per iteration of the single-threaded benchmarks.
+const BATCH: u64 = 1024;
+/// Values moved per iteration of the contended benchmarks, split between the producers.
+const CONTENDED_MSGS: u64 = 100_000;
+/// Producer counts for the contended benchmarks.
+const PRODUCERS: [u64; 3] = [1, 2, 4];
+
+/// Pushes a batch into a queue with room for all of it, then pops it back out.
+fn single_thread(c: &mut Criterion) {
+    let mut group = c.benchmark_group("single_thread");
+    group.throughput(Throughput::Elements(BATCH));
+
+    for capacity in [BATCH as usize, 4 * BATCH as usize] {
+        let q = bounded_mpsc::RawMpsc::new(capacity);
+        group.bench_with_input(BenchmarkId::new("bounded", capacity), &q, |b, q| {
+            b.iter(|| {
+                for i in 0..BATCH {
+                    q.push(black_box(i)).unwrap();
+                }
+                while let Some(data) = q.pop() {
+                    black_box(data);
+                }
+            })
+        });
+    }
+
+    fn unbounded<const SEG: usize>(
+        group: &mut criterion::BenchmarkGroup<'_, impl criterion::measurement::Measurement>,
+    ) {
+        let q = unbounded_mpsc::RawMpsc::<u64, SEG>::with_segment_size();
+        group.bench_with_input(BenchmarkId::new("unbounded", SEG), &q, |b, q| {
+            b.iter(|| {
+                for i in 0..BATCH {
+                    q.push(black_box(i));
+                }
+                while let Some(data) = q.pop() {
+                    black_box(data);
+                }
+            })
+        });
+    }
+    unbounded::<32>(&mut group);
+    unbounded::<{ unbounded_mpsc::SEGMENT_SIZE }>(&mut group);
+    unbounded::<1024>(&mut group);
+
+    group.finish();
+}
+
+/// Pushes and pops one value at a time through an unbounded queue, so every `SEG`th value
+/// crosses into a new segment: allocation, linking and reclamation dominate with small
+/// segments.
+fn segment_boundary(c: &mut Criterion) {
+    let mut group = c.benchmark_group("segment_boundary");
+    group.throughput(Throughput::Elements(BATCH));
+
+    fn lockstep<const SEG: usize>(
+        group: &mut criterion::BenchmarkGroup<'_, impl criterion::measurement::Measurement>,
+    ) {
+        let q = unbounded_mpsc::RawMpsc::<u64, SEG>::with_segment_size();
+        group.bench_with_input(BenchmarkId::from_parameter(SEG), &q, |b, q| {
+            b.iter(|| {
+                for i in 0..BATCH {
+                    q.push(black_box(i));
+                    black_box(q.pop());
+                }
+            })
+        });
+    }
+    lockstep::<2>(&mut group);
+    lockstep::<16>(&mut group);
+    lockstep::<{ unbounded_mpsc::SEGMENT_SIZE }>(&mut group);
+
+    group.finish();
+}
+
+/// Times `producers` threads pushing `CONTENDED_MSGS` values in total while the calling
+/// thread pops them all.
+fn contended_run<Q: Send + Sync + 'static>(
+    queue: Arc<Q>,
+    producers: u64,
+    push: fn(&Q, u64) -> Result<(), u64>,
+    pop: fn(&Q) -> Option<u64>,
+) -> Duration {
+    let per_producer = CONTENDED_MSGS / producers;
+    let start = Arc::new(Barrier::new(producers as usize + 1));
+    let handles: Vec<_> = (0..producers)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let start = Arc::clone(&start);
+            thread::spawn(move || {
+                start.wait();
+                for i in 0..per_producer {
+                    let mut data = i;
+                    while let Err(back) = push(&queue, data) {
+                        data = back;
+                        thread::yield_now();
+                    }
+                }
+            })
+        })
+        .collect();
+
+    start.wait();
+    let began = Instant::now();
+    let mut received = 0;
+    while received < per_producer * producers {
+        match pop(&queue) {
+            Some(data) => {
+                black_box(data);
+                received += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+    let elapsed = began.elapsed();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    elapsed
+}
+
+/// Several producers pushing into one queue drained by a single consumer.
+fn contended(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended");
+    group.throughput(Throughput::Elements(CONTENDED_MSGS));
+    group.sample_size(20);
+
+    for producers in PRODUCERS {
+        group.bench_with_input(
+            BenchmarkId::new("bounded", producers),
+            &producers,
+            |b, &producers| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| {
+                            contended_run(
+                                Arc::new(bounded_mpsc::RawMpsc::new(1024)),
+                                producers,
+                                |q, data| q.push(data),
+                                |q| q.pop(),
+                            )
+                        })
+                        .sum()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("unbounded", producers),
+            &producers,
+            |b, &producers| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| {
+                            contended_run(
+                                Arc::new(unbounded_mpsc::RawMpsc::new()),
+                                producers,
+                                |q, data| {
+                                    q.push(data);
+                                    Ok(())
+                                },
+                                |q| q.pop(),
+                            )
+                        })
+                        .sum()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, single_thread, segment_boundary, contended);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+//! Consumes a channel as a `Stream` from a tokio task.
+//!
+//! Run with `cargo run --example async_stream --features async`.
+
+use std::time::Duration;
+
+use lock_free_mpsc::mpsc::bounded_mpsc::{AsyncReceiver, channel};
+
+/// The consumer task: sums every value until all senders are gone.
+async fn consume(mut rx: AsyncReceiver<u64>) -> u64 {
+    let mut sum = 0;
+    while let Some(value) = rx.recv().await {
+        println!("received {value}");
+        sum += value;
+    }
+    sum
+}
+
+#[tokio::main]
+async fn main() {
+    let (tx, rx) = channel(16);
+    let consumer = tokio::spawn(consume(rx.into_async()));
+
+    let producers: Vec<_> = (0..4)
+        .map(|id| {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                for i in 0..5 {
+                    tx.send_async(id * 100 + i).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+
+    for producer in producers {
+        producer.await.unwrap();
+    }
+    println!("sum = {}", consumer.await.unwrap());
+}
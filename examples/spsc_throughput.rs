@@ -0,0 +1,65 @@
+//! Compares 1:1 throughput of the SPSC queue against the bounded MPSC queue.
+//!
+//! Run with `cargo run --release --example spsc_throughput`.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lock_free_mpsc::mpsc::bounded_mpsc::{RawMpsc, Spsc};
+
+const ITEMS: u64 = 10_000_000;
+const CAPACITY: usize = 1024;
+
+/// Moves `ITEMS` values from a producer thread to the calling thread and returns how long it
+/// took.
+fn run<P, C>(push: P, pop: C) -> Duration
+where
+    P: Fn(u64) -> Result<(), u64> + Send + 'static,
+    C: Fn() -> Option<u64>,
+{
+    let start = Instant::now();
+    let producer = thread::spawn(move || {
+        for i in 0..ITEMS {
+            let mut data = i;
+            while let Err(back) = push(data) {
+                data = back;
+                thread::yield_now();
+            }
+        }
+    });
+
+    let mut received = 0;
+    while received < ITEMS {
+        match pop() {
+            Some(data) => {
+                black_box(data);
+                received += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+    producer.join().unwrap();
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let per_sec = ITEMS as f64 / elapsed.as_secs_f64();
+    println!(
+        "{name:>5}: {elapsed:>10.2?} ({:.1} M msgs/s)",
+        per_sec / 1e6
+    );
+}
+
+fn main() {
+    let mpsc = Arc::new(RawMpsc::new(CAPACITY));
+    let producer_side = Arc::clone(&mpsc);
+    report(
+        "mpsc",
+        run(move |data| producer_side.push(data), || mpsc.pop()),
+    );
+
+    let (tx, rx) = Spsc::new(CAPACITY).split();
+    report("spsc", run(move |data| tx.push(data), || rx.pop()));
+}
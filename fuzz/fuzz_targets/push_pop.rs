@@ -0,0 +1,98 @@
+//! Drives a bounded queue with a sequence of operations decoded from the input and checks
+//! every result against a `VecDeque` model.
+//!
+//! The first byte picks the capacity, and whether the slot count is rounded up to a power
+//! of two so indices wrap with a mask instead of a comparison. Every following byte is one
+//! operation; see [`Op`].
+
+#![no_main]
+
+use std::collections::VecDeque;
+
+use libfuzzer_sys::fuzz_target;
+use lock_free_mpsc::mpsc::bounded_mpsc::RawMpsc;
+
+/// One operation on the queue, decoded from a byte.
+enum Op {
+    /// Pushes the next value.
+    Push,
+    /// Pops one value.
+    Pop,
+    /// Pushes up to `n` values through a `ProducerHandle`, which claims slots in runs and
+    /// gives back the ones it did not fill when dropped.
+    PushRun(usize),
+    /// Pops up to `n` values at once.
+    PopBulk(usize),
+}
+
+impl Op {
+    fn decode(byte: u8) -> Self {
+        let n = usize::from(byte >> 2);
+        match byte & 0b11 {
+            0 => Op::Push,
+            1 => Op::Pop,
+            2 => Op::PushRun(n),
+            _ => Op::PopBulk(n),
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&first, ops)) = data.split_first() else {
+        return;
+    };
+    let capacity = usize::from(first & 0x3f) + 1;
+    let queue = if first & 0x40 == 0 {
+        RawMpsc::new(capacity)
+    } else {
+        RawMpsc::with_capacity_pow2(capacity)
+    };
+    let capacity = queue.capacity();
+
+    let mut model = VecDeque::new();
+    let mut next = 0u32;
+    for &byte in ops {
+        match Op::decode(byte) {
+            Op::Push => {
+                let pushed = queue.push(next);
+                if model.len() < capacity {
+                    assert_eq!(pushed, Ok(()));
+                    model.push_back(next);
+                } else {
+                    assert_eq!(pushed, Err(next));
+                }
+                next += 1;
+            }
+            Op::Pop => assert_eq!(queue.pop(), model.pop_front()),
+            Op::PushRun(n) => {
+                let mut producer = queue.producer();
+                for _ in 0..n {
+                    // The handle may have claimed fewer slots than are free, so only a full
+                    // queue may turn it away.
+                    match producer.push(next) {
+                        Ok(()) => model.push_back(next),
+                        Err(_) => {
+                            assert_eq!(model.len(), capacity);
+                            break;
+                        }
+                    }
+                    next += 1;
+                }
+            }
+            Op::PopBulk(n) => {
+                let mut out = Vec::new();
+                queue.pop_bulk(&mut out, n);
+                let expected: Vec<_> = model.drain(..n.min(model.len())).collect();
+                assert_eq!(out, expected);
+            }
+        }
+        assert_eq!(queue.len(), model.len());
+        assert_eq!(queue.is_empty(), model.is_empty());
+    }
+
+    let mut rest = Vec::new();
+    while let Some(data) = queue.pop() {
+        rest.push(data);
+    }
+    assert!(rest.iter().eq(model.iter()));
+});
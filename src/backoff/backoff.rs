@@ -5,14 +5,19 @@
 //! configurable delays (`spin_loop`s) when contention is detected, allowing threads to back off
 //! and reduce CPU cache thrashing or spinning overhead.
 
-use std::hint::spin_loop;
-use std::sync::atomic::{
+use core::hint::spin_loop;
+use core::sync::atomic::{
     AtomicUsize,
     Ordering::{AcqRel, Acquire},
 };
 
+use super::Backoff;
+
 const MAX_WAIT_SPIN: u32 = 1 << 18;
 const MIN_WAIT_SPIN: u32 = 32;
+/// Contention level from which `wait` spins for `MAX_WAIT_SPIN`; shifting further would only
+/// push bits out of the `u32`.
+const MAX_WAIT_SHIFT: usize = (MAX_WAIT_SPIN / MIN_WAIT_SPIN).ilog2() as usize;
 
 /// A lock-free backoff mechanism used globally across threads to coordinate retries.
 ///
@@ -21,13 +26,18 @@ const MIN_WAIT_SPIN: u32 = 32;
 /// [`wait`](GlobalBackoff::wait) to yield some CPU time via `spin_loop()`, scaled based on how many
 /// threads are currently contending. This reduces unnecessary contention and improves throughput
 /// under load.
-
 #[repr(transparent)]
 pub struct GlobalBackoff {
     /// Number of threads currently contending.
     active_threads: AtomicUsize,
 }
 
+impl Default for GlobalBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GlobalBackoff {
     /// Creates a new `GlobalBackoff` instance with no registered threads.
     ///
@@ -51,17 +61,26 @@ impl GlobalBackoff {
     /// to the number of currently active threads.
     ///
     /// Should typically be called once before entering a contention-sensitive region.
-
+    ///
+    /// # Safety
+    ///
+    /// Every call must be paired with exactly one later call to [`de_reg`](Self::de_reg),
+    /// otherwise the contention count drifts and inflates every subsequent wait.
     #[inline(always)]
     pub unsafe fn reg_wait(&self) {
         let n_iters = self.active_threads.fetch_add(1, AcqRel);
-        self.spin_for(n_iters as u32);
+        Self::spin_for(n_iters as u32);
     }
 
     /// Deregisters the calling thread from contention tracking.
     ///
     /// Decrements the count of active contending threads. Should be called once a thread
     /// exits a contention-sensitive operation.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by a thread that previously registered with
+    /// [`reg_wait`](Self::reg_wait); an unmatched call underflows the counter.
     #[inline(always)]
     pub unsafe fn de_reg(&self) {
         self.active_threads.fetch_sub(1, AcqRel);
@@ -76,20 +95,82 @@ impl GlobalBackoff {
     /// other contention failures.
     #[inline(always)]
     pub fn wait(&self) {
-        let n_iters = (MIN_WAIT_SPIN << self.active_threads.load(Acquire)).min(MAX_WAIT_SPIN);
-        self.spin_for(n_iters);
+        Self::spin_for(wait_spins(self.active_threads.load(Acquire)));
     }
 
-    /// Performs an exact number of spin iterations using `std::hint::spin_loop()`.
+    /// Performs an exact number of spin iterations using `core::hint::spin_loop()`.
     ///
-    /// Used internally by [`reg_wait`](Self::reg_wait) and [`wait`](Self::wait).
+    /// Used by [`reg_wait`](Self::reg_wait) and [`wait`](Self::wait), but needs no backoff
+    /// instance. It never yields to the OS scheduler; it is pure CPU busy-waiting.
     ///
-    /// # Safety
-    /// This method does not yield to the OS scheduler; it performs pure CPU busy-waiting.
+    /// # Examples
+    ///
+    /// ```
+    /// use lock_free_mpsc::backoff::GlobalBackoff;
+    ///
+    /// GlobalBackoff::spin_for(16);
+    /// ```
     #[inline(always)]
-    pub fn spin_for(&self, iters: u32) {
+    pub fn spin_for(iters: u32) {
         for _ in 0..iters {
             spin_loop();
         }
     }
 }
+
+/// Returns how long [`GlobalBackoff::wait`] spins with `active` contending threads: doubling
+/// from `MIN_WAIT_SPIN` per thread, saturating at `MAX_WAIT_SPIN`.
+#[inline(always)]
+fn wait_spins(active: usize) -> u32 {
+    MIN_WAIT_SPIN << active.min(MAX_WAIT_SHIFT)
+}
+
+impl Backoff for GlobalBackoff {
+    #[inline]
+    fn new() -> Self {
+        GlobalBackoff::new()
+    }
+
+    #[inline(always)]
+    fn wait(&self) {
+        GlobalBackoff::wait(self)
+    }
+
+    /// The only state is the shared contention count, which is not reset.
+    #[inline(always)]
+    fn reset(&self) {}
+
+    #[inline(always)]
+    unsafe fn register(&self) {
+        unsafe { self.reg_wait() }
+    }
+
+    #[inline(always)]
+    unsafe fn unregister(&self) {
+        unsafe { self.de_reg() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering::Relaxed;
+
+    #[test]
+    fn test_wait_spins_double_then_saturate() {
+        assert_eq!(wait_spins(0), MIN_WAIT_SPIN);
+        assert_eq!(wait_spins(1), 2 * MIN_WAIT_SPIN);
+        assert_eq!(wait_spins(MAX_WAIT_SHIFT - 1), MAX_WAIT_SPIN / 2);
+        // A plain shift would lose bits from 27 threads on, and overflow from 32
+        for active in [MAX_WAIT_SHIFT, 27, 32, 64, usize::MAX] {
+            assert_eq!(wait_spins(active), MAX_WAIT_SPIN);
+        }
+    }
+
+    #[test]
+    fn test_wait_with_many_registered_threads() {
+        let backoff = GlobalBackoff::new();
+        backoff.active_threads.store(40, Relaxed);
+        backoff.wait();
+    }
+}
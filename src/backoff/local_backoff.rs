@@ -1,4 +1,6 @@
-use std::{cell::Cell, hint::spin_loop};
+use core::{cell::Cell, hint::spin_loop};
+
+use super::Backoff;
 
 /// A thread-local exponential backoff strategy for reducing contention.
 ///
@@ -6,7 +8,6 @@ use std::{cell::Cell, hint::spin_loop};
 /// data structures, where threads compete for the same resource. It exponentially increases
 /// the number of CPU spin iterations each time `wait` is called, which helps reduce
 /// contention and CPU usage under heavy load.
-
 pub struct LocalBackoff {
     /// Tracks the current number of spin iterations for this thread.
     spins: Cell<u32>,
@@ -18,12 +19,13 @@ pub struct LocalBackoff {
 const MAX_SPIN: u32 = 1 << 16;
 
 impl LocalBackoff {
-    /// Creates a new `LocalBackoff` instance with an initial spin count of 0.
+    /// Creates a new `LocalBackoff` instance with an initial spin count of 1, the same
+    /// value [`reset`](Self::reset) restores.
     ///
     /// Typically used in a local scope for retry-based synchronization primitives.
     pub fn new() -> Self {
         Self {
-            spins: Cell::new(0),
+            spins: Cell::new(1),
         }
     }
 
@@ -31,7 +33,7 @@ impl LocalBackoff {
     ///
     /// The number of iterations doubles with each call (up to `MAX_SPIN`), allowing
     /// contention to decrease before the next retry attempt. Internally, it uses
-    /// [`std::hint::spin_loop`] to inform the CPU that it is in a spin-wait loop.
+    /// [`core::hint::spin_loop`] to inform the CPU that it is in a spin-wait loop.
     pub fn wait(&self) {
         let curr_spin = self.spins.get();
         // Exponential backoff: increase spin count for next wait
@@ -57,4 +59,82 @@ impl LocalBackoff {
     pub fn reset(&self) {
         self.spins.set(1);
     }
+
+    /// Returns `true` once the spin count has grown to `MAX_SPIN`.
+    ///
+    /// Further calls to [`wait`](Self::wait) no longer back off any harder, so a caller
+    /// that can block should stop spinning and park instead.
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        self.spins.get() >= MAX_SPIN
+    }
+}
+
+impl Default for LocalBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backoff for LocalBackoff {
+    #[inline]
+    fn new() -> Self {
+        LocalBackoff::new()
+    }
+
+    #[inline]
+    fn wait(&self) {
+        LocalBackoff::wait(self)
+    }
+
+    #[inline]
+    fn reset(&self) {
+        LocalBackoff::reset(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_grows_from_new() {
+        let backoff = LocalBackoff::new();
+        assert_eq!(backoff.spins.get(), 1);
+
+        let mut prev = backoff.spins.get();
+        for _ in 0..4 {
+            backoff.wait();
+            let curr = backoff.spins.get();
+            assert!(curr > prev, "spin count stuck at {curr}");
+            prev = curr;
+        }
+        assert_eq!(prev, 1 << 4);
+    }
+
+    #[test]
+    fn test_wait_caps_and_reset_restores_initial() {
+        let backoff = LocalBackoff::new();
+        for _ in 0..32 {
+            backoff.wait();
+        }
+        assert_eq!(backoff.spins.get(), MAX_SPIN);
+
+        backoff.reset();
+        assert_eq!(backoff.spins.get(), LocalBackoff::new().spins.get());
+    }
+
+    #[test]
+    fn test_is_completed_after_max_doublings() {
+        let backoff = LocalBackoff::new();
+        let mut waits = 0;
+        while !backoff.is_completed() {
+            backoff.wait();
+            waits += 1;
+        }
+        assert_eq!(waits, MAX_SPIN.ilog2());
+
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
 }
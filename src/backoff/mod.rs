@@ -1,4 +1,50 @@
+//! Backoff strategies used by the queues while they wait out contention.
+//!
+//! The queues are generic over [`Backoff`], so the waiting policy can be swapped without
+//! touching the queue itself: [`GlobalBackoff`] scales its spins with the number of
+//! contending threads, [`YieldBackoff`] hands the CPU back to the scheduler and [`SpinOnly`]
+//! barely waits at all.
+
+#[allow(clippy::module_inception)]
 mod backoff;
 mod local_backoff;
+mod spin_only;
+#[cfg(feature = "std")]
+mod yield_backoff;
 pub use backoff::GlobalBackoff;
 pub use local_backoff::LocalBackoff;
+pub use spin_only::SpinOnly;
+#[cfg(feature = "std")]
+pub use yield_backoff::YieldBackoff;
+
+/// A strategy for waiting between failed attempts at a contended operation.
+pub trait Backoff {
+    /// Creates the backoff in its initial, least patient state.
+    fn new() -> Self;
+
+    /// Waits before the next attempt.
+    fn wait(&self);
+
+    /// Returns the backoff to its initial state, typically after an attempt succeeded.
+    fn reset(&self);
+
+    /// Announces that the calling thread is about to contend.
+    ///
+    /// Strategies that scale with the number of contenders hook in here; the default does
+    /// nothing.
+    ///
+    /// # Safety
+    ///
+    /// Every call must be paired with exactly one later call to
+    /// [`unregister`](Self::unregister) from the same thread.
+    #[inline(always)]
+    unsafe fn register(&self) {}
+
+    /// Announces that the calling thread has stopped contending.
+    ///
+    /// # Safety
+    ///
+    /// Must only follow a matching call to [`register`](Self::register).
+    #[inline(always)]
+    unsafe fn unregister(&self) {}
+}
@@ -0,0 +1,25 @@
+use core::hint::spin_loop;
+
+use super::Backoff;
+
+/// A backoff that only issues a single spin hint between attempts.
+///
+/// Retries happen almost immediately, which gives the lowest latency when contention is
+/// rare but burns CPU when it is not.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SpinOnly;
+
+impl Backoff for SpinOnly {
+    #[inline(always)]
+    fn new() -> Self {
+        SpinOnly
+    }
+
+    #[inline(always)]
+    fn wait(&self) {
+        spin_loop();
+    }
+
+    #[inline(always)]
+    fn reset(&self) {}
+}
@@ -0,0 +1,108 @@
+//! A backoff that cooperates with the OS scheduler on long waits.
+//!
+//! Modelled on crossbeam's `Backoff::snooze`: the first few waits spin, later ones yield the
+//! time slice, and past that the thread sleeps for a few microseconds at a time. Once
+//! [`is_completed`](YieldBackoff::is_completed) reports `true` the caller is better off parking
+//! than retrying.
+
+use std::hint::spin_loop;
+use std::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use std::thread;
+use std::time::Duration;
+
+use super::Backoff;
+
+/// Steps up to which `wait` spins, doubling the spin count each time.
+const SPIN_LIMIT: u32 = 6;
+/// Steps up to which `wait` yields to the scheduler; past it, it sleeps.
+const YIELD_LIMIT: u32 = 10;
+/// Step at which the backoff is considered exhausted.
+const SLEEP_LIMIT: u32 = 14;
+/// Length of a sleep once the yield phase is over.
+const SLEEP: Duration = Duration::from_micros(50);
+
+/// A backoff that escalates from spinning to yielding to sleeping.
+///
+/// Unlike the pure spinning strategies it does not starve other threads on an oversubscribed
+/// machine. The step counter is atomic, so one instance can be shared between producers.
+#[derive(Default, Debug)]
+pub struct YieldBackoff {
+    /// How many times `wait` has been called since the last reset, capped at `SLEEP_LIMIT`.
+    step: AtomicU32,
+}
+
+impl YieldBackoff {
+    /// Creates a new `YieldBackoff` in its initial spinning phase.
+    pub const fn new() -> Self {
+        Self {
+            step: AtomicU32::new(0),
+        }
+    }
+
+    /// Waits before the next attempt, escalating the longer it is called without a reset.
+    pub fn wait(&self) {
+        let step = self.step.load(Relaxed);
+        if step <= SPIN_LIMIT {
+            for _ in 0..1 << step {
+                spin_loop();
+            }
+        } else if step <= YIELD_LIMIT {
+            thread::yield_now();
+        } else {
+            thread::sleep(SLEEP);
+        }
+        if step < SLEEP_LIMIT {
+            self.step.store(step + 1, Relaxed);
+        }
+    }
+
+    /// Returns `true` once waiting has gone on long enough that blocking would be cheaper
+    /// than backing off any further.
+    pub fn is_completed(&self) -> bool {
+        self.step.load(Relaxed) >= SLEEP_LIMIT
+    }
+
+    /// Returns the backoff to its initial spinning phase.
+    pub fn reset(&self) {
+        self.step.store(0, Relaxed);
+    }
+}
+
+impl Backoff for YieldBackoff {
+    #[inline]
+    fn new() -> Self {
+        YieldBackoff::new()
+    }
+
+    #[inline]
+    fn wait(&self) {
+        YieldBackoff::wait(self)
+    }
+
+    #[inline]
+    fn reset(&self) {
+        YieldBackoff::reset(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completes_after_escalating() {
+        let backoff = YieldBackoff::new();
+        for _ in 0..SLEEP_LIMIT {
+            assert!(!backoff.is_completed());
+            backoff.wait();
+        }
+        assert!(backoff.is_completed());
+
+        // Further waits stay in the sleeping phase
+        backoff.wait();
+        assert!(backoff.is_completed());
+
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
+}
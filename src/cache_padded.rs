@@ -1,4 +1,4 @@
-///! This module is from the `crossbeam-utils` crate.
+//! This module is from the `crossbeam-utils` crate.
 use core::fmt;
 use core::ops::{Deref, DerefMut};
 
@@ -46,6 +46,18 @@ use core::ops::{Deref, DerefMut};
 /// assert_eq!(addr2 % 32, 0);
 /// ```
 ///
+/// The padding is transparent: `CachePadded<T>` dereferences to `T`, so it can wrap the
+/// atomics of a user-built lock-free structure without changing how they are used.
+///
+/// ```
+/// use lock_free_mpsc::CachePadded;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// let counter: CachePadded<AtomicUsize> = AtomicUsize::new(0).into();
+/// counter.fetch_add(1, Ordering::Relaxed);
+/// assert_eq!(counter.into_inner().into_inner(), 1);
+/// ```
+///
 /// When building a concurrent queue with a head and a tail index, it is wise to place them in
 /// different cache lines so that concurrent threads pushing and popping elements don't invalidate
 /// each other's cache lines:
@@ -150,6 +162,38 @@ pub struct CachePadded<T> {
     value: T,
 }
 
+// Each target family gets the line size documented above; checked on every build so a change
+// to the `repr(align)` attributes can't silently drift from it.
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm64ec",
+    target_arch = "powerpc64",
+))]
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == 128);
+#[cfg(any(
+    target_arch = "arm",
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "sparc",
+    target_arch = "hexagon",
+))]
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == 32);
+#[cfg(target_arch = "m68k")]
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == 16);
+#[cfg(target_arch = "s390x")]
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == 256);
+#[cfg(any(
+    target_arch = "x86",
+    target_arch = "wasm32",
+    target_arch = "riscv32",
+    target_arch = "riscv64",
+    target_arch = "sparc64",
+))]
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == 64);
+
 unsafe impl<T: Send> Send for CachePadded<T> {}
 unsafe impl<T: Sync> Sync for CachePadded<T> {}
 
@@ -215,3 +259,40 @@ impl<T: fmt::Display> fmt::Display for CachePadded<T> {
         fmt::Display::fmt(&self.value, f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{align_of, size_of};
+
+    #[test]
+    fn test_alignment_matches_the_target_cache_line() {
+        let expected = if cfg!(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "powerpc64"
+        )) {
+            128
+        } else if cfg!(any(target_arch = "x86", target_arch = "riscv64")) {
+            64
+        } else {
+            return;
+        };
+        assert_eq!(align_of::<CachePadded<u8>>(), expected);
+        assert_eq!(size_of::<CachePadded<u8>>(), expected);
+        // A larger value is padded to the next multiple of the line
+        assert_eq!(
+            size_of::<CachePadded<[u8; 129]>>(),
+            129usize.next_multiple_of(expected)
+        );
+    }
+
+    #[test]
+    fn test_deref_and_conversions() {
+        let mut padded = CachePadded::from(vec![1, 2]);
+        padded.push(3);
+        assert_eq!(padded.len(), 3);
+        assert_eq!(format!("{padded:?}"), "CachePadded { value: [1, 2, 3] }");
+        assert_eq!(CachePadded::<u32>::default().into_inner(), 0);
+    }
+}
@@ -1,3 +1,16 @@
+//! Lock-free multi-producer single-consumer queues and channels.
+//!
+//! The crate is `no_std` unless the default `std` feature is enabled. The queues only need a
+//! global allocator (the `alloc` feature); blocking receives need `std` to park threads.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod backoff;
 pub mod cache_padded;
+#[cfg(feature = "alloc")]
 pub mod mpsc;
+
+pub use cache_padded::CachePadded;
@@ -0,0 +1,86 @@
+//! Where the bounded queue's slot array is allocated.
+//!
+//! [`Alloc`] is a minimal stand-in for the unstable `core::alloc::Allocator`, enough for the
+//! single buffer a [`RawMpsc`](super::RawMpsc) allocates up front (and again on
+//! [`resize`](super::RawMpsc::resize)). [`Global`] forwards to the global allocator and is
+//! the default.
+
+use alloc::alloc::{alloc, alloc_zeroed, dealloc};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+/// An allocator the slot array of a bounded queue can be placed in.
+///
+/// # Safety
+///
+/// Memory returned by [`allocate`](Self::allocate) and
+/// [`allocate_zeroed`](Self::allocate_zeroed) must be valid for reads and writes of
+/// `layout` and stay so until it is passed to [`deallocate`](Self::deallocate) of the same
+/// allocator, or of a clone of it.
+pub unsafe trait Alloc {
+    /// Allocates a block fitting `layout`, or returns `None` if that fails.
+    ///
+    /// `layout` never has a size of zero.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Like [`allocate`](Self::allocate), but the block is filled with zeroes.
+    ///
+    /// The default implementation zeroes the block after allocating it.
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let ptr = self.allocate(layout)?;
+        // SAFETY: the block is valid for writes of `layout.size()` bytes.
+        unsafe { ptr.as_ptr().write_bytes(0, layout.size()) };
+        Some(ptr)
+    }
+
+    /// Frees a block.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`allocate`](Self::allocate) or
+    /// [`allocate_zeroed`](Self::allocate_zeroed) of this allocator with the same `layout`,
+    /// and not been freed since.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+/// The global allocator, as used by `Box` and `Vec`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global;
+
+// SAFETY: forwards to the global allocator.
+unsafe impl Alloc for Global {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        // SAFETY: callers never ask for a zero-sized block.
+        NonNull::new(unsafe { alloc(layout) })
+    }
+
+    #[inline]
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+        // SAFETY: as above.
+        NonNull::new(unsafe { alloc_zeroed(layout) })
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { dealloc(ptr.as_ptr(), layout) }
+    }
+}
+
+// SAFETY: forwards to `A`.
+unsafe impl<A: Alloc + ?Sized> Alloc for &A {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    #[inline]
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate_zeroed(layout)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { (**self).deallocate(ptr, layout) }
+    }
+}
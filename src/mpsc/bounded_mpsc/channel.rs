@@ -0,0 +1,1810 @@
+//! A `Sender`/`Receiver` split over the bounded [`RawMpsc`] queue.
+//!
+//! [`channel`] hands out any number of cloneable [`Sender`]s but exactly one [`Receiver`],
+//! so the single-consumer requirement of the underlying queue is enforced by the type system
+//! instead of by convention.
+
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(feature = "async")]
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+#[cfg(feature = "async")]
+use core::pin::Pin;
+#[cfg(feature = "async")]
+use core::task::Waker;
+#[cfg(feature = "std")]
+use core::task::{Context, Poll};
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use super::raw_mpsc::{RawMpsc, Reservation};
+use crate::mpsc::chan::{Chan, RawQueue};
+#[cfg(feature = "std")]
+use crate::mpsc::errors::{RecvError, RecvTimeoutError};
+use crate::mpsc::errors::{SendError, TryRecvError, TrySendError};
+use crate::mpsc::queue::Ack;
+use crate::mpsc::select::sealed;
+
+impl<T> RawQueue for RawMpsc<T> {
+    type Item = T;
+
+    #[inline]
+    fn push(&self, data: T) -> Result<(), T> {
+        RawMpsc::push(self, data)
+    }
+
+    #[inline]
+    fn pop(&self) -> Option<T> {
+        RawMpsc::pop_without_waking(self)
+    }
+
+    #[inline]
+    fn pop_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        RawMpsc::pop_with_without_waking(self, f)
+    }
+
+    #[inline]
+    fn pop_ack(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        RawMpsc::pop_ack_without_waking(self, f)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    fn len(&self) -> usize {
+        RawMpsc::len(self)
+    }
+}
+
+/// Creates a bounded channel able to buffer up to `capacity` values.
+///
+/// A `capacity` of zero makes a rendezvous channel, like `std::sync::mpsc::sync_channel(0)`:
+/// nothing is buffered, and a value only changes hands when the sender and the receiver
+/// meet. [`Sender::send_blocking`] then waits until the [`Receiver`] has taken the value,
+/// while [`Sender::try_send`] only succeeds if the receiver is already blocked waiting.
+///
+/// # Examples
+///
+/// ```
+/// use lock_free_mpsc::mpsc::bounded_mpsc::channel;
+///
+/// let (tx, rx) = channel(4);
+/// tx.send(1).unwrap();
+/// assert_eq!(rx.recv(), Ok(1));
+/// ```
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(match capacity {
+        // The one slot holds a value only while it is being handed over.
+        0 => Chan::rendezvous(RawMpsc::new(1)),
+        _ => Chan::new(RawMpsc::new(capacity)),
+    });
+    let sender = Sender {
+        inner: Arc::clone(&inner),
+    };
+    let receiver = Receiver {
+        inner,
+        _not_sync: PhantomData,
+    };
+    (sender, receiver)
+}
+
+impl<T> Chan<RawMpsc<T>> {
+    /// Free slots left in the queue; the single slot of a rendezvous channel never counts.
+    fn remaining(&self) -> usize {
+        if self.is_rendezvous() {
+            0
+        } else {
+            self.queue.remaining()
+        }
+    }
+
+    /// Values the queue holds at most; a rendezvous channel holds none.
+    #[cfg(feature = "std")]
+    fn capacity(&self) -> usize {
+        if self.is_rendezvous() {
+            0
+        } else {
+            self.queue.capacity()
+        }
+    }
+}
+
+/// The sending half of a bounded channel.
+///
+/// Senders can be cloned freely and moved to other threads. The channel is disconnected
+/// once every `Sender` has been dropped.
+pub struct Sender<T> {
+    inner: Arc<Chan<RawMpsc<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Attempts to send a value into the channel.
+    ///
+    /// Returns the value back inside a [`SendError`] if the channel is full or closed, or the
+    /// [`Receiver`] has been dropped. Use [`try_send`](Self::try_send) to tell the two apart.
+    pub fn send(&self, data: T) -> Result<(), SendError<T>> {
+        self.try_send(data).map_err(SendError::from)
+    }
+
+    /// Attempts to send a value into the channel without waiting.
+    ///
+    /// Returns [`TrySendError::Full`] if the channel has no room,
+    /// [`TrySendError::Closed`] if it has been closed, or [`TrySendError::Disconnected`] if
+    /// the [`Receiver`] has been dropped.
+    pub fn try_send(&self, data: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(data)
+    }
+
+    /// Claims room for one value up front, without having the value yet.
+    ///
+    /// The returned [`SendPermit`] holds a slot of the channel until it is used with
+    /// [`SendPermit::send`], which cannot fail, or dropped, which gives the slot back. Fails
+    /// like [`try_send`](Self::try_send) does. A zero-capacity channel has no slot to claim,
+    /// so reserving on one always fails with [`TrySendError::Full`].
+    ///
+    /// Values are received in the order their slots were claimed, so anything sent after
+    /// the permit was taken is only received once the permit is used or dropped; a permit
+    /// should not be held for long.
+    pub fn try_reserve(&self) -> Result<SendPermit<'_, T>, TrySendError<()>> {
+        let slot = self.reserve()?;
+        Ok(SendPermit {
+            chan: &self.inner,
+            slot: Some(slot),
+        })
+    }
+
+    /// Claims a slot to build a value in place, without moving it into the channel.
+    ///
+    /// The returned [`ReservedSlot`] exposes the slot's storage as a `&mut MaybeUninit<T>`,
+    /// so a large value such as a frame buffer can be written straight into the channel. Fails,
+    /// and is ordered, like [`try_reserve`](Self::try_reserve); a zero-capacity channel has
+    /// no slot to lend, so it always fails with [`TrySendError::Full`].
+    pub fn send_ref(&self) -> Result<ReservedSlot<'_, T>, TrySendError<()>> {
+        let slot = self.reserve()?;
+        Ok(ReservedSlot {
+            chan: &self.inner,
+            slot: Some(slot),
+        })
+    }
+
+    /// Begins a send and claims a slot for it, ending the send again if there is none.
+    fn reserve(&self) -> Result<Reservation<'_, T>, TrySendError<()>> {
+        self.inner.begin_send()?;
+        let slot = if self.inner.is_rendezvous() {
+            None
+        } else {
+            self.inner.queue.reserve()
+        };
+        slot.ok_or_else(|| {
+            self.inner.end_send(false);
+            TrySendError::Full(())
+        })
+    }
+
+    /// Closes the channel for every sender.
+    ///
+    /// Later sends fail with [`TrySendError::Closed`], and senders parked on a full channel
+    /// give up. Values already sent are still delivered: the [`Receiver`] drains them and
+    /// only then reports the channel as disconnected. Either side can close the channel.
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Returns `true` once the channel has been closed by either side.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Creates a [`WeakSender`] for this channel.
+    ///
+    /// The weak handle doesn't count as a sender: once every `Sender` has been dropped the
+    /// receiver sees the channel as disconnected, and the weak handle can no longer be
+    /// upgraded.
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Returns how many more values can be sent before the channel is full.
+    ///
+    /// This is a best-effort snapshot: other senders and the receiver may have changed it by
+    /// the time it is returned. A zero-capacity channel always reports 0.
+    pub fn remaining(&self) -> usize {
+        self.inner.remaining()
+    }
+
+    /// Returns `true` if `other` sends into the same channel as `self`.
+    pub fn same_channel(&self, other: &Sender<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Returns `true` if `recv` receives from the channel `self` sends into.
+    pub fn same_channel_as(&self, recv: &Receiver<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &recv.inner)
+    }
+
+    /// Marks the end of a burst of sends: issues a `Release` fence and unparks the
+    /// [`Receiver`] once if it is blocked.
+    ///
+    /// Sent values need no help to become visible; see [`RawMpsc::publish`] for what the
+    /// fence orders.
+    pub fn publish(&self) {
+        self.inner.publish();
+    }
+
+    /// Sends every value of `iter` in order, stopping at the first one that can't be sent.
+    ///
+    /// On failure returns how many values were sent, along with the one that failed inside a
+    /// [`SendError`]; the iterator's remaining values are dropped. Resuming with the failed
+    /// value first keeps the order intact.
+    pub fn send_all<I: IntoIterator<Item = T>>(
+        &self,
+        iter: I,
+    ) -> Result<(), (usize, SendError<T>)> {
+        for (sent, data) in iter.into_iter().enumerate() {
+            self.send(data).map_err(|err| (sent, err))?;
+        }
+        Ok(())
+    }
+
+    /// Sends a value, blocking the calling thread while the channel is full.
+    ///
+    /// The thread parks until the [`Receiver`] frees a slot. Returns the value back inside a
+    /// [`SendError`] if the `Receiver` is, or gets, dropped, or the channel is, or gets,
+    /// closed.
+    #[cfg(feature = "std")]
+    pub fn send_blocking(&self, data: T) -> Result<(), SendError<T>> {
+        self.inner.send_blocking(data)
+    }
+
+    /// Sends a value from async code, waiting for room if the channel is full.
+    ///
+    /// While the channel is full the task is put on a wait list and woken once the receiver
+    /// frees a slot. Resolves to [`SendError`] holding the value if the [`Receiver`] is, or
+    /// gets, dropped.
+    #[cfg(feature = "async")]
+    pub fn send_async(&self, data: T) -> SendFuture<'_, T> {
+        SendFuture {
+            tx: self,
+            data: Some(data),
+            waker: None,
+        }
+    }
+}
+
+/// Future returned by [`Sender::send_async`].
+#[cfg(feature = "async")]
+pub struct SendFuture<'a, T> {
+    tx: &'a Sender<T>,
+    /// The value still to be sent; `None` once the future has completed.
+    data: Option<T>,
+    /// The waker the task is on the wait list with, if it is.
+    waker: Option<Waker>,
+}
+
+#[cfg(feature = "async")]
+impl<T> SendFuture<'_, T> {
+    /// Takes the task off the wait list, once it stops waiting.
+    fn unregister(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            self.tx.inner.unregister_sender(&waker);
+        }
+    }
+}
+
+// The value is moved around freely and never pinned.
+#[cfg(feature = "async")]
+impl<T> Unpin for SendFuture<'_, T> {}
+
+#[cfg(feature = "async")]
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let data = self
+            .data
+            .take()
+            .expect("`SendFuture` polled after completion");
+        let data = match self.tx.try_send(data) {
+            Err(TrySendError::Full(data)) => data,
+            done => {
+                self.unregister();
+                return Poll::Ready(done.map_err(SendError::from));
+            }
+        };
+
+        // Polled by a different task than last time: only the new one is to be woken.
+        if !self.waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+            self.unregister();
+            self.waker = Some(cx.waker().clone());
+        }
+        self.tx.inner.register_sender(cx.waker());
+        // A slot may have been freed before the registration was visible; try once more.
+        match self.tx.try_send(data) {
+            Err(TrySendError::Full(data)) => {
+                self.data = Some(data);
+                Poll::Pending
+            }
+            done => {
+                self.unregister();
+                Poll::Ready(done.map_err(SendError::from))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for SendFuture<'_, T> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// A slot of a bounded channel claimed by [`Sender::try_reserve`].
+///
+/// Dropping the permit without sending gives the slot back, so the receiver never waits on
+/// a value that is not coming.
+pub struct SendPermit<'a, T> {
+    chan: &'a Chan<RawMpsc<T>>,
+    /// Taken by `send`; a permit still holding it on drop gives it back.
+    slot: Option<Reservation<'a, T>>,
+}
+
+impl<T> SendPermit<'_, T> {
+    /// Writes `data` into the claimed slot, completing the send.
+    pub fn send(mut self, data: T) {
+        if let Some(slot) = self.slot.take() {
+            slot.fill(data);
+        }
+    }
+}
+
+impl<T> Drop for SendPermit<'_, T> {
+    fn drop(&mut self) {
+        drop(self.slot.take());
+        // Even without a value, the receiver may have stopped at this slot with values sent
+        // after it waiting behind.
+        self.chan.end_send(true);
+    }
+}
+
+/// A slot of a bounded channel claimed by [`Sender::send_ref`], for a value to be built in
+/// place.
+///
+/// Dropping it without [`commit`](Self::commit)ting gives the slot back, as dropping a
+/// [`SendPermit`] does. That includes unwinding out of a panic while the value is half
+/// built: the slot is rolled back and the receiver never sees it. Whatever was written into
+/// the slot is not dropped then, only forgotten.
+pub struct ReservedSlot<'a, T> {
+    chan: &'a Chan<RawMpsc<T>>,
+    /// Taken by `commit`; a slot still holding it on drop gives it back.
+    slot: Option<Reservation<'a, T>>,
+}
+
+impl<T> ReservedSlot<'_, T> {
+    /// Returns the slot's storage, to write the value into.
+    pub fn as_uninit(&mut self) -> &mut MaybeUninit<T> {
+        match &mut self.slot {
+            Some(slot) => slot.as_uninit(),
+            None => unreachable!("only `commit` takes the slot"),
+        }
+    }
+
+    /// Completes the send with the value written through [`as_uninit`](Self::as_uninit).
+    ///
+    /// # Safety
+    ///
+    /// The value must have been fully initialised, as for [`MaybeUninit::assume_init`].
+    pub unsafe fn commit(mut self) {
+        if let Some(slot) = self.slot.take() {
+            // SAFETY: guaranteed by the caller.
+            unsafe { slot.commit() };
+        }
+    }
+}
+
+impl<T> Drop for ReservedSlot<'_, T> {
+    fn drop(&mut self) {
+        drop(self.slot.take());
+        // Same as for `SendPermit`: the receiver may have stopped at this slot.
+        self.chan.end_send(true);
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.inner.acquire_sender();
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.inner.release_sender();
+    }
+}
+
+/// A handle to a bounded channel that doesn't keep it connected.
+///
+/// Created by [`Sender::downgrade`]. It can't send by itself; [`upgrade`](Self::upgrade)
+/// turns it back into a [`Sender`] as long as at least one other `Sender` is still alive.
+pub struct WeakSender<T> {
+    inner: Weak<Chan<RawMpsc<T>>>,
+}
+
+impl<T> WeakSender<T> {
+    /// Returns a new [`Sender`] for the channel, or `None` if every `Sender` has been
+    /// dropped.
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        let inner = self.inner.upgrade()?;
+        // Built lazily: a `Sender` dropped on the failure path would release a count it
+        // never acquired.
+        inner.try_acquire_sender().then(|| Sender { inner })
+    }
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Weak::clone(&self.inner),
+        }
+    }
+}
+
+/// The receiving half of a bounded channel.
+///
+/// There is exactly one `Receiver` per channel: it can be moved to another thread but is
+/// neither `Clone` nor `Sync`, so only one thread can ever pop from the queue.
+pub struct Receiver<T> {
+    inner: Arc<Chan<RawMpsc<T>>>,
+    /// Opts out of `Sync` so a `&Receiver` can't be shared between consumers.
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<T> Receiver<T> {
+    /// Attempts to receive a value without waiting.
+    ///
+    /// Returns [`TryRecvError::Empty`] if the channel is currently empty, or
+    /// [`TryRecvError::Disconnected`] once every sender is gone, or the channel is closed,
+    /// and the queue is drained.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// Runs `f` on the next value without moving it out of the channel, then drops it and
+    /// returns what `f` returned.
+    ///
+    /// Returns `None` without waiting if no value is ready, whether or not the channel is
+    /// still connected. Meant for large messages that are only needed by reference: the
+    /// slot stays taken while `f` runs, so the channel has one slot less of room until then.
+    /// On a zero-capacity channel the sender is only released once `f` has returned.
+    pub fn recv_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.inner.try_recv_with(f)
+    }
+
+    /// Runs `f` on the next value without moving it out of the channel. The value is dropped
+    /// if `f` returns [`Ack::Ack`], and handed out again by the next receive if it returns
+    /// [`Ack::Nack`].
+    ///
+    /// Returns `None` without waiting if no value is ready. A panic in `f` drops the value,
+    /// so a message that always fails its handler that way is not redelivered forever.
+    pub fn recv_ack(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        self.inner.try_recv_ack(f)
+    }
+
+    /// Closes the channel, so every later send fails with [`TrySendError::Closed`].
+    ///
+    /// Values already buffered are still received; once they are drained, receiving reports
+    /// the channel as disconnected even while senders are alive.
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Closes the channel like [`close`](Self::close), and drops every value already buffered
+    /// instead of leaving it to be received.
+    ///
+    /// Meant for a consumer that is shutting down and wants its memory back right away. Unlike
+    /// dropping the receiver, the `Sender`s keep a channel that cleanly reports
+    /// [`TrySendError::Closed`] to every later send. Senders parked on a full
+    /// channel give up with their value handed back. A send racing the close
+    /// may still land a value afterwards, which the next receive hands out.
+    pub fn close_and_clear(&self) {
+        self.inner.close_and_clear();
+    }
+
+    /// Returns `true` once the channel has been closed by either side.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Returns how many more values can be sent before the channel is full.
+    ///
+    /// Only a snapshot while senders are running; see [`Sender::remaining`].
+    pub fn remaining(&self) -> usize {
+        self.inner.remaining()
+    }
+
+    /// Receives a value, blocking the current thread until one is available.
+    ///
+    /// After a short spin the thread parks and is unparked by the next send, so an idle
+    /// receiver does not burn CPU.
+    ///
+    /// Returns [`RecvError`] once every sender has been dropped and the queue is drained;
+    /// values sent before the last sender was dropped are still delivered first.
+    #[cfg(feature = "std")]
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.inner.recv()
+    }
+
+    /// Receives every value until the senders are done, calling `f` on each in order.
+    ///
+    /// Blocks like [`recv`](Self::recv) between values, and returns once every sender has
+    /// been dropped, or the channel closed, and every value sent before that has been passed
+    /// to `f`. The usual "consume until the producers finish" loop, in one call.
+    #[cfg(feature = "std")]
+    pub fn recv_until_disconnect(&self, f: impl FnMut(T)) {
+        self.inner.recv_until_disconnect(f);
+    }
+
+    /// Receives a batch of values, blocking until at least one is available.
+    ///
+    /// Once the first value arrives, up to `max - 1` more that are ready right away are taken
+    /// without waiting, so a busy pipeline pays for one wake-up per batch rather than per
+    /// value. The values are appended to `out` in FIFO order and their number is returned; a
+    /// `max` of zero returns `Ok(0)` without blocking.
+    ///
+    /// Returns [`RecvError`] if the channel is disconnected, or closed, and empty.
+    #[cfg(feature = "std")]
+    pub fn recv_batch(&self, max: usize, out: &mut Vec<T>) -> Result<usize, RecvError> {
+        self.inner.recv_batch(max, out)
+    }
+
+    /// Blocks until at least `n` values are queued, so a consumer can wait for a minimum
+    /// batch before draining it with [`recv_batch`](Self::recv_batch).
+    ///
+    /// Values whose senders have claimed a slot but are still writing them already count.
+    /// An `n` of zero returns `Ok(())` without blocking.
+    ///
+    /// Returns [`RecvError`] if the channel is disconnected, or closed, with fewer than `n`
+    /// values left, and straight away if `n` exceeds the capacity, since that many values
+    /// never fit in the channel at once.
+    #[cfg(feature = "std")]
+    pub fn wait_for(&self, n: usize) -> Result<(), RecvError> {
+        if n > self.inner.capacity() {
+            return Err(RecvError);
+        }
+        self.inner.wait_for(n)
+    }
+
+    /// Receives a value, blocking for at most `timeout`.
+    ///
+    /// Returns [`RecvTimeoutError::Timeout`] if nothing arrived in time, or
+    /// [`RecvTimeoutError::Disconnected`] once every sender is gone, or the channel is closed,
+    /// and the queue is drained.
+    #[cfg(feature = "std")]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.inner.recv_timeout(timeout)
+    }
+
+    /// Receives a batch of values, waiting at most `timeout` for the first one.
+    ///
+    /// Like [`recv_batch`](Self::recv_batch), up to `max - 1` more values that are ready
+    /// once the first has arrived are taken without waiting, and appended to `out` in FIFO
+    /// order. Bounds the latency of a batching consumer: flush whatever came in within
+    /// `timeout`, or as soon as `max` values are there. A `max` of zero returns `Ok(0)`
+    /// without blocking.
+    ///
+    /// Returns [`RecvTimeoutError::Timeout`] only if no value arrived in time, or
+    /// [`RecvTimeoutError::Disconnected`] once every sender is gone, or the channel is closed,
+    /// and the queue is drained.
+    #[cfg(feature = "std")]
+    pub fn recv_many_timeout(
+        &self,
+        max: usize,
+        timeout: Duration,
+        out: &mut Vec<T>,
+    ) -> Result<usize, RecvTimeoutError> {
+        self.inner.recv_many_timeout(max, timeout, out)
+    }
+
+    /// Receives a value, blocking until `deadline` at the latest.
+    ///
+    /// Behaves like [`recv_timeout`](Self::recv_timeout) but takes an absolute wake-up time.
+    /// If `deadline` has already passed the channel is polled exactly once, without parking.
+    #[cfg(feature = "std")]
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        self.inner.recv_deadline(deadline)
+    }
+
+    /// Returns a reference to the next value without receiving it.
+    ///
+    /// Returns `None` if nothing is ready to be received right now. Taking `&mut self` keeps
+    /// the value from being received, and so freed, while the reference is alive.
+    pub fn peek(&mut self) -> Option<&T> {
+        // SAFETY: this is the only `Receiver`, and the exclusive borrow rules out a
+        // `try_recv` or `recv` until the returned reference is dropped.
+        unsafe { self.inner.queue.peek() }
+    }
+
+    /// Returns an iterator over the values that can be received without waiting.
+    ///
+    /// The iterator stops at the first moment the queue is observed empty, so it drains only
+    /// what is visible at the time; producers may keep adding values afterwards, which a later
+    /// call will pick up. It never blocks or spins.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+
+    /// Removes every value currently buffered and returns them in FIFO order.
+    ///
+    /// Like [`try_iter`](Self::try_iter) this never blocks and only sees what is visible at
+    /// call time, but it collects the batch into a `Vec` in one call.
+    pub fn drain(&self) -> Vec<T> {
+        self.try_iter().collect()
+    }
+
+    /// Polls for the next value, for executors driving the receiver by hand.
+    ///
+    /// Returns `Poll::Ready(Some(_))` with a value, `Poll::Ready(None)` once every sender has
+    /// been dropped (or the channel was closed) and the buffered values are drained, and
+    /// otherwise registers the task's waker and returns `Poll::Pending`; the next send, or the
+    /// channel ending, wakes it. Only the most recently registered waker is woken.
+    ///
+    /// Needs no external crate, so it works with any runtime or a hand-rolled one without the
+    /// `async` feature. It does need `std`, since the waker is kept behind a
+    /// `std::sync::Mutex`.
+    #[cfg(feature = "std")]
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.inner.poll_recv(cx)
+    }
+
+    /// Turns the receiver into an [`AsyncReceiver`] for use from async code.
+    #[cfg(feature = "async")]
+    pub fn into_async(self) -> AsyncReceiver<T> {
+        AsyncReceiver { rx: self }
+    }
+}
+
+impl<T> sealed::Sealed<T> for Receiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    #[cfg(feature = "std")]
+    fn prepare_park(&self) {
+        self.inner.prepare_park();
+    }
+
+    #[cfg(feature = "std")]
+    fn cancel_park(&self) {
+        self.inner.cancel_park();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.disconnect_receiver();
+    }
+}
+
+/// A non-blocking iterator over the values currently buffered in a channel.
+///
+/// Created by [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// A blocking iterator that owns the [`Receiver`].
+///
+/// Each call to `next` waits for the next value and returns `None` once every sender has
+/// been dropped and the queue is drained. Created by `Receiver::into_iter`, which makes
+/// `for value in receiver { .. }` the natural consumer loop.
+#[cfg(feature = "std")]
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+#[cfg(feature = "std")]
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
+
+/// The receiving half of a channel, for use from async code.
+///
+/// Created by [`Receiver::into_async`]. It implements [`Stream`], yielding values until every
+/// sender has been dropped and the queue is drained, and [`recv`](Self::recv) gives a future
+/// for `rx.recv().await` without pulling in a stream combinator crate. Waiting never blocks
+/// the executor thread: an empty channel registers the task's waker, and the next send
+/// wakes it.
+#[cfg(feature = "async")]
+pub struct AsyncReceiver<T> {
+    rx: Receiver<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncReceiver<T> {
+    /// Returns a future that resolves to the next value, or to `None` once every sender has
+    /// been dropped and the queue is drained.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { rx: self }
+    }
+
+    /// Turns this back into a blocking [`Receiver`].
+    pub fn into_inner(self) -> Receiver<T> {
+        self.rx
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Stream for AsyncReceiver<T> {
+    type Item = T;
+
+    /// Returns `Poll::Ready(None)` once every sender has been dropped and the queue is
+    /// drained; values sent before that are still delivered first.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.rx.inner.poll_recv(cx)
+    }
+}
+
+/// Future returned by [`AsyncReceiver::recv`].
+#[cfg(feature = "async")]
+pub struct Recv<'a, T> {
+    rx: &'a mut AsyncReceiver<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Pin::new(&mut *self.rx).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_remaining() {
+        let (tx, rx) = channel(4);
+        assert_eq!((tx.remaining(), rx.remaining()), (4, 4));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!((tx.remaining(), rx.remaining()), (2, 2));
+        tx.send(3).unwrap();
+        tx.send(4).unwrap();
+        assert_eq!((tx.remaining(), rx.remaining()), (0, 0));
+        rx.recv().unwrap();
+        assert_eq!(tx.remaining(), 1);
+
+        let (tx, _rx) = channel::<i32>(0);
+        assert_eq!(tx.remaining(), 0);
+    }
+
+    #[test]
+    fn test_recv_ack_redelivers_on_nack() {
+        let (tx, rx) = channel(4);
+        tx.send(String::from("retry me")).unwrap();
+        tx.send(String::from("next")).unwrap();
+
+        let mut attempts = 0;
+        let mut handle = |msg: &mut String| {
+            attempts += 1;
+            if attempts < 3 {
+                msg.push('!');
+                Ack::Nack
+            } else {
+                assert_eq!(msg, "retry me!!");
+                Ack::Ack
+            }
+        };
+        assert_eq!(rx.recv_ack(&mut handle), Some(Ack::Nack));
+        assert_eq!(rx.recv_ack(&mut handle), Some(Ack::Nack));
+        assert_eq!(rx.recv_ack(&mut handle), Some(Ack::Ack));
+        assert_eq!(rx.try_recv().as_deref(), Ok("next"));
+        assert_eq!(rx.recv_ack(|_| Ack::Ack), None);
+    }
+
+    #[test]
+    fn test_same_channel() {
+        let (tx, rx) = channel::<i32>(4);
+        let tx2 = tx.clone();
+        assert!(tx.same_channel(&tx2));
+        assert!(tx2.same_channel_as(&rx));
+
+        let (other_tx, other_rx) = channel::<i32>(4);
+        assert!(!tx.same_channel(&other_tx));
+        assert!(!tx.same_channel_as(&other_rx));
+        assert!(!other_tx.same_channel_as(&rx));
+    }
+
+    #[test]
+    fn test_send_recv() {
+        let (tx, rx) = channel(4);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_recv_with_holds_the_slot_until_done() {
+        let (tx, rx) = channel(1);
+        tx.send(String::from("large")).unwrap();
+        let len = rx.recv_with(|msg| {
+            assert_eq!(
+                tx.try_send(String::new()),
+                Err(TrySendError::Full(String::new()))
+            );
+            msg.len()
+        });
+        assert_eq!(len, Some(5));
+        assert_eq!(rx.recv_with(|msg| msg.len()), None);
+        tx.send(String::from("next")).unwrap();
+        assert_eq!(rx.try_recv().as_deref(), Ok("next"));
+
+        drop(tx);
+        assert_eq!(rx.recv_with(|msg| msg.len()), None);
+    }
+
+    #[test]
+    fn test_try_reserve_then_send() {
+        let (tx, rx) = channel(2);
+        let permit = tx.try_reserve().unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(tx.try_reserve().err(), Some(TrySendError::Full(())));
+        // The receiver waits at the reserved slot
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        permit.send(1);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn test_try_reserve_dropped_gives_the_slot_back() {
+        let (tx, rx) = channel(1);
+        drop(tx.try_reserve().unwrap());
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        // Once a later send has claimed a slot behind it, the dropped one is stepped over
+        let (tx, rx) = channel(2);
+        let permit = tx.try_reserve().unwrap();
+        tx.send(2).unwrap();
+        drop(permit);
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx.send(3).unwrap();
+        tx.send(4).unwrap();
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(rx.try_recv(), Ok(4));
+    }
+
+    #[test]
+    fn test_try_reserve_fails_like_try_send() {
+        let (tx, rx) = channel::<i32>(0);
+        assert_eq!(tx.try_reserve().err(), Some(TrySendError::Full(())));
+        rx.close();
+        assert_eq!(tx.try_reserve().err(), Some(TrySendError::Closed(())));
+        drop(rx);
+        assert_eq!(tx.try_reserve().err(), Some(TrySendError::Disconnected(())));
+    }
+
+    #[test]
+    fn test_send_ref_builds_the_value_in_place() {
+        let (tx, rx) = channel::<[u64; 64]>(2);
+        let mut slot = tx.send_ref().unwrap();
+        let frame = slot.as_uninit().as_mut_ptr().cast::<u64>();
+        for i in 0..64 {
+            unsafe { frame.add(i).write(i as u64) };
+        }
+        // Not visible until committed
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        unsafe { slot.commit() };
+        let frame = rx.try_recv().unwrap();
+        assert!(frame.iter().enumerate().all(|(i, &v)| v == i as u64));
+
+        let (tx, _rx) = channel::<u8>(0);
+        assert!(matches!(tx.send_ref(), Err(TrySendError::Full(()))));
+    }
+
+    #[test]
+    fn test_send_ref_panic_rolls_back() {
+        let (tx, rx) = channel::<String>(2);
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut slot = tx.send_ref().unwrap();
+            slot.as_uninit().write(String::from("half built"));
+            panic!("construction failed");
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        // Both slots are usable again
+        tx.send(String::from("a")).unwrap();
+        tx.send(String::from("b")).unwrap();
+        assert_eq!(rx.try_recv().as_deref(), Ok("a"));
+        assert_eq!(rx.try_recv().as_deref(), Ok("b"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_permits_from_many_threads() {
+        const THREADS: usize = 4;
+        const PER_THREAD: usize = 500;
+
+        let (tx, rx) = channel(8);
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_THREAD {
+                        // Every other value first takes a permit and gives it back unused
+                        let mut give_back = i % 2 == 0;
+                        loop {
+                            match tx.try_reserve() {
+                                Ok(permit) if give_back => {
+                                    drop(permit);
+                                    give_back = false;
+                                }
+                                Ok(permit) => {
+                                    permit.send((t, i));
+                                    break;
+                                }
+                                Err(_) => thread::yield_now(),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut next = [0; THREADS];
+        for (t, i) in rx {
+            assert_eq!(i, next[t]);
+            next[t] += 1;
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(next, [PER_THREAD; THREADS]);
+    }
+
+    #[test]
+    fn test_permit_taken_before_close_is_still_delivered() {
+        let (tx, rx) = channel(2);
+        let permit = tx.try_reserve().unwrap();
+        rx.close();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        permit.send(1);
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_send_full_returns_value() {
+        let (tx, _rx) = channel(1);
+        tx.send(1).unwrap();
+        assert_eq!(tx.send(2), Err(SendError(2)));
+    }
+
+    #[test]
+    fn test_send_after_receiver_dropped() {
+        let (tx, rx) = channel(4);
+        drop(rx);
+        assert_eq!(tx.send(7), Err(SendError(7)));
+        assert_eq!(tx.try_send(8), Err(TrySendError::Disconnected(8)));
+    }
+
+    #[test]
+    fn test_close_then_drain() {
+        let (tx, rx) = channel(4);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        rx.close();
+        assert!(tx.is_closed());
+
+        // Buffered values survive the close; only then does the channel end
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_close_and_clear_drops_buffered_values() {
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        #[derive(Debug)]
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel(2);
+        tx.send(Counted(Arc::clone(&dropped))).unwrap();
+        tx.send(Counted(Arc::clone(&dropped))).unwrap();
+        let blocked = {
+            let (tx, dropped) = (tx.clone(), Arc::clone(&dropped));
+            thread::spawn(move || tx.send_blocking(Counted(dropped)))
+        };
+        thread::sleep(Duration::from_millis(20));
+
+        rx.close_and_clear();
+        assert_eq!(dropped.load(Relaxed), 2);
+        // The parked sender gets its value back rather than having it dropped
+        let SendError(back) = blocked.join().unwrap().unwrap_err();
+        assert_eq!(dropped.load(Relaxed), 2);
+        drop(back);
+        assert_eq!(dropped.load(Relaxed), 3);
+
+        assert!(matches!(
+            tx.try_send(Counted(Arc::clone(&dropped))),
+            Err(TrySendError::Closed(_))
+        ));
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+    }
+
+    #[test]
+    fn test_send_after_close() {
+        let (tx, rx) = channel(4);
+        tx.close();
+        assert!(rx.is_closed());
+        assert_eq!(tx.try_send(1), Err(TrySendError::Closed(1)));
+        assert_eq!(tx.send(2), Err(SendError(2)));
+        assert_eq!(tx.send_blocking(3), Err(SendError(3)));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_close_wakes_parked_sender_and_receiver() {
+        let (tx, rx) = channel(1);
+        tx.send(0).unwrap();
+        let blocked = {
+            let tx = tx.clone();
+            thread::spawn(move || tx.send_blocking(1))
+        };
+        thread::sleep(Duration::from_millis(20));
+        tx.close();
+        assert_eq!(blocked.join().unwrap(), Err(SendError(1)));
+
+        let (tx, rx2) = channel::<u32>(1);
+        let receiver = thread::spawn(move || rx2.recv());
+        thread::sleep(Duration::from_millis(20));
+        tx.close();
+        assert_eq!(receiver.join().unwrap(), Err(RecvError));
+        assert_eq!(rx.recv(), Ok(0));
+    }
+
+    #[test]
+    fn test_send_all_reports_partial_failure() {
+        let (tx, rx) = channel(4);
+        assert_eq!(tx.send_all(0..10), Err((4, SendError(4))));
+        assert_eq!(rx.drain(), [0, 1, 2, 3]);
+        assert_eq!(tx.send_all(4..7), Ok(()));
+        assert_eq!(rx.drain(), [4, 5, 6]);
+
+        drop(rx);
+        assert_eq!(tx.send_all([7, 8]), Err((0, SendError(7))));
+    }
+
+    #[test]
+    fn test_try_send_full() {
+        let (tx, rx) = channel(1);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(tx.try_send(3), Ok(()));
+    }
+
+    #[test]
+    fn test_recv_disconnected_after_drain() {
+        let (tx, rx) = channel(4);
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        tx2.send(2).unwrap();
+        drop(tx2);
+
+        // Values sent before the last sender dropped are still delivered
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_sees_last_item_from_other_thread() {
+        for _ in 0..100 {
+            let (tx, rx) = channel(1);
+            let handle = thread::spawn(move || tx.send(42).unwrap());
+            assert_eq!(rx.recv(), Ok(42));
+            assert_eq!(rx.recv(), Err(RecvError));
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_sender_clone_drop_churn() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 100;
+
+        let (tx, rx) = channel(8);
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..ROUNDS {
+                        let clone = tx.clone();
+                        clone.send_blocking(t * ROUNDS + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = 0;
+        while rx.recv().is_ok() {
+            received += 1;
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(received, THREADS * ROUNDS);
+        assert!(rx.inner.is_disconnected());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_weak_sender() {
+        let (tx, rx) = channel(4);
+        let weak = tx.downgrade();
+
+        let upgraded = weak.upgrade().unwrap();
+        upgraded.send(1).unwrap();
+        drop(upgraded);
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        drop(tx);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        drop(rx);
+        assert!(weak.clone().upgrade().is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_cloned_senders_across_threads() {
+        const SENDERS: usize = 4;
+        const ITEMS: usize = 100;
+
+        let (tx, rx) = channel(SENDERS * ITEMS);
+        let handles: Vec<_> = (0..SENDERS)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..ITEMS {
+                        tx.send(t * ITEMS + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Ok(value) = rx.recv() {
+            received.push(value);
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        received.sort_unstable();
+        assert_eq!(received, (0..SENDERS * ITEMS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_parks_until_send() {
+        let (tx, rx) = channel(4);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(5).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        });
+        assert_eq!(rx.recv(), Ok(5));
+        // The sender is dropped while the receiver is parked
+        assert_eq!(rx.recv(), Err(RecvError));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_timeout() {
+        let (tx, rx) = channel(4);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(9).unwrap();
+        });
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_millis(100)), Ok(9));
+        handle.join().unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_deadline() {
+        let (tx, rx) = channel(4);
+        let past = Instant::now();
+
+        // A passed deadline still picks up what is already buffered
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_deadline(past), Ok(1));
+        assert_eq!(rx.recv_deadline(past), Err(RecvTimeoutError::Timeout));
+
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(2).unwrap();
+        });
+        let deadline = Instant::now() + Duration::from_millis(500);
+        assert_eq!(rx.recv_deadline(deadline), Ok(2));
+        handle.join().unwrap();
+        assert_eq!(rx.recv_deadline(past), Err(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn test_peek_then_conditionally_receive() {
+        let (tx, mut rx) = channel(4);
+        assert_eq!(rx.peek(), None);
+
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        if rx.peek() == Some(&1) {
+            assert_eq!(rx.try_recv(), Ok(1));
+        }
+        assert_eq!(rx.peek(), Some(&2));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.peek(), None);
+    }
+
+    #[test]
+    fn test_try_iter_drains_available() {
+        let (tx, rx) = channel(8);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.try_iter().count(), 5);
+        assert_eq!(rx.try_iter().next(), None);
+
+        tx.send(5).unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![5]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_into_iter_ends_on_disconnect() {
+        let (tx, rx) = channel(8);
+        let handles: Vec<_> = (0..2)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..4 {
+                        tx.send(t * 4 + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received: Vec<_> = rx.into_iter().collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_send_blocking_waits_for_room() {
+        let (tx, rx) = channel(1);
+        tx.send(0).unwrap();
+        let handle = thread::spawn(move || {
+            for i in 1..50 {
+                tx.send_blocking(i).unwrap();
+            }
+        });
+        for i in 0..50 {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_send_blocking_fails_for_every_parked_sender_once_receiver_dropped() {
+        let (tx, rx) = channel(1);
+        tx.send(0).unwrap();
+        let handles: Vec<_> = (1..=3)
+            .map(|i| {
+                let tx = tx.clone();
+                thread::spawn(move || tx.send_blocking(i))
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+
+        let mut returned: Vec<_> = handles
+            .into_iter()
+            .map(|h| h.join().unwrap().unwrap_err().into_inner())
+            .collect();
+        returned.sort_unstable();
+        assert_eq!(returned, [1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_batch_takes_what_is_ready() {
+        let (tx, rx) = channel(8);
+        for i in 0..6 {
+            tx.send(i).unwrap();
+        }
+        let mut out = vec![99];
+        assert_eq!(rx.recv_batch(4, &mut out), Ok(4));
+        assert_eq!(rx.recv_batch(4, &mut out), Ok(2));
+        assert_eq!(out, [99, 0, 1, 2, 3, 4, 5]);
+
+        // Senders going away while the receiver waits ends the batch with an error
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(tx);
+        });
+        assert_eq!(rx.recv_batch(4, &mut out), Err(RecvError));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_until_disconnect_with_blocked_producers() {
+        const PRODUCERS: usize = 3;
+        const PER_PRODUCER: usize = 200;
+        // Small enough that producers keep parking on a full channel
+        let (tx, rx) = channel(2);
+        let handles: Vec<_> = (0..PRODUCERS)
+            .map(|_| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        tx.send_blocking(i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut calls = 0;
+        let mut sum = 0;
+        rx.recv_until_disconnect(|i| {
+            calls += 1;
+            sum += i;
+        });
+        assert_eq!(calls, PRODUCERS * PER_PRODUCER);
+        assert_eq!(sum, PRODUCERS * (PER_PRODUCER * (PER_PRODUCER - 1) / 2));
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // Returns straight away on a channel that has already ended
+        rx.recv_until_disconnect(|_| unreachable!());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_many_timeout_flushes_on_time_or_size() {
+        let (tx, rx) = channel(8);
+        let mut out = Vec::new();
+        assert_eq!(
+            rx.recv_many_timeout(4, Duration::from_millis(10), &mut out),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert!(out.is_empty());
+
+        // Values arriving before the deadline end the wait, up to `max` of them
+        let producer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send_all(0..6).unwrap();
+            tx
+        });
+        let first = rx
+            .recv_many_timeout(4, Duration::from_secs(5), &mut out)
+            .unwrap();
+        assert!((1..=4).contains(&first));
+        let tx = producer.join().unwrap();
+        while out.len() < 6 {
+            rx.recv_many_timeout(4, Duration::ZERO, &mut out).unwrap();
+        }
+        assert_eq!(out, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(rx.recv_many_timeout(0, Duration::ZERO, &mut out), Ok(0));
+
+        drop(tx);
+        assert_eq!(
+            rx.recv_many_timeout(4, Duration::from_millis(10), &mut out),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_wait_for_blocks_until_the_threshold() {
+        let (tx, rx) = channel(8);
+        assert_eq!(rx.wait_for(0), Ok(()));
+        // More values than fit at once can never be waited for
+        assert_eq!(rx.wait_for(9), Err(RecvError));
+        assert_eq!(channel::<i32>(0).1.wait_for(1), Err(RecvError));
+
+        let producer = thread::spawn(move || {
+            for i in 0..6 {
+                thread::sleep(Duration::from_millis(2));
+                tx.send(i).unwrap();
+            }
+            tx
+        });
+        assert_eq!(rx.wait_for(6), Ok(()));
+        let mut out = Vec::new();
+        assert_eq!(rx.recv_batch(6, &mut out), Ok(6));
+        assert_eq!(out, [0, 1, 2, 3, 4, 5]);
+
+        // Once the senders are gone only what is already queued counts
+        let tx = producer.join().unwrap();
+        tx.send(6).unwrap();
+        drop(tx);
+        assert_eq!(rx.wait_for(2), Err(RecvError));
+        assert_eq!(rx.wait_for(1), Ok(()));
+        assert_eq!(rx.recv(), Ok(6));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_rendezvous_send_waits_for_recv() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+        let (tx, rx) = channel(0);
+        let sent = Arc::new(AtomicBool::new(false));
+        let producer = {
+            let sent = Arc::clone(&sent);
+            thread::spawn(move || {
+                tx.send_blocking(1).unwrap();
+                sent.store(true, SeqCst);
+                tx.send_blocking(2).unwrap();
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        // Nothing is buffered: the sender is still waiting for a receiver
+        assert!(!sent.load(SeqCst));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        producer.join().unwrap();
+        assert!(sent.load(SeqCst));
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_rendezvous_try_send_needs_a_waiting_receiver() {
+        let (tx, rx) = channel(0);
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+
+        let receiver = thread::spawn(move || rx.recv());
+        let mut data = 2;
+        while let Err(TrySendError::Full(back)) = tx.try_send(data) {
+            data = back;
+            thread::yield_now();
+        }
+        assert_eq!(receiver.join().unwrap(), Ok(2));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_rendezvous_many_senders_each_handed_over_once() {
+        let (tx, rx) = channel(0);
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..50 {
+                        tx.send_blocking(t * 50 + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received: Vec<_> = core::iter::from_fn(|| rx.recv().ok()).collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..200).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_rendezvous_sender_gets_value_back_when_receiver_dropped() {
+        let (tx, rx) = channel(0);
+        let producer = thread::spawn(move || tx.send_blocking(7));
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(producer.join().unwrap(), Err(SendError(7)));
+    }
+
+    #[test]
+    fn test_drain_preserves_order_across_wrap() {
+        let (tx, rx) = channel(4);
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.recv(), Ok(0));
+        assert_eq!(rx.recv(), Ok(1));
+        // These wrap around the end of the ring
+        for i in 3..6 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.drain(), vec![2, 3, 4, 5]);
+        assert!(rx.drain().is_empty());
+    }
+
+    /// A waker that does nothing, built by hand as a custom executor would.
+    fn noop_waker() -> std::task::Waker {
+        use std::task::{RawWaker, RawWakerVTable, Waker};
+
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(
+            |_| RawWaker::new(core::ptr::null(), &VTABLE),
+            |_| {},
+            |_| {},
+            |_| {},
+        );
+        // SAFETY: every function of the vtable ignores the data pointer.
+        unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_poll_recv_with_a_noop_waker() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (tx, rx) = channel(4);
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+
+        let producer = thread::spawn(move || {
+            for i in 0..10 {
+                tx.send_blocking(i).unwrap();
+            }
+        });
+        let mut received = Vec::new();
+        loop {
+            match rx.poll_recv(&mut cx) {
+                Poll::Ready(Some(value)) => received.push(value),
+                Poll::Ready(None) => break,
+                Poll::Pending => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        use std::sync::Arc;
+        use std::task::{Wake, Waker};
+
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let mut fut = std::pin::pin!(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_send_async_waits_for_room() {
+        let (tx, rx) = channel(1);
+        tx.send(1).unwrap();
+
+        let handle = thread::spawn(move || block_on(tx.send_async(2)));
+        thread::sleep(Duration::from_millis(20));
+        assert!(!handle.is_finished());
+
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(handle.join().unwrap(), Ok(()));
+        assert_eq!(rx.recv(), Ok(2));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_send_async_rendezvous_completes_once_receiver_waits() {
+        let (tx, rx) = channel(0);
+        let handle = thread::spawn(move || block_on(tx.send_async(1)));
+        thread::sleep(Duration::from_millis(20));
+        assert!(!handle.is_finished());
+
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(handle.join().unwrap(), Ok(()));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_send_async_fails_once_receiver_dropped() {
+        let (tx, rx) = channel(1);
+        tx.send(1).unwrap();
+
+        let handle = thread::spawn(move || block_on(tx.send_async(2)));
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(handle.join().unwrap(), Err(SendError(2)));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_send_async_many_producers() {
+        const SENDERS: usize = 4;
+        const ITEMS: usize = 200;
+
+        let (tx, rx) = channel(2);
+        let handles: Vec<_> = (0..SENDERS)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    block_on(async {
+                        for i in 0..ITEMS {
+                            tx.send_async(t * ITEMS + i).await.unwrap();
+                        }
+                    })
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received: Vec<_> = rx.into_iter().collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..SENDERS * ITEMS).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_send_async_completed_without_wake_leaves_the_wait_list() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::task::{Wake, Waker};
+
+        struct CountingWaker(AtomicUsize);
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let (tx, rx) = channel(1);
+        tx.send(0).unwrap();
+        let counters: Vec<_> = (0..3)
+            .map(|_| Arc::new(CountingWaker(AtomicUsize::new(0))))
+            .collect();
+        let wakers: Vec<_> = counters
+            .iter()
+            .map(|c| Waker::from(Arc::clone(c)))
+            .collect();
+        let mut futs: Vec<_> = (1..4).map(|i| tx.send_async(i)).collect();
+        let mut poll = |i: usize| {
+            Pin::new(&mut futs[i]).poll(&mut Context::from_waker(&wakers[i])) == Poll::Ready(Ok(()))
+        };
+        let (x, y, a) = (0, 1, 2);
+        assert!(!poll(x) && !poll(y) && !poll(a));
+
+        assert_eq!(rx.try_recv(), Ok(0));
+        assert_eq!(counters[x].0.load(Ordering::Relaxed), 1);
+        // `a` takes the freed slot before `x` gets around to it, without ever being woken
+        assert!(poll(a));
+        assert!(!poll(x) && !poll(y));
+
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(counters[y].0.load(Ordering::Relaxed), 1);
+        assert!(poll(y));
+        assert_eq!(rx.try_recv(), Ok(2));
+        // The next wake-up goes to `x`, the one still waiting, not to `a`'s stale waker
+        assert_eq!(counters[x].0.load(Ordering::Relaxed), 2);
+        assert_eq!(counters[a].0.load(Ordering::Relaxed), 0);
+        assert!(poll(x));
+        assert_eq!(rx.try_recv(), Ok(1));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_async_recv_wakes_on_send_and_ends_on_disconnect() {
+        let (tx, rx) = channel(4);
+        let mut rx = rx.into_async();
+        let handle = thread::spawn(move || {
+            for i in 0..3 {
+                thread::sleep(Duration::from_millis(5));
+                tx.send(i).unwrap();
+            }
+        });
+
+        let received = block_on(async {
+            let mut received = Vec::new();
+            while let Some(value) = rx.recv().await {
+                received.push(value);
+            }
+            received
+        });
+        handle.join().unwrap();
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+}
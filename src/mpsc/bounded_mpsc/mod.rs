@@ -1,2 +1,18 @@
+mod allocator;
+mod channel;
+mod raw_mpmc;
 mod raw_mpsc;
+mod scoped;
 mod slot_arr;
+mod spsc;
+
+pub use allocator::{Alloc, Global};
+#[cfg(feature = "std")]
+pub use channel::IntoIter;
+#[cfg(feature = "async")]
+pub use channel::{AsyncReceiver, Recv, SendFuture};
+pub use channel::{Receiver, ReservedSlot, SendPermit, Sender, TryIter, WeakSender, channel};
+pub use raw_mpmc::RawMpmc;
+pub use raw_mpsc::{PRODUCER_RUN, ProducerHandle, RawIntoIter, RawMpsc};
+pub use scoped::{Consumer, Producer};
+pub use spsc::{Spsc, SpscConsumer, SpscProducer};
@@ -0,0 +1,396 @@
+//! A low-level bounded Multi-Producer Multi-Consumer (MPMC) queue.
+//!
+//! The sibling of [`RawMpsc`](super::RawMpsc) for when more than one thread pops. Consumers
+//! claim `tail` with a CAS, exactly like producers claim `next_head`, and the per-slot state
+//! keeps a slot's two users apart once indices have been handed out:
+//!
+//! - a consumer may claim a slot whose producer is still writing it, and waits for the slot
+//!   to become `REGISTERED` before taking the value;
+//! - a producer may claim a slot whose previous value is still being read by the consumer
+//!   that claimed it one lap earlier, and waits for the slot to be `READY` again.
+//!
+//! Both sides move a slot through `RESERVED` with a CAS, so a value is only ever written into
+//! an empty slot and only ever taken once.
+//!
+//! Unlike in the single-consumer queue, `next_head` and `tail` count up without wrapping at
+//! the slot count, and are only reduced to a slot when one is claimed. A thread that read an
+//! index and was preempted while both sides went all the way around the ring then fails its
+//! CAS, instead of claiming a slot that the index no longer stands for.
+//!
+//! Two consumers a lap apart can claim the same slot before the first has read it; whichever
+//! takes the value first gets it, and the other waits for the next one written there. Every
+//! value is still taken exactly once.
+
+use core::sync::atomic::Ordering::{AcqRel, Acquire};
+
+use super::slot_arr::{SlotArr, slots_for};
+use crate::{
+    backoff::{Backoff, GlobalBackoff, LocalBackoff},
+    cache_padded::CachePadded,
+    mpsc::sync::AtomicUsize,
+};
+
+/// Number of backoff rounds spent waiting on the other side of a slot before yielding.
+const SLOT_SPIN_ROUNDS: u32 = 64;
+
+/// A bounded lock-free multi-producer multi-consumer (MPMC) queue.
+///
+/// Unlike [`RawMpsc`](super::RawMpsc), any number of threads may call [`pop`](Self::pop)
+/// concurrently. Values are never lost or delivered twice, but values pushed by different
+/// producers, or popped by different consumers, are not ordered with respect to each other.
+pub struct RawMpmc<T, B = GlobalBackoff> {
+    /// The next index to be claimed by producers.
+    next_head: CachePadded<AtomicUsize>,
+    /// The next index to be claimed by consumers.
+    tail: CachePadded<AtomicUsize>,
+    /// Backoff shared by producers and consumers losing a CAS.
+    backoff: CachePadded<B>,
+    /// Internal storage array for queue slots.
+    slots: SlotArr<T>,
+}
+
+impl<T> RawMpmc<T> {
+    /// Creates a new bounded MPMC queue with the given capacity.
+    ///
+    /// Internally allocates `capacity + 1` slots, so a producer only reuses a slot claimed by
+    /// a consumer at least two pops back, rather than the one the latest pop may still be
+    /// reading.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_backoff(capacity)
+    }
+}
+
+impl<T, B: Backoff> RawMpmc<T, B> {
+    /// Creates a new bounded MPMC queue with the given capacity whose producers and consumers
+    /// back off with `B`.
+    pub fn with_backoff(capacity: usize) -> Self {
+        Self {
+            next_head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            backoff: CachePadded::new(B::new()),
+            slots: SlotArr::new(slots_for(capacity)),
+        }
+    }
+
+    /// Attempts to push data into the queue.
+    ///
+    /// Returns `Ok(())` if the push succeeded, or returns the original `data` back
+    /// in `Err(data)` if the queue is full.
+    pub fn push(&self, data: T) -> Result<(), T> {
+        // A stale `head` can trail `tail`; the difference is then negative, and the CAS fails.
+        let full = |head: usize, tail| head.wrapping_sub(tail) as isize >= self.capacity() as isize;
+        let Some(idx) = self.claim(&self.next_head, &self.tail, full) else {
+            return Err(data);
+        };
+
+        // The consumer of the previous lap may still be reading this slot.
+        let mut data = Some(data);
+        wait_for(|| match self.slots.set(idx, data.take()?) {
+            Ok(()) => Some(()),
+            Err(back) => {
+                data = Some(back);
+                None
+            }
+        });
+        Ok(())
+    }
+
+    /// Attempts to pop a value from the queue.
+    ///
+    /// Returns `Some(T)` if a value was available, or `None` if the queue is empty. Safe to
+    /// call from any number of threads at once.
+    pub fn pop(&self) -> Option<T> {
+        let idx = self.claim(&self.tail, &self.next_head, |tail, head| tail == head)?;
+        // The producer that claimed this slot may still be writing it.
+        Some(wait_for(|| self.slots.unset(idx).ok()))
+    }
+
+    /// Advances `index` (`next_head` or `tail`) by one with a CAS and returns the slot it
+    /// claimed, or `None` if `blocked(index, other)` says there is nothing to claim.
+    ///
+    /// `index` is loaded before `other`, so a consumer never sees `tail` ahead of `head`.
+    fn claim(
+        &self,
+        index: &AtomicUsize,
+        other: &AtomicUsize,
+        blocked: impl Fn(usize, usize) -> bool,
+    ) -> Option<usize> {
+        unsafe { self.backoff.register() };
+        let claimed = loop {
+            let curr = index.load(Acquire);
+            if blocked(curr, other.load(Acquire)) {
+                break None;
+            }
+            match index.compare_exchange(curr, curr.wrapping_add(1), AcqRel, Acquire) {
+                Ok(_) => {
+                    self.backoff.reset();
+                    break Some(curr % self.slots.capacity);
+                }
+                Err(_) => self.backoff.wait(),
+            }
+        };
+        unsafe { self.backoff.unregister() };
+        claimed
+    }
+
+    /// Returns the number of values currently buffered in the queue.
+    ///
+    /// This is a best-effort snapshot, and counts slots that are claimed but not yet written
+    /// or not yet read.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+        // Both may have moved on between the loads, past as many as `capacity` apart.
+        head.wrapping_sub(tail).min(self.capacity())
+    }
+
+    /// Returns `true` if the queue holds no values.
+    ///
+    /// Like [`len`](Self::len), this is only a snapshot under concurrent access.
+    pub fn is_empty(&self) -> bool {
+        self.tail.load(Acquire) == self.next_head.load(Acquire)
+    }
+
+    /// Returns the maximum number of values the queue can hold, as passed to
+    /// [`new`](Self::new).
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity - 1
+    }
+}
+
+/// Retries `attempt` until it succeeds, backing off and then yielding in between.
+///
+/// Used where the other side of a slot has already claimed it and is only a few instructions
+/// away from finishing with it.
+fn wait_for<R>(mut attempt: impl FnMut() -> Option<R>) -> R {
+    let backoff = LocalBackoff::new();
+    let mut rounds = 0;
+    loop {
+        if let Some(done) = attempt() {
+            return done;
+        }
+        // Under loom every retry has to yield, or the model never schedules the other side.
+        if rounds < SLOT_SPIN_ROUNDS && !cfg!(all(loom, test)) {
+            backoff.wait();
+            rounds += 1;
+        } else {
+            #[cfg(any(feature = "std", all(loom, test)))]
+            crate::mpsc::sync::yield_now();
+            #[cfg(not(any(feature = "std", all(loom, test))))]
+            backoff.wait();
+        }
+    }
+}
+
+impl<T, B> Drop for RawMpmc<T, B> {
+    /// Drops the queue and all remaining values in it.
+    fn drop(&mut self) {
+        for idx in 0..self.slots.capacity {
+            drop(self.slots.unset(idx));
+        }
+    }
+}
+
+// SAFETY: values move between threads through the slots, so both need `T: Send`.
+unsafe impl<T: Send, B: Send> Send for RawMpmc<T, B> {}
+unsafe impl<T: Send, B: Sync> Sync for RawMpmc<T, B> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backoff::YieldBackoff;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn test_push_pop_and_full() {
+        let q = RawMpmc::new(3);
+        assert_eq!(q.capacity(), 3);
+        assert_eq!(q.pop(), None);
+
+        for round in 0..5 {
+            for i in 0..3 {
+                assert!(q.push(round * 10 + i).is_ok());
+            }
+            assert_eq!(q.push(99), Err(99));
+            assert_eq!(q.len(), 3);
+            for i in 0..3 {
+                assert_eq!(q.pop(), Some(round * 10 + i));
+            }
+            assert!(q.is_empty());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_multi_consumer_delivers_each_value_once() {
+        const PRODUCERS: usize = 3;
+        const CONSUMERS: usize = 3;
+        const MSGS_PER_PRODUCER: usize = 5_000;
+
+        let q = Arc::new(RawMpmc::<usize, YieldBackoff>::with_backoff(8));
+        let done = Arc::new(AtomicBool::new(false));
+        let seen = Arc::new(Mutex::new(HashSet::new()));
+
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let (q, done, seen) = (Arc::clone(&q), Arc::clone(&done), Arc::clone(&seen));
+                thread::spawn(move || {
+                    let mut local = Vec::new();
+                    loop {
+                        match q.pop() {
+                            Some(value) => local.push(value),
+                            None if done.load(Relaxed) && q.is_empty() => break,
+                            None => thread::yield_now(),
+                        }
+                    }
+                    let mut seen = seen.lock().unwrap();
+                    for value in local {
+                        assert!(seen.insert(value), "value {value} delivered twice");
+                    }
+                })
+            })
+            .collect();
+
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    for i in 0..MSGS_PER_PRODUCER {
+                        let mut value = p * MSGS_PER_PRODUCER + i;
+                        while let Err(back) = q.push(value) {
+                            value = back;
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for p in producers {
+            p.join().unwrap();
+        }
+        done.store(true, Relaxed);
+        for c in consumers {
+            c.join().unwrap();
+        }
+        assert_eq!(seen.lock().unwrap().len(), PRODUCERS * MSGS_PER_PRODUCER);
+    }
+
+    struct DropCounter<'a>(&'a AtomicUsize);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_drop_releases_remaining_items() {
+        let dropped = AtomicUsize::new(0);
+        {
+            let q = RawMpmc::new(4);
+            for _ in 0..4 {
+                assert!(q.push(DropCounter(&dropped)).is_ok());
+            }
+            drop(q.pop());
+            assert_eq!(dropped.load(Relaxed), 1);
+        }
+        assert_eq!(dropped.load(Relaxed), 4);
+    }
+}
+
+/// Model-checked tests, run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+#[cfg(all(loom, test))]
+mod loom_tests {
+    use super::*;
+    use crate::backoff::SpinOnly;
+    use loom::model::Builder;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn loom_two_consumers_never_share_a_value() {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let q = Arc::new(RawMpmc::<usize, SpinOnly>::with_backoff(1));
+            assert!(q.push(0).is_ok());
+
+            let producer = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.push(1).err())
+            };
+            let consumer = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.pop())
+            };
+
+            let mut values: Vec<_> = q.pop().into_iter().collect();
+            values.extend(consumer.join().unwrap());
+            values.extend(producer.join().unwrap());
+            values.extend(core::iter::from_fn(|| q.pop()));
+            values.sort_unstable();
+            assert_eq!(values, [0, 1]);
+        });
+    }
+
+    #[test]
+    fn loom_consumer_preempted_across_a_lap() {
+        // A consumer may read `tail` and `head`, lose the CPU, and only CAS `tail` once the
+        // other consumer and the producer have gone all the way around the ring.
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let q = Arc::new(RawMpmc::<usize, SpinOnly>::with_backoff(1));
+            assert!(q.push(0).is_ok());
+
+            let consumer = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.pop())
+            };
+
+            let mut values: Vec<_> = q.pop().into_iter().collect();
+            let mut value = 1;
+            while let Err(back) = q.push(value) {
+                value = back;
+                thread::yield_now();
+            }
+            values.extend(q.pop());
+            values.extend(consumer.join().unwrap());
+            values.extend(core::iter::from_fn(|| q.pop()));
+            values.sort_unstable();
+            assert_eq!(values, [0, 1]);
+            assert!(q.push(2).is_ok());
+            assert_eq!(q.pop(), Some(2));
+        });
+    }
+
+    #[test]
+    fn loom_producer_preempted_across_a_lap() {
+        // The mirror image: a producer reads `head` and `tail` of the empty queue and only
+        // CASes `head` once another producer has filled the ring and gone around it.
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let q = Arc::new(RawMpmc::<usize, SpinOnly>::with_backoff(1));
+            let producer = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.push(2).err())
+            };
+
+            let mut values = Vec::new();
+            for value in 0..2 {
+                values.extend(q.push(value).err());
+                values.extend(q.pop());
+            }
+            values.extend(producer.join().unwrap());
+            values.extend(core::iter::from_fn(|| q.pop()));
+            values.sort_unstable();
+            assert_eq!(values, [0, 1, 2]);
+        });
+    }
+}
@@ -5,47 +5,197 @@
 //! to pop data.
 //!
 //! Internally, it uses an array of slots with atomic head and tail indices, along
-//! with an exponential backoff strategy to handle contention efficiently.
+//! with a pluggable [`Backoff`] strategy to handle contention efficiently.
 
-use std::sync::atomic::Ordering::{AcqRel, Acquire, Release};
-use std::{fmt::Debug, mem::transmute, sync::atomic::AtomicUsize};
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
 
-use super::slot_arr::SlotArr;
-use crate::{backoff::GlobalBackoff, cache_padded::CachePadded};
+use super::allocator::{Alloc, Global};
+use super::scoped::{Consumer, Producer};
+use super::slot_arr::{SlotArr, capacity_overflow, slots_for, try_slots_for};
+#[cfg(feature = "std")]
+use crate::mpsc::notify::SendNotify;
+#[cfg(feature = "metrics")]
+use crate::mpsc::stats::{QueueCounters, QueueStats};
+use crate::{
+    backoff::{Backoff, GlobalBackoff, LocalBackoff},
+    cache_padded::CachePadded,
+    mpsc::errors::{AllocError, TryRecvError, TrySendError},
+    mpsc::queue::{Ack, Queue},
+    mpsc::snapshot::QueueSnapshot,
+    mpsc::sync::{AtomicUsize, fence},
+};
+
+/// Capacity of a queue made with `Default`.
+const DEFAULT_CAPACITY: usize = 16;
+
+/// Number of backoff rounds `pop` spends waiting on an in-flight write before yielding.
+const POP_SPIN_ROUNDS: u32 = 64;
 
 /// A bounded lock-free multi-producer single-consumer (MPSC) queue.
 ///
 /// `RawMpsc<T>` supports multiple threads concurrently pushing elements
 /// while allowing only one thread to pop elements. It uses a ring buffer internally,
-/// and contention is reduced using a global exponential backoff strategy.
+/// and producers that lose a race for a slot wait according to the backoff strategy `B`,
+/// a contention-scaled [`GlobalBackoff`] unless another is chosen with
+/// [`with_backoff`](Self::with_backoff).
 ///
 /// This is a low-level primitive used by higher-level channel abstractions.
-pub struct RawMpsc<T> {
+///
+/// # Thread safety
+///
+/// The queue is `Send` and `Sync` only when `T: Send`, as std's channels are: a value pushed
+/// on one thread is popped on another. A queue of `Rc`s therefore can't be shared between
+/// threads; [`LocalMpsc`](crate::mpsc::local::LocalMpsc) is the queue for those.
+///
+/// ```compile_fail
+/// use lock_free_mpsc::mpsc::bounded_mpsc::RawMpsc;
+/// use std::rc::Rc;
+///
+/// let q = RawMpsc::<Rc<i32>>::new(4);
+/// std::thread::scope(|s| {
+///     s.spawn(|| q.push(Rc::new(1)));
+/// });
+/// ```
+pub struct RawMpsc<T, B = GlobalBackoff, A: Alloc = Global> {
     /// The next index to be pushed to by producers.
     next_head: CachePadded<AtomicUsize>,
     /// The next index to be popped by the single consumer.
     tail: CachePadded<AtomicUsize>,
-    /// Global exponential backoff to reduce contention during CAS failure.
-    global_wait: CachePadded<GlobalBackoff>,
+    /// Backoff shared by the producers to reduce contention during CAS failure.
+    backoff: CachePadded<B>,
     /// Internal storage array for queue slots.
-    slots: SlotArr<T>,
+    slots: SlotArr<T, A>,
+    /// Producers parked in [`push_blocking`](Self::push_blocking) until a slot frees up.
+    #[cfg(feature = "std")]
+    producers: SendNotify,
+    /// Counters reported by [`stats`](Self::stats).
+    #[cfg(feature = "metrics")]
+    metrics: QueueCounters,
+    /// The logical capacity requested in `new`, one less than the number of slots.
+    capacity: usize,
+    /// `slots.capacity - 1` when the slot count is a power of two, letting indices wrap
+    /// with a single `&`.
+    mask: Option<usize>,
 }
 
-impl<T: Debug> RawMpsc<T> {
+impl<T> RawMpsc<T> {
     /// Creates a new bounded MPSC queue with the given capacity.
     ///
     /// Internally allocates `capacity + 1` slots to avoid ambiguity between full and empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics with "capacity overflow" if the slots would take more than `isize::MAX` bytes.
     pub fn new(capacity: usize) -> Self {
-        let slots = SlotArr::new(capacity + 1);
+        Self::with_slots(slots_for(capacity))
+    }
+
+    /// Like [`new`](Self::new), but returns an error instead of panicking on an overflowing
+    /// capacity, or aborting when the allocation fails.
+    ///
+    /// Lets a caller that sizes queues from untrusted input, or runs close to its memory
+    /// limit, turn the failure away instead of going down with it.
+    pub fn try_new(capacity: usize) -> Result<Self, AllocError> {
+        Self::try_new_in(capacity, Global)
+    }
+
+    /// Creates a new bounded MPSC queue holding at least `capacity` values, with the slot
+    /// count rounded up to a power of two.
+    ///
+    /// Indices then wrap with a mask instead of a comparison. Because of the rounding,
+    /// [`capacity`](Self::capacity) may report more than was asked for.
+    ///
+    /// # Memory
+    ///
+    /// The ring keeps one slot empty, so it needs `capacity + 1` slots before rounding. A
+    /// `capacity` one below a power of two fits exactly, but a power of two itself does not:
+    /// asking for 8 values takes 16 slots, twice what `new(8)` allocates. Ask for `2^k - 1`
+    /// values to get a mask without the extra slots.
+    pub fn with_capacity_pow2(capacity: usize) -> Self {
+        let slot_count = slots_for(capacity)
+            .checked_next_power_of_two()
+            .unwrap_or_else(|| capacity_overflow());
+        Self::with_slots(slot_count)
+    }
+
+    /// Creates a queue holding the values of `snapshot`, in order.
+    ///
+    /// The capacity is the one recorded in the snapshot, raised to the number of values if
+    /// that is more, so every value fits.
+    pub fn from_snapshot(snapshot: QueueSnapshot<T>) -> Self {
+        let capacity = snapshot.capacity.unwrap_or(0).max(snapshot.items.len());
+        let queue = Self::new(capacity);
+        if queue.extend(snapshot.items) != 0 {
+            unreachable!("the queue was sized for every value");
+        }
+        queue
+    }
+}
+
+impl<T, B: Backoff> RawMpsc<T, B> {
+    /// Creates a new bounded MPSC queue with the given capacity whose producers back off
+    /// with `B`.
+    ///
+    /// The strategy is picked through the type, e.g.
+    /// `RawMpsc::<u32, YieldBackoff>::with_backoff(64)`.
+    pub fn with_backoff(capacity: usize) -> Self {
+        Self::with_backoff_in(capacity, Global)
+    }
+
+    fn with_slots(slot_count: usize) -> Self {
+        Self::with_slots_in(slot_count, Global)
+    }
+}
+
+impl<T, A: Alloc> RawMpsc<T, GlobalBackoff, A> {
+    /// Creates a new bounded MPSC queue with the given capacity whose slots are allocated
+    /// from `alloc`.
+    ///
+    /// Like [`new`](RawMpsc::new), this allocates `capacity + 1` slots, in a single block.
+    pub fn new_in(capacity: usize, alloc: A) -> Self {
+        Self::with_backoff_in(capacity, alloc)
+    }
+
+    /// Like [`new_in`](Self::new_in), but returns an error instead of panicking on an
+    /// overflowing capacity, or aborting when `alloc` fails.
+    pub fn try_new_in(capacity: usize, alloc: A) -> Result<Self, AllocError> {
+        let slots = SlotArr::try_new_in(try_slots_for(capacity)?, alloc)?;
+        Ok(Self::from_slots(slots))
+    }
+}
+
+impl<T, B: Backoff, A: Alloc> RawMpsc<T, B, A> {
+    /// Creates a new bounded MPSC queue with the given capacity whose producers back off
+    /// with `B` and whose slots are allocated from `alloc`.
+    pub fn with_backoff_in(capacity: usize, alloc: A) -> Self {
+        Self::with_slots_in(slots_for(capacity), alloc)
+    }
+
+    fn with_slots_in(slot_count: usize, alloc: A) -> Self {
+        Self::from_slots(SlotArr::new_in(slot_count, alloc))
+    }
+
+    fn from_slots(slots: SlotArr<T, A>) -> Self {
+        let slot_count = slots.capacity;
         let next_head = CachePadded::new(AtomicUsize::new(0));
         let tail = CachePadded::new(AtomicUsize::new(0));
-        let global_wait = CachePadded::new(GlobalBackoff::new());
+        let backoff = CachePadded::new(B::new());
+        let mask = slot_count.is_power_of_two().then(|| slot_count - 1);
 
         Self {
             next_head,
             tail,
-            global_wait,
+            backoff,
             slots,
+            capacity: slot_count - 1,
+            mask,
+            #[cfg(feature = "std")]
+            producers: SendNotify::new(),
+            #[cfg(feature = "metrics")]
+            metrics: QueueCounters::new(),
         }
     }
 
@@ -54,15 +204,10 @@ impl<T: Debug> RawMpsc<T> {
     /// Returns `Ok(())` if the push succeeded, or returns the original `data` back
     /// in `Err(data)` if the queue is full.
     pub fn push(&self, data: T) -> Result<(), T> {
-        unsafe { self.global_wait.reg_wait() };
+        unsafe { self.backoff.register() };
         let curr_head = loop {
             let curr_head = self.next_head.load(Acquire);
-            let next_head = curr_head + 1;
-
-            // Bounds the index to wrap around at capacity
-            let is_less =
-                unsafe { transmute::<isize, usize>(-((next_head < self.slots.capacity) as isize)) };
-            let next_head_bounded = next_head & is_less;
+            let next_head_bounded = self.next_index(curr_head);
 
             if next_head_bounded != self.tail.load(Acquire) {
                 match self
@@ -70,204 +215,2619 @@ impl<T: Debug> RawMpsc<T> {
                     .compare_exchange(curr_head, next_head_bounded, AcqRel, Acquire)
                 {
                     Ok(_) => {
-                        unsafe { self.global_wait.de_reg() };
+                        unsafe { self.backoff.unregister() };
+                        self.backoff.reset();
                         break curr_head;
                     }
-                    Err(_) => self.global_wait.wait(),
+                    Err(_) => {
+                        #[cfg(feature = "metrics")]
+                        self.metrics.cas_retried();
+                        self.backoff.wait();
+                    }
                 }
             } else {
-                unsafe { self.global_wait.de_reg() };
+                unsafe { self.backoff.unregister() };
+                #[cfg(feature = "metrics")]
+                self.metrics.rejected_full();
                 return Err(data);
             }
         };
 
-        self.slots.set(curr_head, data).unwrap(); // infallible under valid usage
+        if self.slots.set(curr_head, data).is_err() {
+            unreachable!("a claimed slot is always ready");
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.pushed(1);
         Ok(())
     }
 
-    /// Attempts to pop a value from the queue.
+    /// Attempts to push data with a single `compare_exchange_weak` on `next_head`, never
+    /// looping or backing off.
     ///
-    /// Returns `Some(T)` if a value was available, or `None` if the queue is empty.
-    pub fn pop(&self) -> Option<T> {
-        let tail = self.tail.load(Acquire);
-        let head = self.next_head.load(Acquire);
+    /// For producers that must not wait, such as real-time audio threads, and would rather
+    /// drop or retry on their own terms. **`Err(data)` does not necessarily mean the queue is
+    /// full**: it is also returned when another producer won the race for the slot, or when
+    /// the weak CAS fails spuriously.
+    pub fn try_push_weak(&self, data: T) -> Result<(), T> {
+        let curr_head = self.next_head.load(Acquire);
+        let next_head = self.next_index(curr_head);
+        if next_head == self.tail.load(Acquire) {
+            #[cfg(feature = "metrics")]
+            self.metrics.rejected_full();
+            return Err(data);
+        }
+        if self
+            .next_head
+            .compare_exchange_weak(curr_head, next_head, AcqRel, Relaxed)
+            .is_err()
+        {
+            #[cfg(feature = "metrics")]
+            self.metrics.cas_retried();
+            return Err(data);
+        }
+
+        if self.slots.set(curr_head, data).is_err() {
+            unreachable!("a claimed slot is always ready");
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.pushed(1);
+        Ok(())
+    }
+
+    /// Issues a `Release` fence, a single publish point after a burst of pushes.
+    ///
+    /// Every push already publishes its own value: the consumer that pops it sees it fully
+    /// written, whatever the ordering of anything else. What the fence adds is for the
+    /// producer's other writes, made with `Relaxed` stores: they happen before whatever an
+    /// `Acquire` load reads from any store the producer makes after `publish`, such as a
+    /// `Relaxed` flag telling the consumer the burst is complete. A consumer that sees such a
+    /// flag therefore also finds every value pushed before `publish` in the queue.
+    #[inline]
+    pub fn publish(&self) {
+        fence(Release);
+    }
+
+    /// Pushes as many values from the front of `items` as currently fit, returning how many
+    /// were enqueued.
+    ///
+    /// The whole run is claimed with a single CAS on `next_head`, so a batch costs one round
+    /// of contention instead of one per value. The enqueued prefix is drained from `items`;
+    /// whatever did not fit stays behind, in order, for a later call.
+    pub fn push_bulk(&self, items: &mut Vec<T>) -> usize {
+        if items.is_empty() {
+            return 0;
+        }
+        let Some((start, count)) = self.claim_run(items.len()) else {
+            return 0;
+        };
+
+        // The claimed run may wrap past the end of the ring; write it as two contiguous parts.
+        let first_run = count.min(self.slots.capacity - start);
+        let indices = (start..start + first_run).chain(0..count - first_run);
+        for (idx, data) in indices.zip(items.drain(..count)) {
+            if self.slots.set(idx, data).is_err() {
+                unreachable!("a claimed slot is always ready");
+            }
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.pushed(count);
+        count
+    }
 
-        if tail != head {
-            match self.slots.unset(tail) {
-                Ok(data) => {
-                    let next_tail = tail + 1;
-                    let is_less = unsafe {
-                        transmute::<isize, usize>(-((next_tail < self.slots.capacity) as isize))
-                    };
-                    let next_tail_bounded = next_tail & is_less;
-                    self.tail.store(next_tail_bounded, Release);
-                    Some(data)
+    /// Claims a run of up to `max` consecutive slots with a single CAS on `next_head`,
+    /// returning its first index and length, or `None` if the queue is full.
+    fn claim_run(&self, max: usize) -> Option<(usize, usize)> {
+        unsafe { self.backoff.register() };
+        let claimed = loop {
+            let curr_head = self.next_head.load(Acquire);
+            let tail = self.tail.load(Acquire);
+            let count = max.min(self.capacity - self.distance(tail, curr_head));
+
+            if count == 0 {
+                #[cfg(feature = "metrics")]
+                self.metrics.rejected_full();
+                break None;
+            }
+            match self.next_head.compare_exchange(
+                curr_head,
+                self.advance(curr_head, count),
+                AcqRel,
+                Acquire,
+            ) {
+                Ok(_) => {
+                    self.backoff.reset();
+                    break Some((curr_head, count));
                 }
                 Err(_) => {
-                    // Corruption or double-pop should not happen in valid single-consumer usage
-                    None
+                    #[cfg(feature = "metrics")]
+                    self.metrics.cas_retried();
+                    self.backoff.wait();
                 }
             }
-        } else {
-            None
+        };
+        unsafe { self.backoff.unregister() };
+        claimed
+    }
+
+    /// Returns a [`ProducerHandle`] that claims slots [`PRODUCER_RUN`] at a time, for a
+    /// producer about to push a burst of values.
+    pub fn producer(&self) -> ProducerHandle<'_, T, B, A> {
+        ProducerHandle {
+            queue: self,
+            next: 0,
+            end: 0,
         }
     }
-}
 
-impl<T> Drop for RawMpsc<T> {
-    /// Drops the queue and all remaining values in it.
+    /// Splits the queue into a [`Producer`] and a [`Consumer`] that borrow it, for threads
+    /// spawned with `std::thread::scope`.
     ///
-    /// Any items that have not been consumed are dropped here.
-    fn drop(&mut self) {
-        let head = self.next_head.load(Acquire);
-        let tail = self.tail.load(Acquire);
-        let mut curr = head;
-
-        while curr != tail {
-            let next_curr = {
-                let next = curr + 1;
-                let is_less =
-                    unsafe { transmute::<isize, usize>(-((next < self.slots.capacity) as isize)) };
-                next & is_less
-            };
-            let _ = self.slots.unset(curr);
-            curr = next_curr;
-        }
+    /// Unlike sharing the queue through an `Arc`, handing the halves out costs no reference
+    /// counting. The `Producer` is `Copy`, so every thread can take one, while the
+    /// `Consumer` is the only way to pop until both are dropped.
+    ///
+    /// ```
+    /// use lock_free_mpsc::mpsc::bounded_mpsc::RawMpsc;
+    /// use std::thread;
+    ///
+    /// let mut queue = RawMpsc::new(16);
+    /// let (tx, rx) = queue.split();
+    /// let mut received = Vec::new();
+    /// thread::scope(|s| {
+    ///     for p in 0..4 {
+    ///         s.spawn(move || {
+    ///             for i in 0..100 {
+    ///                 tx.push_blocking(p * 100 + i);
+    ///             }
+    ///         });
+    ///     }
+    ///     while received.len() < 400 {
+    ///         match rx.pop() {
+    ///             Some(data) => received.push(data),
+    ///             None => thread::yield_now(),
+    ///         }
+    ///     }
+    /// });
+    /// received.sort_unstable();
+    /// assert!(received.into_iter().eq(0..400));
+    /// ```
+    pub fn split(&mut self) -> (Producer<'_, T, B, A>, Consumer<'_, T, B, A>) {
+        (Producer::new(self), Consumer::new(self))
     }
-}
 
-// SAFETY: `RawMpsc` is `Send` and `Sync` as long as `T` is properly handled within the SlotArr.
-unsafe impl<T> Send for RawMpsc<T> {}
-unsafe impl<T> Sync for RawMpsc<T> {}
+    /// Claims a single slot to be written later, or returns `None` if the queue is full.
+    ///
+    /// Until the [`Reservation`] is filled or dropped, the consumer treats the queue as
+    /// ending at the reserved slot instead of waiting for it.
+    pub(crate) fn reserve(&self) -> Option<Reservation<'_, T, B, A>> {
+        let (idx, _) = self.claim_run(1)?;
+        if !self.slots.hold(idx) {
+            unreachable!("a claimed slot is always ready");
+        }
+        Some(Reservation { queue: self, idx })
+    }
 
-#[cfg(all(test, not(no_std)))]
-mod tests {
-    use super::*;
-    use std::collections::HashSet;
-    use std::sync::{Arc, Barrier};
-    use std::thread;
+    /// Pushes values from `iter` in order until the queue is full, returning how many did not
+    /// fit.
+    ///
+    /// The first value that is rejected and everything the iterator would have yielded after
+    /// it are dropped; `0` means every value was enqueued.
+    pub fn extend<I: IntoIterator<Item = T>>(&self, iter: I) -> usize {
+        let mut iter = iter.into_iter();
+        for data in iter.by_ref() {
+            if self.push(data).is_err() {
+                return 1 + iter.count();
+            }
+        }
+        0
+    }
 
-    #[test]
-    fn test_basic_push_pop() {
-        let q = RawMpsc::new(8);
+    /// Pushes data into the queue, parking the calling thread while the queue is full.
+    ///
+    /// The thread is unparked by the consumer once it frees a slot. A raw queue has no
+    /// receiver to lose, so this only returns once the value is in.
+    #[cfg(feature = "std")]
+    pub fn push_blocking(&self, data: T) {
+        let mut data = Some(data);
+        self.producers.park_until(|| {
+            match self.push(data.take().expect("retried after completion")) {
+                Ok(()) => Some(()),
+                Err(back) => {
+                    data = Some(back);
+                    None
+                }
+            }
+        });
+    }
 
-        assert!(q.push(10).is_ok());
-        assert!(q.push(20).is_ok());
+    /// Pushes data into the queue, evicting the oldest value to make room if it is full.
+    ///
+    /// Returns the evicted value, if any, so the queue always holds the most recent
+    /// [`capacity`](Self::capacity) values; useful for telemetry where stale data is worth
+    /// less than fresh. A queue with a capacity of zero hands `data` straight back, and so
+    /// does a full queue whose oldest slot a producer has claimed but not written yet, such
+    /// as one a [`ProducerHandle`] still holds, since there is nothing to evict there.
+    ///
+    /// Evicting moves `tail`, which belongs to the consumer, so like [`pop`](Self::pop) this
+    /// must only be called from the single consumer, or under a lock that also excludes the
+    /// consumer and other callers of `push_overwrite`. Plain [`push`](Self::push)es may run
+    /// concurrently.
+    pub fn push_overwrite(&self, data: T) -> Option<T> {
+        if self.capacity == 0 {
+            return Some(data);
+        }
+        let mut data = data;
+        let mut evicted = None;
+        loop {
+            match self.push(data) {
+                Ok(()) => return evicted,
+                Err(back) => data = back,
+            }
+            // Another producer may take the freed slot first; then evict again. Only the last
+            // value evicted is handed back, any earlier one is dropped.
+            match self.pop_without_waking() {
+                Some(oldest) => evicted = Some(oldest),
+                None => return Some(data),
+            }
+        }
+    }
 
-        assert_eq!(q.pop(), Some(10));
-        assert_eq!(q.pop(), Some(20));
-        assert_eq!(q.pop(), None);
+    /// Attempts to pop a value from the queue.
+    ///
+    /// Returns `Some(T)` if a value was available, or `None` if the queue is empty.
+    ///
+    /// A producer claims its index before writing the value, so the slot at `tail` can
+    /// briefly still be unwritten even though the queue is not empty. Rather than reporting
+    /// such an in-flight value as missing, `pop` backs off until the producer finishes.
+    pub fn pop(&self) -> Option<T> {
+        let data = self.pop_without_waking()?;
+        self.wake_producer();
+        Some(data)
     }
 
-    #[test]
-    fn test_push_until_full() {
-        let q = RawMpsc::new(4);
+    /// [`pop`](Self::pop) without waking a producer parked in
+    /// [`push_blocking`](Self::push_blocking), for the channel, which keeps its own wait
+    /// list.
+    pub(crate) fn pop_without_waking(&self) -> Option<T> {
+        let mut tail = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
 
-        for i in 0..4 {
-            assert!(q.push(i).is_ok());
+        // Slots given up by a `ProducerHandle` are stepped over.
+        while tail != head {
+            let data = match self.take_claimed(tail) {
+                Claimed::Value(data) => Some(data),
+                Claimed::Skipped => None,
+                Claimed::Pending => return None,
+            };
+            tail = self.next_index(tail);
+            self.tail.store(tail, Release);
+            if data.is_some() {
+                #[cfg(feature = "metrics")]
+                self.metrics.popped(1);
+                return data;
+            }
         }
+        None
+    }
 
-        // Now full, next push should fail
-        assert_eq!(q.push(99), Err(99));
+    /// Runs `f` on the oldest value where it sits in the queue, then drops it and returns
+    /// what `f` returned, or returns `None` if the queue is empty.
+    ///
+    /// Saves moving a large value out of its slot when it is only needed by reference. The
+    /// slot stays claimed while `f` runs, so producers cannot reuse it, and is released even
+    /// if `f` panics. Like [`pop`](Self::pop), this must only be called from the consumer.
+    pub fn pop_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let ret = self.pop_with_without_waking(f)?;
+        self.wake_producer();
+        Some(ret)
     }
 
-    #[test]
-    fn test_pop_from_empty() {
-        let q = RawMpsc::<u64>::new(4);
+    /// [`pop_with`](Self::pop_with) without waking a producer parked in
+    /// [`push_blocking`](Self::push_blocking).
+    pub(crate) fn pop_with_without_waking<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.take_front(|mut taken| f(taken.value()))
+    }
 
-        assert_eq!(q.pop(), None);
+    /// Runs `f` on the oldest value where it sits in the queue and, if it returns
+    /// [`Ack::Ack`], drops it. On [`Ack::Nack`] the value stays at the front of the queue
+    /// for the next pop. Returns `None` if the queue is empty.
+    ///
+    /// If `f` panics the value is dropped, as with [`pop_with`](Self::pop_with), so a value
+    /// that always makes its handler panic can't wedge the queue. Like [`pop`](Self::pop),
+    /// this must only be called from the consumer.
+    pub fn pop_ack(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        let ack = self.pop_ack_without_waking(f)?;
+        if ack == Ack::Ack {
+            self.wake_producer();
+        }
+        Some(ack)
     }
 
-    #[test]
-    fn test_multi_producer_single_consumer() {
-        const THREADS: usize = 8;
-        const ITEMS_PER_THREAD: usize = 32;
-        const CAPACITY: usize = THREADS * ITEMS_PER_THREAD;
+    /// [`pop_ack`](Self::pop_ack) without waking a producer parked in
+    /// [`push_blocking`](Self::push_blocking).
+    pub(crate) fn pop_ack_without_waking(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        self.take_front(|mut taken| {
+            let ack = f(taken.value());
+            if ack == Ack::Nack {
+                taken.put_back();
+            }
+            ack
+        })
+    }
 
-        let q = Arc::new(RawMpsc::new(CAPACITY));
-        let start_barrier = Arc::new(Barrier::new(THREADS + 1));
+    /// Pops the oldest value only if `pred` accepts it, returning `None` without touching the
+    /// queue if it is empty or `pred` returns `false`.
+    ///
+    /// A rejected value stays at the front for the next pop. `pred` sees the value where it
+    /// sits in the queue, so checking and popping it is a single step for the consumer. If
+    /// `pred` panics the value is dropped, as with [`pop_ack`](Self::pop_ack). Like
+    /// [`pop`](Self::pop), this must only be called from the consumer.
+    pub fn try_pop_if(&self, pred: impl FnOnce(&T) -> bool) -> Option<T> {
+        let data = self.take_front(|mut taken| {
+            if pred(taken.value()) {
+                Some(taken.into_inner())
+            } else {
+                taken.put_back();
+                None
+            }
+        })??;
+        self.wake_producer();
+        Some(data)
+    }
 
-        let mut handles = Vec::with_capacity(THREADS);
-        for t in 0..THREADS {
-            let q = Arc::clone(&q);
-            let barrier = Arc::clone(&start_barrier);
-            handles.push(thread::spawn(move || {
-                barrier.wait();
-                for i in 0..ITEMS_PER_THREAD {
-                    let value = t * 1000 + i;
-                    loop {
-                        if q.push(value).is_ok() {
-                            break;
-                        }
-                        std::thread::yield_now(); // give other threads a chance
-                    }
+    /// Begins taking the oldest value and hands it to `f`, which drops it unless it moves
+    /// it out or puts it back untouched in its slot.
+    fn take_front<R>(&self, f: impl FnOnce(Taken<'_, T, B, A>) -> R) -> Option<R> {
+        let mut tail = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+
+        while tail != head {
+            match self.begin_take_claimed(tail) {
+                Claimed::Value(()) => {
+                    // The value at `tail` was begun taking; `Taken` finishes it.
+                    return Some(f(Taken {
+                        queue: self,
+                        idx: tail,
+                    }));
                 }
-            }));
+                Claimed::Skipped => {}
+                Claimed::Pending => return None,
+            }
+            tail = self.next_index(tail);
+            self.tail.store(tail, Release);
         }
+        None
+    }
 
-        // Synchronize start
-        start_barrier.wait();
+    /// Returns a reference to the value at the front of the queue without removing it.
+    ///
+    /// Returns `None` if the queue is empty, or if the front value is still being written by
+    /// its producer.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called from the single consumer, which must not pop from the queue while
+    /// the returned reference is alive.
+    pub unsafe fn peek(&self) -> Option<&T> {
+        let mut tail = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+        // As the consumer, step over slots given up by a `ProducerHandle` for good.
+        while tail != head && self.slots.clear_skip(tail) {
+            tail = self.next_index(tail);
+            self.tail.store(tail, Release);
+        }
+        if tail == head {
+            return None;
+        }
+        unsafe { self.slots.peek(tail) }
+    }
 
-        // Pop all items
-        let mut results = HashSet::new();
-        while results.len() < THREADS * ITEMS_PER_THREAD {
-            if let Some(val) = q.pop() {
-                results.insert(val);
-            } else {
-                std::thread::yield_now();
+    /// Returns clones of the buffered values in FIFO order, leaving the queue untouched.
+    ///
+    /// Meant for assertions in tests and for diagnostics. Like [`pop`](Self::pop), this must
+    /// only be called from the single consumer, so no value can be taken out while it is
+    /// being cloned. Values still being written by their producers, and any behind them,
+    /// are left out.
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut idx = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+        let mut values = Vec::with_capacity(self.distance(idx, head));
+        while idx != head {
+            // SAFETY: the caller is the consumer and doesn't pop until this returns.
+            match unsafe { self.slots.peek(idx) } {
+                Some(data) => values.push(data.clone()),
+                None if self.slots.is_skipped(idx) => {}
+                None => break,
             }
+            idx = self.next_index(idx);
         }
+        values
+    }
 
-        for h in handles {
-            h.join().unwrap();
+    /// Pops up to `max` values in FIFO order, appending them to `out` and returning how many
+    /// were moved.
+    ///
+    /// Only the values claimed by producers when the call starts are considered. `tail` is
+    /// published once, after the whole run has been taken, instead of once per value.
+    pub fn pop_bulk(&self, out: &mut Vec<T>, max: usize) -> usize {
+        let tail = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+        let count = max.min(self.distance(tail, head));
+        if count == 0 {
+            return 0;
         }
 
-        // Check all expected values were received
-        for t in 0..THREADS {
-            for i in 0..ITEMS_PER_THREAD {
-                let expected = t * 1000 + i;
-                assert!(results.contains(&expected), "missing {expected}");
+        out.reserve(count);
+        let before = out.len();
+        let mut idx = tail;
+        for _ in 0..count {
+            match self.take_claimed(idx) {
+                Claimed::Value(data) => out.push(data),
+                Claimed::Skipped => {}
+                Claimed::Pending => break,
             }
+            idx = self.next_index(idx);
         }
+        self.tail.store(idx, Release);
+        self.wake_all_producers();
+        // Fewer than `count` if some slots were given up, or are still held, by a producer.
+        let moved = out.len() - before;
+        #[cfg(feature = "metrics")]
+        self.metrics.popped(moved);
+        moved
     }
 
-    #[test]
-    fn test_order_preserved_single_thread() {
-        let q = RawMpsc::new(8);
-
-        for i in 0..8 {
-            assert!(q.push(i).is_ok());
+    /// Pops every value claimed by producers so far into a [`QueueSnapshot`] that records
+    /// the capacity, so the queue can be rebuilt with [`from_snapshot`](Self::from_snapshot).
+    ///
+    /// Must only be called from the single consumer. As with [`pop_bulk`](Self::pop_bulk),
+    /// a value still held by a producer ends the snapshot.
+    pub fn drain(&self) -> QueueSnapshot<T> {
+        let mut items = Vec::new();
+        self.pop_bulk(&mut items, usize::MAX);
+        QueueSnapshot {
+            capacity: Some(self.capacity),
+            items,
         }
+    }
 
-        for i in 0..8 {
-            assert_eq!(q.pop(), Some(i));
+    /// Drops every value claimed by producers so far, leaving the queue empty and usable.
+    ///
+    /// Must only be called from the single consumer. Values whose producers are still
+    /// writing them are waited for, as in [`pop`](Self::pop).
+    pub fn clear(&self) {
+        let mut curr = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+        while curr != head {
+            match self.take_claimed(curr) {
+                Claimed::Value(_) => {
+                    #[cfg(feature = "metrics")]
+                    self.metrics.popped(1);
+                }
+                Claimed::Skipped => {}
+                Claimed::Pending => break,
+            }
+            curr = self.next_index(curr);
         }
+        self.tail.store(curr, Release);
+        self.wake_all_producers();
+    }
 
-        assert_eq!(q.pop(), None);
+    /// Attempts to enqueue a value, returning [`TrySendError::Full`] if there is no room.
+    ///
+    /// This is the recommended spelling of [`push`](Self::push) and matches the
+    /// `std::sync::mpsc`/`crossbeam` naming. A raw queue has no receiver to lose, so
+    /// [`TrySendError::Disconnected`] is never returned.
+    #[inline]
+    pub fn try_send(&self, data: T) -> Result<(), TrySendError<T>> {
+        self.push(data).map_err(TrySendError::Full)
     }
 
-    #[test]
-    fn test_degenerate_capacity_zero() {
-        let q = RawMpsc::<i32>::new(0);
-        assert!(q.push(1).is_err());
-        assert_eq!(q.pop(), None);
+    /// Attempts to dequeue a value, returning [`TryRecvError::Empty`] if there is none.
+    ///
+    /// This is the recommended spelling of [`pop`](Self::pop) and, like it, must only be
+    /// called from the single consumer.
+    #[inline]
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.pop().ok_or(TryRecvError::Empty)
     }
 
-    #[test]
-    fn test_capacity_one_behavior() {
-        let q = RawMpsc::new(1);
-        assert!(q.push(1).is_ok());
-        assert!(q.push(2).is_err());
-        assert_eq!(q.pop(), Some(1));
-        assert_eq!(q.pop(), None);
-        assert!(q.push(3).is_ok()); // no wraparound
+    /// Returns the number of values currently buffered in the queue.
+    ///
+    /// This is a best-effort snapshot: with producers running concurrently the value may be
+    /// stale by the time it is returned, and it counts slots that producers have claimed but
+    /// not finished writing yet.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Acquire);
+        let head = self.next_head.load(Acquire);
+        self.distance(tail, head)
     }
 
-    #[test]
-    fn free_drop_test() {
-        let q = RawMpsc::new(10);
-        for i in 0..10 {
-            assert!(q.push(i).is_ok())
-        }
+    /// Returns `true` if the queue holds no values.
+    ///
+    /// Like [`len`](Self::len), this is only a snapshot under concurrent producers.
+    pub fn is_empty(&self) -> bool {
+        self.tail.load(Acquire) == self.next_head.load(Acquire)
+    }
+
+    /// Returns the maximum number of values the queue can hold, as passed to
+    /// [`new`](Self::new).
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns how many more values the queue can take before a `push` fails.
+    ///
+    /// Like [`len`](Self::len), this is only a snapshot under concurrent producers.
+    pub fn remaining(&self) -> usize {
+        self.capacity.saturating_sub(self.len())
+    }
+
+    /// Returns `true` if a `push` would currently fail because the queue is full.
+    ///
+    /// Like [`len`](Self::len), this is only a snapshot under concurrent access.
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+
+    /// Rewinds a drained queue to its freshly created state, so it can be reused without
+    /// allocating a new one.
+    ///
+    /// The queue must be empty, which is checked in debug builds. In release builds any
+    /// values still queued are leaked rather than dropped. Metrics counters are left as is.
+    pub fn reset(&mut self) {
+        debug_assert!(self.is_empty(), "reset a queue that still holds values");
+        // `&mut self` already orders this after every producer and the consumer.
+        self.next_head.store(0, Relaxed);
+        self.tail.store(0, Relaxed);
+        self.slots.reset();
+    }
+
+    /// Moves the queue into a new ring holding `new_capacity` values, keeping their order.
+    ///
+    /// The oldest values are moved over first. If they don't all fit, the newest ones are
+    /// returned in FIFO order instead; otherwise the returned `Vec` is empty. Like
+    /// [`new`](Self::new), the ring gets `new_capacity + 1` slots.
+    pub fn resize(&mut self, new_capacity: usize) -> Vec<T>
+    where
+        A: Clone,
+    {
+        let slot_count = slots_for(new_capacity);
+        let slots = SlotArr::new_in(slot_count, self.slots.allocator().clone());
+        let mut overflow = Vec::new();
+        let mut len = 0;
+        // `&mut self` already orders this after every producer and the consumer.
+        let head = self.next_head.load(Relaxed);
+        let mut idx = self.tail.load(Relaxed);
+        while idx != head {
+            // Slots a `ProducerHandle` gave up hold nothing and are simply left behind.
+            if let Ok(data) = self.slots.unset(idx) {
+                if len < new_capacity {
+                    slots
+                        .set(len, data)
+                        .unwrap_or_else(|_| unreachable!("fresh slots are READY"));
+                    len += 1;
+                } else {
+                    overflow.push(data);
+                }
+            }
+            idx = self.next_index(idx);
+        }
+
+        self.slots = slots;
+        self.capacity = new_capacity;
+        self.mask = slot_count.is_power_of_two().then(|| slot_count - 1);
+        self.next_head.store(len, Relaxed);
+        self.tail.store(0, Relaxed);
+        overflow
+    }
+
+    /// Returns a snapshot of the queue's push, pop, full and contention counters.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> QueueStats {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the raw `(next_head, tail)` indices, for reports of suspected ordering bugs.
+    ///
+    /// Both wrap at the slot count, one more than the capacity. They are loaded one after
+    /// the other, so under concurrent use they may not describe a single moment.
+    #[cfg(feature = "debug-internals")]
+    pub fn debug_indices(&self) -> (usize, usize) {
+        let head = self.next_head.load(Acquire);
+        let tail = self.tail.load(Acquire);
+        (head, tail)
+    }
+}
+
+impl<T, B, A: Alloc> RawMpsc<T, B, A> {
+    /// Unparks the longest waiting producer of `push_blocking`, if any.
+    #[inline]
+    fn wake_producer(&self) {
+        #[cfg(feature = "std")]
+        self.producers.notify_one();
+    }
+
+    /// Unparks every producer waiting in `push_blocking`, after several slots were freed.
+    #[inline]
+    fn wake_all_producers(&self) {
+        #[cfg(feature = "std")]
+        self.producers.notify_all();
+    }
+
+    /// Takes the value out of a slot a producer has already claimed.
+    ///
+    /// The producer may still be writing it, in which case this backs off until it is done.
+    fn take_claimed(&self, idx: usize) -> Claimed<T> {
+        match self.begin_take_claimed(idx) {
+            // SAFETY: `begin_take_claimed` began taking the value.
+            Claimed::Value(()) => Claimed::Value(unsafe { self.slots.finish_take(idx) }),
+            Claimed::Skipped => Claimed::Skipped,
+            Claimed::Pending => Claimed::Pending,
+        }
+    }
+
+    /// Begins taking the value of a slot a producer has already claimed.
+    fn begin_take_claimed(&self, idx: usize) -> Claimed {
+        let backoff = LocalBackoff::new();
+        let mut rounds = 0;
+        loop {
+            if self.slots.begin_take(idx) {
+                return Claimed::Value(());
+            }
+            if self.slots.clear_skip(idx) {
+                return Claimed::Skipped;
+            }
+            // A reserved slot, or one whose claim was rolled back, won't be written any time
+            // soon.
+            if self.slots.is_held(idx) || !self.is_claimed(idx) {
+                return Claimed::Pending;
+            }
+            // Under loom every retry has to yield, or the model never schedules the producer.
+            if rounds < POP_SPIN_ROUNDS && !cfg!(all(loom, test)) {
+                backoff.wait();
+                rounds += 1;
+            } else {
+                // The producer may have been preempted mid-write; stop burning its CPU time.
+                #[cfg(any(feature = "std", all(loom, test)))]
+                crate::mpsc::sync::yield_now();
+                #[cfg(not(any(feature = "std", all(loom, test))))]
+                backoff.wait();
+            }
+        }
+    }
+
+    /// Returns `true` if `idx` lies between the consumer's `tail` and `next_head`, i.e. a
+    /// producer has claimed it.
+    #[inline]
+    fn is_claimed(&self, idx: usize) -> bool {
+        let tail = self.tail.load(Relaxed);
+        let head = self.next_head.load(Acquire);
+        self.distance(tail, idx) < self.distance(tail, head)
+    }
+
+    /// Returns the ring index following `idx`, wrapping back to `0` past the last slot.
+    #[inline(always)]
+    fn next_index(&self, idx: usize) -> usize {
+        let next = idx + 1;
+        match self.mask {
+            Some(mask) => next & mask,
+            None if next == self.slots.capacity => 0,
+            None => next,
+        }
+    }
+
+    /// Returns the ring index `n` places after `idx`, for `n` no larger than the capacity.
+    #[inline(always)]
+    fn advance(&self, idx: usize, n: usize) -> usize {
+        let next = idx + n;
+        match self.mask {
+            Some(mask) => next & mask,
+            None if next >= self.slots.capacity => next - self.slots.capacity,
+            None => next,
+        }
+    }
+
+    /// Returns the number of slots from `tail` up to (not including) `head`.
+    #[inline(always)]
+    fn distance(&self, tail: usize, head: usize) -> usize {
+        if head >= tail {
+            head - tail
+        } else {
+            // `head` has wrapped around the end of the ring while `tail` has not.
+            self.slots.capacity - tail + head
+        }
+    }
+}
+
+/// What the consumer finds in a slot a producer has claimed.
+enum Claimed<V = ()> {
+    /// The producer wrote its value.
+    Value(V),
+    /// A [`ProducerHandle`] gave the slot up without writing it.
+    Skipped,
+    /// The slot is held by a [`Reservation`] or a [`ProducerHandle`], or was handed back; the
+    /// queue ends here for now.
+    Pending,
+}
+
+/// A single slot claimed by [`RawMpsc::reserve`] for a value to be written later.
+///
+/// Dropping it without [`fill`](Self::fill)ing it gives the slot back like
+/// [`ProducerHandle::release`] does.
+pub(crate) struct Reservation<'a, T, B = GlobalBackoff, A: Alloc = Global> {
+    queue: &'a RawMpsc<T, B, A>,
+    idx: usize,
+}
+
+impl<T, B, A: Alloc> Reservation<'_, T, B, A> {
+    /// Writes `data` into the reserved slot.
+    pub(crate) fn fill(self, data: T) {
+        // SAFETY: `reserve` held the slot and only this reservation releases it.
+        unsafe { self.queue.slots.fill_held(self.idx, data) };
+        #[cfg(feature = "metrics")]
+        self.queue.metrics.pushed(1);
+        core::mem::forget(self);
+    }
+
+    /// Returns the reserved slot's storage, for the value to be built in place.
+    pub(crate) fn as_uninit(&mut self) -> &mut MaybeUninit<T> {
+        // SAFETY: `reserve` held the slot, and the borrow of `self` keeps it from being
+        // committed or released while the reference is alive.
+        unsafe { &mut *self.queue.slots.held_ptr(self.idx) }
+    }
+
+    /// Publishes the value built through [`as_uninit`](Self::as_uninit).
+    ///
+    /// # Safety
+    ///
+    /// The value must have been fully initialised.
+    pub(crate) unsafe fn commit(self) {
+        // SAFETY: `reserve` held the slot and the caller initialised it.
+        unsafe { self.queue.slots.commit_held(self.idx) };
+        #[cfg(feature = "metrics")]
+        self.queue.metrics.pushed(1);
+        core::mem::forget(self);
+    }
+}
+
+impl<T, B, A: Alloc> Drop for Reservation<'_, T, B, A> {
+    fn drop(&mut self) {
+        let queue = self.queue;
+        // The slot has to be ready before a producer can claim it again.
+        unsafe { queue.slots.release_held(self.idx, false) };
+        let handed_back = queue
+            .next_head
+            .compare_exchange(queue.next_index(self.idx), self.idx, AcqRel, Relaxed)
+            .is_ok();
+        if !handed_back && !queue.slots.skip(self.idx) {
+            unreachable!("an unwritten claimed slot is always ready");
+        }
+    }
+}
+
+/// The value [`RawMpsc::pop_with`] is working on, released once it is done, or unwinding.
+struct Taken<'a, T, B, A: Alloc> {
+    queue: &'a RawMpsc<T, B, A>,
+    idx: usize,
+}
+
+impl<T, B, A: Alloc> Taken<'_, T, B, A> {
+    /// The value, where it sits in its slot.
+    fn value(&mut self) -> &mut T {
+        // SAFETY: the value at `idx` was begun taking, and `self` is the only handle on it.
+        unsafe { &mut *self.queue.slots.taken_ptr(self.idx) }
+    }
+
+    /// Moves the value out and pops it.
+    fn into_inner(self) -> T {
+        let queue = self.queue;
+        let idx = self.idx;
+        core::mem::forget(self);
+        // SAFETY: as in `value`, and `self` no longer drops it.
+        let data = unsafe { queue.slots.finish_take(idx) };
+        queue.tail.store(queue.next_index(idx), Release);
+        #[cfg(feature = "metrics")]
+        queue.metrics.popped(1);
+        data
+    }
+
+    /// Leaves the value untouched in its slot, still at the front of the queue.
+    fn put_back(self) {
+        let (queue, idx) = (self.queue, self.idx);
+        core::mem::forget(self);
+        // SAFETY: as in `value`; `tail` stays on the value.
+        unsafe { queue.slots.abort_take(idx) };
+    }
+}
+
+impl<T, B, A: Alloc> Drop for Taken<'_, T, B, A> {
+    fn drop(&mut self) {
+        let queue = self.queue;
+        // SAFETY: the value at `idx` was begun taking and nothing else finishes it.
+        unsafe { queue.slots.drop_taken(self.idx) };
+        queue.tail.store(queue.next_index(self.idx), Release);
+        #[cfg(feature = "metrics")]
+        queue.metrics.popped(1);
+    }
+}
+
+impl<T, B: Backoff, A: Alloc + Default> Default for RawMpsc<T, B, A> {
+    /// Creates an empty queue with a capacity of 16.
+    fn default() -> Self {
+        Self::with_backoff_in(DEFAULT_CAPACITY, A::default())
+    }
+}
+
+impl<T: Clone, B: Backoff, A: Alloc + Clone> Clone for RawMpsc<T, B, A> {
+    /// Creates a queue with the same capacity holding clones of the buffered values, in FIFO
+    /// order, in slots of its own allocated from a clone of the allocator.
+    ///
+    /// Built on [`snapshot`](Self::snapshot), so it must likewise only be called from the
+    /// single consumer, or while no consumer is running. Values still being written by their
+    /// producers, and any behind them, are left out. Metrics counters start from zero.
+    fn clone(&self) -> Self {
+        let queue = Self::with_slots_in(self.slots.capacity, self.slots.allocator().clone());
+        if queue.extend(self.snapshot()) != 0 {
+            unreachable!("the clone has as many slots as the original");
+        }
+        queue
+    }
+}
+
+impl<T, B: Backoff, A: Alloc> Queue<T> for RawMpsc<T, B, A> {
+    #[inline]
+    fn try_push(&self, data: T) -> Result<(), T> {
+        self.push(data)
+    }
+
+    #[inline]
+    fn try_pop(&self) -> Option<T> {
+        self.pop()
+    }
+}
+
+impl<T, B: Backoff, A: Alloc> fmt::Debug for RawMpsc<T, B, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawMpsc")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Number of slots a [`ProducerHandle`] claims at a time.
+pub const PRODUCER_RUN: usize = 8;
+
+/// A producer's handle onto a [`RawMpsc`] that claims slots in runs.
+///
+/// Obtained from [`RawMpsc::producer`]. Each refill claims up to [`PRODUCER_RUN`] slots with
+/// a single CAS on `next_head` and the handle then fills them one [`push`](Self::push) at a
+/// time without touching `next_head` again, so a burst of `N` values costs about
+/// `N / PRODUCER_RUN` rounds of contention instead of `N`.
+///
+/// # Unused slots
+///
+/// The consumer reads slots in order and stops at the first claimed slot not written yet, so
+/// values pushed by other producers behind a handle's unused slots are not popped until the
+/// handle lets go of them: `pop` returns `None` there, as for an empty queue. Call
+/// [`release`](Self::release), or drop the handle, once the burst is over. Releasing first
+/// tries to hand the unused slots back by moving `next_head` back to the first of them,
+/// which works as long as no other producer has claimed slots since. Otherwise the slots
+/// are marked as skipped and the consumer steps over them without returning a value, so no
+/// hole is ever read as data.
+pub struct ProducerHandle<'a, T, B = GlobalBackoff, A: Alloc = Global> {
+    queue: &'a RawMpsc<T, B, A>,
+    /// The next claimed slot to write.
+    next: usize,
+    /// One past the last claimed slot; equal to `next` when nothing is claimed.
+    end: usize,
+}
+
+impl<T, B: Backoff, A: Alloc> ProducerHandle<'_, T, B, A> {
+    /// Pushes data into the next claimed slot, claiming a new run first if the handle has
+    /// none left.
+    ///
+    /// Returns `Err(data)` if the queue is full.
+    pub fn push(&mut self, data: T) -> Result<(), T> {
+        if self.next == self.end {
+            let Some((start, count)) = self.queue.claim_run(PRODUCER_RUN) else {
+                return Err(data);
+            };
+            self.next = start;
+            self.end = self.queue.advance(start, count);
+            // Held, the run reads as the end of the queue until each slot is written, rather
+            // than as values the consumer has to wait for.
+            let mut idx = start;
+            while idx != self.end {
+                if !self.queue.slots.hold(idx) {
+                    unreachable!("a claimed slot is always ready");
+                }
+                idx = self.queue.next_index(idx);
+            }
+        }
+        // SAFETY: the slot was held when its run was claimed and is written only once.
+        unsafe { self.queue.slots.fill_held(self.next, data) };
+        self.next = self.queue.next_index(self.next);
+        #[cfg(feature = "metrics")]
+        self.queue.metrics.pushed(1);
+        Ok(())
+    }
+}
+
+impl<T, B, A: Alloc> ProducerHandle<'_, T, B, A> {
+    /// Returns the number of claimed slots not written yet.
+    pub fn reserved(&self) -> usize {
+        self.queue.distance(self.next, self.end)
+    }
+
+    /// Gives back every claimed slot that has not been written, so the consumer is not held
+    /// up by them. The next `push` claims a fresh run.
+    pub fn release(&mut self) {
+        if self.next == self.end {
+            return;
+        }
+        // The slots have to be ready before a producer can claim them again.
+        let mut idx = self.next;
+        while idx != self.end {
+            // SAFETY: the slot was held when its run was claimed and has not been written.
+            unsafe { self.queue.slots.release_held(idx, false) };
+            idx = self.queue.next_index(idx);
+        }
+        // No one else can have moved `next_head` away and back to `end`: it would have to
+        // lap the ring, and the consumer cannot pass our unwritten slots.
+        let handed_back = self
+            .queue
+            .next_head
+            .compare_exchange(self.end, self.next, AcqRel, Relaxed)
+            .is_ok();
+        if !handed_back {
+            while self.next != self.end {
+                if !self.queue.slots.skip(self.next) {
+                    unreachable!("an unwritten claimed slot is always ready");
+                }
+                self.next = self.queue.next_index(self.next);
+            }
+        }
+        self.next = self.end;
+    }
+}
+
+impl<T, B, A: Alloc> Drop for ProducerHandle<'_, T, B, A> {
+    /// Releases the slots claimed but not written; see [`release`](Self::release).
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl<T, B, A: Alloc> RawMpsc<T, B, A> {
+    /// Takes the oldest remaining value out of a queue no one else can reach.
+    ///
+    /// Slots given up by a producer are stepped over, and `tail` moves past every slot
+    /// visited, so a value is never taken twice.
+    fn take_owned(&mut self) -> Option<T> {
+        // `&mut self` already orders this after every producer and the consumer.
+        let head = self.next_head.load(Relaxed);
+        let mut tail = self.tail.load(Relaxed);
+        while tail != head {
+            let data = self.slots.unset(tail);
+            tail = self.next_index(tail);
+            self.tail.store(tail, Relaxed);
+            if let Ok(data) = data {
+                return Some(data);
+            }
+        }
+        None
+    }
+}
+
+impl<T, B, A: Alloc> Drop for RawMpsc<T, B, A> {
+    /// Drops the queue and all remaining values in it.
+    ///
+    /// Any items that have not been consumed are dropped here.
+    fn drop(&mut self) {
+        while let Some(data) = self.take_owned() {
+            drop(data);
+        }
+    }
+}
+
+/// An iterator that owns a [`RawMpsc`] and yields its remaining values in FIFO order.
+///
+/// Created by `RawMpsc::into_iter`. Values not yet yielded when the iterator is dropped are
+/// dropped with the queue.
+pub struct RawIntoIter<T, B = GlobalBackoff, A: Alloc = Global> {
+    queue: RawMpsc<T, B, A>,
+}
+
+impl<T, B, A: Alloc> Iterator for RawIntoIter<T, B, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.take_owned()
+    }
+}
+
+impl<T, B, A: Alloc> IntoIterator for RawMpsc<T, B, A> {
+    type Item = T;
+    type IntoIter = RawIntoIter<T, B, A>;
+
+    fn into_iter(self) -> RawIntoIter<T, B, A> {
+        RawIntoIter { queue: self }
+    }
+}
+
+// SAFETY: values move from the producers' threads to the consumer's, so both need
+// `T: Send`. The slots are only accessed through their atomic state, and popping is left to
+// a single consumer by the contract of `pop`.
+unsafe impl<T: Send, B: Send, A: Alloc + Send> Send for RawMpsc<T, B, A> {}
+unsafe impl<T: Send, B: Sync, A: Alloc + Sync> Sync for RawMpsc<T, B, A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backoff::{SpinOnly, YieldBackoff};
+    use std::collections::HashSet;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn test_basic_push_pop() {
+        let q = RawMpsc::new(8);
+
+        assert!(q.push(10).is_ok());
+        assert!(q.push(20).is_ok());
+
+        assert_eq!(q.pop(), Some(10));
+        assert_eq!(q.pop(), Some(20));
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_push_until_full() {
+        let q = RawMpsc::new(4);
+
+        for i in 0..4 {
+            assert!(q.push(i).is_ok());
+        }
+
+        // Now full, next push should fail
+        assert_eq!(q.push(99), Err(99));
+    }
+
+    #[test]
+    fn test_pop_from_empty() {
+        let q = RawMpsc::<u64>::new(4);
+
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_multi_producer_single_consumer() {
+        const THREADS: usize = 8;
+        const ITEMS_PER_THREAD: usize = 32;
+        const CAPACITY: usize = THREADS * ITEMS_PER_THREAD;
+
+        let q = Arc::new(RawMpsc::new(CAPACITY));
+        let start_barrier = Arc::new(Barrier::new(THREADS + 1));
+
+        let mut handles = Vec::with_capacity(THREADS);
+        for t in 0..THREADS {
+            let q = Arc::clone(&q);
+            let barrier = Arc::clone(&start_barrier);
+            handles.push(thread::spawn(move || {
+                barrier.wait();
+                for i in 0..ITEMS_PER_THREAD {
+                    let value = t * 1000 + i;
+                    loop {
+                        if q.push(value).is_ok() {
+                            break;
+                        }
+                        std::thread::yield_now(); // give other threads a chance
+                    }
+                }
+            }));
+        }
+
+        // Synchronize start
+        start_barrier.wait();
+
+        // Pop all items
+        let mut results = HashSet::new();
+        while results.len() < THREADS * ITEMS_PER_THREAD {
+            if let Some(val) = q.pop() {
+                results.insert(val);
+            } else {
+                std::thread::yield_now();
+            }
+        }
+
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        // Check all expected values were received
+        for t in 0..THREADS {
+            for i in 0..ITEMS_PER_THREAD {
+                let expected = t * 1000 + i;
+                assert!(results.contains(&expected), "missing {expected}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_committed_pushes_are_never_reported_empty() {
+        const THREADS: usize = 8;
+        const ITEMS_PER_THREAD: usize = 2_000;
+        const TOTAL: usize = THREADS * ITEMS_PER_THREAD;
+
+        let q = Arc::new(RawMpsc::new(64));
+        let committed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let q = Arc::clone(&q);
+                let committed = Arc::clone(&committed);
+                thread::spawn(move || {
+                    for i in 0..ITEMS_PER_THREAD {
+                        while q.push(t * ITEMS_PER_THREAD + i).is_err() {
+                            thread::yield_now();
+                        }
+                        committed.fetch_add(1, std::sync::atomic::Ordering::Release);
+                    }
+                })
+            })
+            .collect();
+
+        let mut seen = HashSet::with_capacity(TOTAL);
+        while seen.len() < TOTAL {
+            // Everything counted as committed before this point must be poppable
+            let committed_now = committed.load(std::sync::atomic::Ordering::Acquire);
+            while seen.len() < committed_now {
+                let value = q.pop().expect("a committed push was reported as empty");
+                assert!(seen.insert(value), "duplicate {value}");
+            }
+            thread::yield_now();
+        }
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_order_preserved_single_thread() {
+        let q = RawMpsc::new(8);
+
+        for i in 0..8 {
+            assert!(q.push(i).is_ok());
+        }
+
+        for i in 0..8 {
+            assert_eq!(q.pop(), Some(i));
+        }
+
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_degenerate_capacity_zero() {
+        let q = RawMpsc::<i32>::new(0);
+        assert!(q.push(1).is_err());
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_capacity_one_behavior() {
+        let q = RawMpsc::new(1);
+        assert!(q.push(1).is_ok());
+        assert!(q.push(2).is_err());
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), None);
+        assert!(q.push(3).is_ok()); // no wraparound
+    }
+
+    #[test]
+    fn test_len_and_is_empty() {
+        let q = RawMpsc::new(4);
+        assert!(q.is_empty());
+        assert_eq!(q.len(), 0);
+
+        for i in 0..4 {
+            q.push(i).unwrap();
+            assert_eq!(q.len(), i + 1);
+        }
+        assert!(!q.is_empty());
+
+        q.pop().unwrap();
+        assert_eq!(q.len(), 3);
+    }
+
+    #[test]
+    fn test_len_when_head_has_wrapped() {
+        let q = RawMpsc::new(4);
+        for i in 0..4 {
+            q.push(i).unwrap();
+        }
+        for _ in 0..3 {
+            q.pop().unwrap();
+        }
+        q.push(4).unwrap();
+        q.push(5).unwrap();
+
+        // `next_head` is now behind `tail` in the ring
+        assert!(q.next_head.load(Acquire) < q.tail.load(Acquire));
+        assert_eq!(q.len(), 3);
+        assert!(!q.is_empty());
+
+        for expected in 3..6 {
+            assert_eq!(q.pop(), Some(expected));
+        }
+        assert_eq!(q.len(), 0);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_remaining() {
+        let q = RawMpsc::new(4);
+        assert_eq!(q.remaining(), 4);
+        q.push(0).unwrap();
+        q.push(1).unwrap();
+        assert_eq!(q.remaining(), 2);
+        q.push(2).unwrap();
+        q.push(3).unwrap();
+        assert_eq!(q.remaining(), 0);
+
+        // Keep the queue half full while the indices wrap around the ring.
+        for i in 4..20 {
+            q.pop().unwrap();
+            q.pop().unwrap();
+            q.push(i).unwrap();
+            q.push(i).unwrap();
+            assert_eq!(q.remaining(), 0);
+            q.pop().unwrap();
+            q.pop().unwrap();
+            assert_eq!(q.remaining(), 2);
+            q.push(i).unwrap();
+            q.push(i).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_capacity_and_is_full() {
+        let q = RawMpsc::new(3);
+        assert_eq!(q.capacity(), 3);
+        assert!(!q.is_full());
+
+        for i in 0..3 {
+            q.push(i).unwrap();
+        }
+        assert!(q.is_full());
+        assert_eq!(q.push(3), Err(3));
+
+        q.pop().unwrap();
+        assert!(!q.is_full());
+        assert!(q.push(3).is_ok());
+        assert!(q.is_full());
+    }
+
+    #[test]
+    fn test_zero_capacity_is_always_full() {
+        let q = RawMpsc::<i32>::new(0);
+        assert_eq!(q.capacity(), 0);
+        assert!(q.is_full());
+    }
+
+    #[test]
+    fn test_with_capacity_pow2_rounds_up() {
+        let q = RawMpsc::<u8>::with_capacity_pow2(5);
+        assert_eq!(q.capacity(), 7);
+        assert_eq!(q.mask, Some(7));
+
+        // One below a power of two needs no rounding; a power of two needs twice the slots
+        assert_eq!(RawMpsc::<u8>::with_capacity_pow2(7).mask, Some(7));
+        let q = RawMpsc::<u8>::with_capacity_pow2(8);
+        assert_eq!(q.capacity(), 15);
+        assert_eq!(q.mask, Some(15));
+
+        // `new` picks the mask up too when `capacity + 1` already is a power of two
+        assert_eq!(RawMpsc::<u8>::new(7).mask, Some(7));
+        assert_eq!(RawMpsc::<u8>::new(8).mask, None);
+    }
+
+    fn assert_fifo_across_wrap(q: RawMpsc<usize>) {
+        let cap = q.capacity();
+        let mut next_push = 0;
+        let mut next_pop = 0;
+        // Keep the ring about half full while walking it around several times
+        for _ in 0..4 * cap {
+            while q.len() < cap / 2 + 1 {
+                q.push(next_push).unwrap();
+                next_push += 1;
+            }
+            assert_eq!(q.pop(), Some(next_pop));
+            next_pop += 1;
+        }
+        while let Some(value) = q.pop() {
+            assert_eq!(value, next_pop);
+            next_pop += 1;
+        }
+        assert_eq!(next_pop, next_push);
+    }
+
+    #[test]
+    fn test_fifo_across_wrap_masked() {
+        assert_fifo_across_wrap(RawMpsc::with_capacity_pow2(6));
+    }
+
+    #[test]
+    fn test_fifo_across_wrap_unmasked() {
+        assert_fifo_across_wrap(RawMpsc::new(6));
+    }
+
+    #[test]
+    fn test_try_send_try_recv_aliases() {
+        let q = RawMpsc::new(1);
+        assert_eq!(q.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(q.try_send(1), Ok(()));
+        assert_eq!(q.try_send(2), Err(TrySendError::Full(2)));
+        assert_eq!(q.try_recv(), Ok(1));
+        assert_eq!(q.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_push_bulk_clamps_to_free_space() {
+        let q = RawMpsc::new(4);
+        q.push(0).unwrap();
+
+        let mut items: Vec<_> = (1..10).collect();
+        assert_eq!(q.push_bulk(&mut items), 3);
+        assert_eq!(items, (4..10).collect::<Vec<_>>());
+        assert_eq!(q.push_bulk(&mut items), 0);
+        assert_eq!(q.push_bulk(&mut Vec::new()), 0);
+
+        for expected in 0..4 {
+            assert_eq!(q.pop(), Some(expected));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_extend_stops_at_capacity() {
+        let q = RawMpsc::new(4);
+        assert_eq!(q.extend(0..3), 0);
+        assert_eq!(q.extend(3..10), 6);
+        assert!(q.is_full());
+        assert_eq!(q.extend(Some(10)), 1);
+
+        for expected in 0..4 {
+            assert_eq!(q.pop(), Some(expected));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_try_push_weak_without_contention() {
+        let q = RawMpsc::new(2);
+        let mut pushed = 0;
+        // A weak CAS may fail spuriously, but not forever
+        while pushed < 2 {
+            if q.try_push_weak(pushed).is_ok() {
+                pushed += 1;
+            }
+        }
+        assert_eq!(q.try_push_weak(9), Err(9));
+        assert_eq!(q.pop(), Some(0));
+        assert_eq!(q.pop(), Some(1));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_try_push_weak_multi_producer_loses_nothing_accepted() {
+        const PRODUCERS: usize = 4;
+        const ATTEMPTS: usize = 10_000;
+
+        let q = Arc::new(RawMpsc::new(64));
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    (0..ATTEMPTS)
+                        .filter(|i| q.try_push_weak(p * ATTEMPTS + i).is_ok())
+                        .count()
+                })
+            })
+            .collect();
+
+        let mut received = 0;
+        while !producers.iter().all(|p| p.is_finished()) {
+            match q.pop() {
+                Some(_) => received += 1,
+                None => thread::yield_now(),
+            }
+        }
+        received += core::iter::from_fn(|| q.pop()).count();
+        let accepted: usize = producers.into_iter().map(|p| p.join().unwrap()).sum();
+        assert_eq!(received, accepted);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_publish_orders_a_burst_before_a_relaxed_flag() {
+        use std::sync::atomic::AtomicBool;
+
+        const BURST: usize = 32;
+
+        let q = Arc::new(RawMpsc::new(BURST));
+        let done = Arc::new(AtomicBool::new(false));
+        let producer = {
+            let (q, done) = (Arc::clone(&q), Arc::clone(&done));
+            thread::spawn(move || {
+                for i in 0..BURST {
+                    while q.try_push_weak(i).is_err() {}
+                }
+                q.publish();
+                done.store(true, Relaxed);
+            })
+        };
+
+        while !done.load(Acquire) {
+            thread::yield_now();
+        }
+        // The whole burst is in the queue once the flag is seen
+        assert_eq!(q.len(), BURST);
+        for i in 0..BURST {
+            assert_eq!(q.pop(), Some(i));
+        }
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn test_producer_handle_burst_claims_runs() {
+        let q = RawMpsc::new(32);
+        {
+            let mut tx = q.producer();
+            for i in 0..20 {
+                tx.push(i).unwrap();
+            }
+            // 20 values took three runs of 8, leaving 4 claimed slots
+            assert_eq!(tx.reserved(), 4);
+        }
+        assert_eq!(q.len(), 20);
+        for expected in 0..20 {
+            assert_eq!(q.pop(), Some(expected));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_producer_handle_release_hands_slots_back() {
+        let q = RawMpsc::new(16);
+        let mut tx = q.producer();
+        for i in 0..3 {
+            tx.push(i).unwrap();
+        }
+        tx.release();
+        assert_eq!(tx.reserved(), 0);
+        assert_eq!(q.len(), 3);
+
+        q.push(100).unwrap();
+        tx.push(3).unwrap();
+        drop(tx);
+        let popped: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(popped, [0, 1, 2, 100, 3]);
+    }
+
+    // A handle's unwritten slots read as the end of the queue, not as values on their way
+    #[test]
+    fn test_pop_stops_at_a_producer_handles_unwritten_slots() {
+        let q = RawMpsc::new(16);
+        let mut h = q.producer();
+        h.push(1).unwrap();
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), None);
+        let mut out = Vec::new();
+        assert_eq!(q.pop_bulk(&mut out, 16), 0);
+
+        h.push(2).unwrap();
+        assert_eq!(q.pop(), Some(2));
+        drop(h);
+        assert_eq!(q.pop(), None);
+        q.push(3).unwrap();
+        assert_eq!(q.pop(), Some(3));
+    }
+
+    // Once another producer has claimed behind the run, the unused slots become skipped
+    // holes the consumer steps over rather than reads
+    #[test]
+    fn test_producer_handle_release_leaves_no_readable_holes() {
+        let q = RawMpsc::new(16);
+        let mut tx = q.producer();
+        tx.push(0).unwrap();
+        q.push(100).unwrap();
+        tx.push(1).unwrap();
+        drop(tx);
+
+        assert_eq!(unsafe { q.peek() }, Some(&0));
+        assert_eq!(q.pop(), Some(0));
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(unsafe { q.peek() }, Some(&100));
+        assert_eq!(q.pop(), Some(100));
+        assert_eq!(q.pop(), None);
+        assert!(q.is_empty());
+
+        // The skipped slots are usable again on the next lap
+        let mut tx = q.producer();
+        tx.push(0).unwrap();
+        q.push(100).unwrap();
+        drop(tx);
+        let mut out = Vec::new();
+        assert_eq!(q.pop_bulk(&mut out, 16), 2);
+        assert_eq!(out, [0, 100]);
+        for i in 0..16 {
+            q.push(i).unwrap();
+        }
+        q.clear();
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_producer_handles_multi_producer() {
+        const PRODUCERS: usize = 4;
+        const BURSTS: usize = 200;
+        const BURST: usize = 5;
+
+        let q = Arc::new(RawMpsc::<usize, YieldBackoff>::with_backoff(16));
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    let mut tx = q.producer();
+                    for burst in 0..BURSTS {
+                        for i in 0..BURST {
+                            let mut value = (p * BURSTS + burst) * BURST + i;
+                            while let Err(back) = tx.push(value) {
+                                value = back;
+                                thread::yield_now();
+                            }
+                        }
+                        // Odd-length bursts leave part of a run behind every time
+                        tx.release();
+                    }
+                })
+            })
+            .collect();
+
+        let total = PRODUCERS * BURSTS * BURST;
+        let mut seen = HashSet::with_capacity(total);
+        while seen.len() < total {
+            match q.pop() {
+                Some(value) => assert!(seen.insert(value), "value {value} delivered twice"),
+                None => thread::yield_now(),
+            }
+        }
+        for p in producers {
+            p.join().unwrap();
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_reset_reuses_a_drained_queue() {
+        let dropped = AtomicUsize::new(0);
+        let mut q = RawMpsc::new(3);
+        for round in 0..3 {
+            // Leave the indices somewhere in the middle of the ring
+            for _ in 0..=round {
+                assert!(q.push((0, DropCounter(&dropped))).is_ok());
+                q.pop().unwrap();
+            }
+            for i in 0..3 {
+                assert!(q.push((i, DropCounter(&dropped))).is_ok());
+            }
+            assert!(q.is_full());
+            for i in 0..3 {
+                assert_eq!(q.pop().unwrap().0, i);
+            }
+            q.reset();
+            assert_eq!(q.next_head.load(Relaxed), 0);
+            assert_eq!(q.tail.load(Relaxed), 0);
+            assert!(q.is_empty());
+        }
+        assert_eq!(dropped.load(Relaxed), 4 + 5 + 6);
+
+        for i in 0..3 {
+            assert!(q.push((i, DropCounter(&dropped))).is_ok());
+        }
+        assert!(q.push((3, DropCounter(&dropped))).is_err());
+        drop(q);
+        assert_eq!(dropped.load(Relaxed), 15 + 4);
+    }
+
+    #[test]
+    fn test_reset_clears_slots_a_producer_handle_gave_up() {
+        let mut q: RawMpsc<u32> = RawMpsc::new(PRODUCER_RUN * 2);
+        let mut producer = q.producer();
+        producer.push(1).unwrap();
+        drop(producer);
+        q.push(2).unwrap();
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), Some(2));
+        q.reset();
+        for i in 0..q.capacity() as u32 {
+            q.push(i).unwrap();
+        }
+        for i in 0..q.capacity() as u32 {
+            assert_eq!(q.pop(), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_resize_grow_keeps_order() {
+        let mut q = RawMpsc::new(4);
+        // Wrap the indices so the values straddle the end of the ring
+        for i in 0..3 {
+            q.push(i).unwrap();
+            q.pop().unwrap();
+        }
+        for i in 0..4 {
+            q.push(i).unwrap();
+        }
+        assert!(q.resize(7).is_empty());
+        assert_eq!(q.capacity(), 7);
+        assert_eq!(q.len(), 4);
+        for i in 4..7 {
+            q.push(i).unwrap();
+        }
+        assert!(q.push(7).is_err());
+        for i in 0..7 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_resize_shrink_returns_the_overflow() {
+        let dropped = AtomicUsize::new(0);
+        let mut q = RawMpsc::with_capacity_pow2(8);
+        for _ in 0..5 {
+            assert!(q.push((0, DropCounter(&dropped))).is_ok());
+            q.pop().unwrap();
+        }
+        for i in 1..=6 {
+            assert!(q.push((i, DropCounter(&dropped))).is_ok());
+        }
+        assert_eq!(dropped.load(Relaxed), 5);
+
+        let overflow = q.resize(2);
+        assert_eq!(
+            overflow.iter().map(|v| v.0).collect::<Vec<_>>(),
+            [3, 4, 5, 6]
+        );
+        assert_eq!(q.capacity(), 2);
+        assert!(q.is_full());
+        assert_eq!(dropped.load(Relaxed), 5);
+        drop(overflow);
+        assert_eq!(dropped.load(Relaxed), 9);
+
+        assert_eq!(q.pop().unwrap().0, 1);
+        assert!(q.push((7, DropCounter(&dropped))).is_ok());
+        assert_eq!(q.pop().unwrap().0, 2);
+        assert_eq!(q.pop().unwrap().0, 7);
+        assert_eq!(dropped.load(Relaxed), 12);
+
+        // Resizing to nothing hands everything back
+        assert!(q.push((8, DropCounter(&dropped))).is_ok());
+        let overflow = q.resize(0);
+        assert_eq!(overflow.len(), 1);
+        assert!(q.push((9, DropCounter(&dropped))).is_err());
+    }
+
+    #[test]
+    fn test_resize_skips_slots_a_producer_handle_gave_up() {
+        let mut q: RawMpsc<u32> = RawMpsc::new(PRODUCER_RUN * 2);
+        let mut producer = q.producer();
+        producer.push(1).unwrap();
+        // Another push past the run keeps the handle from rolling its claim back
+        q.push(2).unwrap();
+        drop(producer);
+        assert!(q.resize(2).is_empty());
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), None);
+    }
+
+    /// Hands out a fixed buffer front to back and counts the calls made to it.
+    struct Bump {
+        buf: core::cell::UnsafeCell<BumpBuf>,
+        used: core::cell::Cell<usize>,
+        allocs: core::cell::Cell<usize>,
+        frees: core::cell::Cell<usize>,
+    }
+
+    #[repr(align(128))]
+    struct BumpBuf([u8; 4096]);
+
+    impl Bump {
+        fn new() -> Self {
+            Self {
+                buf: core::cell::UnsafeCell::new(BumpBuf([0; 4096])),
+                used: core::cell::Cell::new(0),
+                allocs: core::cell::Cell::new(0),
+                frees: core::cell::Cell::new(0),
+            }
+        }
+
+        // A raw place, not `&mut`: that would invalidate the blocks already handed out.
+        fn base(&self) -> *mut u8 {
+            unsafe { (&raw mut (*self.buf.get()).0).cast() }
+        }
+
+        fn contains(&self, ptr: *const u8) -> bool {
+            let start = self.base().cast_const();
+            (start..start.wrapping_add(4096)).contains(&ptr)
+        }
+    }
+
+    unsafe impl Alloc for Bump {
+        fn allocate(&self, layout: core::alloc::Layout) -> Option<core::ptr::NonNull<u8>> {
+            let start = self.used.get().next_multiple_of(layout.align());
+            let end = start
+                .checked_add(layout.size())
+                .filter(|&end| end <= 4096)?;
+            self.used.set(end);
+            self.allocs.set(self.allocs.get() + 1);
+            core::ptr::NonNull::new(unsafe { self.base().add(start) })
+        }
+
+        unsafe fn deallocate(&self, ptr: core::ptr::NonNull<u8>, _: core::alloc::Layout) {
+            assert!(self.contains(ptr.as_ptr()));
+            self.frees.set(self.frees.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_slots_come_from_the_supplied_allocator() {
+        let bump = Bump::new();
+        {
+            let mut q = RawMpsc::new_in(4, &bump);
+            assert_eq!(bump.allocs.get(), 1);
+            assert!(bump.contains(q.slots.ptr.as_ptr().cast()));
+            for i in 0..4 {
+                q.push(i).unwrap();
+            }
+            assert_eq!(q.pop(), Some(0));
+
+            // The new ring comes from the same allocator and the old one goes back to it
+            assert!(q.resize(8).is_empty());
+            assert_eq!((bump.allocs.get(), bump.frees.get()), (2, 1));
+            assert!(bump.contains(q.slots.ptr.as_ptr().cast()));
+            assert_eq!(q.snapshot(), [1, 2, 3]);
+        }
+        assert_eq!((bump.allocs.get(), bump.frees.get()), (2, 2));
+    }
+
+    struct FailingAlloc;
+
+    unsafe impl Alloc for FailingAlloc {
+        fn allocate(&self, _: core::alloc::Layout) -> Option<core::ptr::NonNull<u8>> {
+            None
+        }
+
+        unsafe fn deallocate(&self, _: core::ptr::NonNull<u8>, _: core::alloc::Layout) {
+            unreachable!("nothing was allocated")
+        }
+    }
+
+    #[test]
+    fn test_try_new_reports_failure() {
+        assert_eq!(
+            RawMpsc::<u64, _, _>::try_new_in(4, FailingAlloc).err(),
+            Some(AllocError::OutOfMemory)
+        );
+        assert_eq!(
+            RawMpsc::<u64>::try_new(usize::MAX).err(),
+            Some(AllocError::CapacityOverflow)
+        );
+        assert_eq!(
+            RawMpsc::<u64>::try_new(isize::MAX as usize).err(),
+            Some(AllocError::CapacityOverflow)
+        );
+
+        let q = RawMpsc::try_new(4).unwrap();
+        q.push(1).unwrap();
+        assert_eq!(q.pop(), Some(1));
+    }
+
+    #[test]
+    fn test_pop_ack_keeps_nacked_value_at_front() {
+        let q = RawMpsc::new(2);
+        q.push(1).unwrap();
+        q.push(2).unwrap();
+        assert_eq!(q.pop_ack(|_| Ack::Nack), Some(Ack::Nack));
+        assert_eq!(q.len(), 2);
+        // The nacked value still holds its slot
+        assert_eq!(q.push(3), Err(3));
+        assert_eq!(
+            q.pop_ack(|v| if *v == 1 { Ack::Ack } else { Ack::Nack }),
+            Some(Ack::Ack)
+        );
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop_ack(|_| Ack::Ack), None);
+    }
+
+    #[test]
+    fn test_try_pop_if_leaves_rejected_value_at_front() {
+        let q = RawMpsc::new(2);
+        assert_eq!(q.try_pop_if(|_| true), None);
+        q.push(1).unwrap();
+        q.push(2).unwrap();
+        assert_eq!(q.try_pop_if(|v| *v == 2), None);
+        // The rejected value still holds its slot
+        assert_eq!(q.push(3), Err(3));
+        assert_eq!(q.try_pop_if(|v| *v == 1), Some(1));
+        q.push(3).unwrap();
+        assert_eq!(q.try_pop_if(|v| *v == 2), Some(2));
+        assert_eq!(q.pop(), Some(3));
+    }
+
+    #[test]
+    fn test_pop_ack_drops_value_when_handler_panics() {
+        let dropped = AtomicUsize::new(0);
+        let q = RawMpsc::new(2);
+        q.push(DropCounter(&dropped))
+            .unwrap_or_else(|_| unreachable!());
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            q.pop_ack(|_| panic!("handler failed"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(dropped.load(Relaxed), 1);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_leaves_the_queue_untouched() {
+        let q = RawMpsc::new(4);
+        assert!(q.snapshot().is_empty());
+        // Wrap the indices so the values straddle the end of the ring
+        for i in 0..3 {
+            q.push(i).unwrap();
+            q.pop().unwrap();
+        }
+        for i in 10..14 {
+            q.push(i).unwrap();
+        }
+        let before = q.snapshot();
+        assert_eq!(before, [10, 11, 12, 13]);
+        assert_eq!(q.len(), 4);
+        assert_eq!(q.snapshot(), before);
+        assert_eq!(q.pop(), Some(10));
+        assert_eq!(q.snapshot(), [11, 12, 13]);
+    }
+
+    #[test]
+    fn test_snapshot_skips_given_up_slots_and_stops_at_held_ones() {
+        let q: RawMpsc<u32> = RawMpsc::new(PRODUCER_RUN * 2);
+        let mut producer = q.producer();
+        producer.push(1).unwrap();
+        q.push(2).unwrap();
+        drop(producer);
+        assert_eq!(q.snapshot(), [1, 2]);
+
+        let held = q.reserve().unwrap();
+        q.push(4).unwrap();
+        assert_eq!(q.snapshot(), [1, 2]);
+        held.fill(3);
+        assert_eq!(q.snapshot(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_clone_of_a_half_full_queue_drains_the_same() {
+        let q = RawMpsc::new(8);
+        // Start the values part way round the ring
+        for i in 0..6 {
+            q.push(i.to_string()).unwrap();
+            q.pop().unwrap();
+        }
+        for i in 0..4 {
+            q.push(format!("v{i}")).unwrap();
+        }
+        let copy = q.clone();
+        assert_eq!(copy.capacity(), q.capacity());
+
+        // Each queue has its own slots and indices
+        q.push("only in q".to_string()).unwrap();
+        let original: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        let cloned: Vec<_> = core::iter::from_fn(|| copy.pop()).collect();
+        assert_eq!(original[..4], cloned[..]);
+        assert_eq!(original.len(), 5);
+    }
+
+    #[test]
+    fn test_default_capacity() {
+        #[derive(Default)]
+        struct Inbox {
+            queue: RawMpsc<String>,
+            spinning: RawMpsc<String, SpinOnly>,
+        }
+
+        let inbox = Inbox::default();
+        assert_eq!(inbox.queue.capacity(), DEFAULT_CAPACITY);
+        assert_eq!(inbox.spinning.capacity(), DEFAULT_CAPACITY);
+        assert!(inbox.queue.is_empty());
+    }
+
+    #[test]
+    fn test_push_overwrite_keeps_the_most_recent_values() {
+        for q in [RawMpsc::new(3), RawMpsc::with_capacity_pow2(3)] {
+            for i in 0..3 {
+                assert_eq!(q.push_overwrite(i), None);
+            }
+            for i in 3..10 {
+                assert_eq!(q.push_overwrite(i), Some(i - 3));
+            }
+            assert!(q.is_full());
+            for expected in 7..10 {
+                assert_eq!(q.pop(), Some(expected));
+            }
+            assert_eq!(q.pop(), None);
+        }
+        assert_eq!(RawMpsc::new(0).push_overwrite(1), Some(1));
+
+        // A front claimed but not written can't be evicted
+        let q = RawMpsc::new(2);
+        let mut h = q.producer();
+        h.push(0).unwrap();
+        assert_eq!(q.pop(), Some(0));
+        q.push(1).unwrap();
+        assert!(q.is_full());
+        assert_eq!(q.push_overwrite(2), Some(2));
+        drop(h);
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_push_bulk_wraps_around_the_ring() {
+        for q in [RawMpsc::new(5), RawMpsc::with_capacity_pow2(5)] {
+            let cap = q.capacity();
+            let mut next_pop = 0;
+            let mut items: Vec<usize> = (0..10 * cap).collect();
+            while !items.is_empty() {
+                // Leave a little in the ring so every batch starts at a different offset
+                q.push_bulk(&mut items);
+                while q.len() > 1 {
+                    assert_eq!(q.pop(), Some(next_pop));
+                    next_pop += 1;
+                }
+            }
+            while let Some(value) = q.pop() {
+                assert_eq!(value, next_pop);
+                next_pop += 1;
+            }
+            assert_eq!(next_pop, 10 * cap);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_push_bulk_multi_producer() {
+        const THREADS: usize = 4;
+        const ITEMS_PER_THREAD: usize = 1_000;
+
+        let q = Arc::new(RawMpsc::new(64));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    let mut items: Vec<_> = (0..ITEMS_PER_THREAD)
+                        .map(|i| t * ITEMS_PER_THREAD + i)
+                        .collect();
+                    while !items.is_empty() {
+                        if q.push_bulk(&mut items) == 0 {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // Each producer's values must come out in the order it pushed them
+        let mut last = [None; THREADS];
+        let mut received = 0;
+        while received < THREADS * ITEMS_PER_THREAD {
+            match q.pop() {
+                Some(value) => {
+                    let t = value / ITEMS_PER_THREAD;
+                    assert!(last[t] < Some(value), "out of order: {value}");
+                    last[t] = Some(value);
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_pop_bulk_takes_at_most_max() {
+        let q = RawMpsc::new(128);
+        for i in 0..100 {
+            q.push(i).unwrap();
+        }
+
+        let mut out = Vec::new();
+        assert_eq!(q.pop_bulk(&mut out, 64), 64);
+        assert_eq!(out, (0..64).collect::<Vec<_>>());
+
+        assert_eq!(q.pop_bulk(&mut out, 64), 36);
+        assert_eq!(out, (0..100).collect::<Vec<_>>());
+        assert_eq!(q.pop_bulk(&mut out, 64), 0);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_pop_bulk_wraps_around_the_ring() {
+        for q in [RawMpsc::new(5), RawMpsc::with_capacity_pow2(5)] {
+            let cap = q.capacity();
+            let mut next_push = 0;
+            let mut out = Vec::new();
+            for _ in 0..10 {
+                while q.push(next_push).is_ok() {
+                    next_push += 1;
+                }
+                // Leave one behind so every batch starts at a different offset
+                q.pop_bulk(&mut out, cap - 1);
+            }
+            q.pop_bulk(&mut out, usize::MAX);
+            assert_eq!(out, (0..next_push).collect::<Vec<_>>());
+        }
+    }
+
+    fn assert_backoff_strategy<B: Backoff + Send + Sync + 'static>() {
+        const THREADS: usize = 4;
+        const ITEMS_PER_THREAD: usize = 500;
+
+        let q = Arc::new(RawMpsc::<usize, B>::with_backoff(8));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    for i in 0..ITEMS_PER_THREAD {
+                        while q.push(t * ITEMS_PER_THREAD + i).is_err() {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        while seen.len() < THREADS * ITEMS_PER_THREAD {
+            match q.pop() {
+                Some(value) => assert!(seen.insert(value)),
+                None => thread::yield_now(),
+            }
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_pluggable_backoff_strategies() {
+        assert_backoff_strategy::<GlobalBackoff>();
+        assert_backoff_strategy::<YieldBackoff>();
+        assert_backoff_strategy::<SpinOnly>();
+    }
+
+    // Payloads don't need to implement `Debug`
+    #[test]
+    fn test_non_debug_payload() {
+        let ran = std::rc::Rc::new(std::cell::Cell::new(false));
+        let q: RawMpsc<Box<dyn FnOnce()>> = RawMpsc::new(2);
+        {
+            let ran = std::rc::Rc::clone(&ran);
+            q.push(Box::new(move || ran.set(true)))
+                .unwrap_or_else(|_| unreachable!());
+        }
+        (q.pop().unwrap())();
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn test_debug_shows_shape_not_values() {
+        struct Opaque;
+
+        let q: RawMpsc<Opaque> = RawMpsc::new(4);
+        q.push(Opaque).unwrap_or_else(|_| unreachable!());
+        assert_eq!(format!("{q:?}"), "RawMpsc { len: 1, capacity: 4, .. }");
+    }
+
+    #[test]
+    fn test_pop_with_works_on_the_value_in_place() {
+        let q = RawMpsc::new(2);
+        q.push(vec![1, 2]).unwrap();
+        q.push(vec![3]).unwrap();
+
+        let front: *const Vec<u32> = unsafe { q.peek() }.unwrap();
+        let sum = q.pop_with(|v| {
+            assert!(core::ptr::eq(v, front));
+            v.push(10);
+            v.iter().sum::<u32>()
+        });
+        assert_eq!(sum, Some(13));
+        assert_eq!(q.pop_with(|v| v.len()), Some(1));
+        assert_eq!(q.pop_with(|v| v.len()), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_pop_with_keeps_the_slot_until_done() {
+        let dropped = AtomicUsize::new(0);
+        let q = RawMpsc::new(1);
+        assert!(q.push(DropCounter(&dropped)).is_ok());
+        q.pop_with(|_| {
+            assert!(q.push(DropCounter(&dropped)).is_err());
+            assert_eq!(dropped.load(Relaxed), 1);
+        })
+        .unwrap();
+        assert_eq!(dropped.load(Relaxed), 2);
+        assert!(q.push(DropCounter(&dropped)).is_ok());
+    }
+
+    #[test]
+    fn test_pop_with_releases_the_slot_when_f_panics() {
+        let dropped = AtomicUsize::new(0);
+        let q = RawMpsc::new(1);
+        assert!(q.push(DropCounter(&dropped)).is_ok());
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            q.pop_with(|_| panic!("consumer failed"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(dropped.load(Relaxed), 1);
+        assert!(q.is_empty());
+        assert!(q.push(DropCounter(&dropped)).is_ok());
+        assert!(q.pop().is_some());
+    }
+
+    #[test]
+    fn test_reservation_holds_its_place_in_line() {
+        let q: RawMpsc<u32> = RawMpsc::new(3);
+        let first = q.reserve().unwrap();
+        q.push(2).unwrap();
+        assert_eq!(q.pop(), None);
+        let mut out = Vec::new();
+        assert_eq!(q.pop_bulk(&mut out, 3), 0);
+        first.fill(1);
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), Some(2));
+
+        // Handed back while it is the last claim, skipped once another follows it
+        drop(q.reserve().unwrap());
+        let skipped = q.reserve().unwrap();
+        q.push(3).unwrap();
+        drop(skipped);
+        assert_eq!(q.len(), 2);
+        assert_eq!(q.pop(), Some(3));
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_pop_with_steps_over_slots_a_producer_handle_gave_up() {
+        let q: RawMpsc<u32> = RawMpsc::new(PRODUCER_RUN * 2);
+        let mut producer = q.producer();
+        producer.push(1).unwrap();
+        q.push(2).unwrap();
+        drop(producer);
+        assert_eq!(q.pop_with(|v| *v), Some(1));
+        assert_eq!(q.pop_with(|v| *v), Some(2));
+        assert_eq!(q.pop_with(|v| *v), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_peek_does_not_consume() {
+        let q = RawMpsc::new(2);
+        assert_eq!(unsafe { q.peek() }, None);
+
+        q.push(String::from("first")).unwrap();
+        q.push(String::from("second")).unwrap();
+        assert_eq!(unsafe { q.peek() }.map(String::as_str), Some("first"));
+        assert_eq!(unsafe { q.peek() }.map(String::as_str), Some("first"));
+
+        assert_eq!(q.pop().as_deref(), Some("first"));
+        assert_eq!(unsafe { q.peek() }.map(String::as_str), Some("second"));
+        assert_eq!(q.pop().as_deref(), Some("second"));
+        assert_eq!(unsafe { q.peek() }, None);
+    }
+
+    struct DropCounter<'a>(&'a AtomicUsize);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_into_iter_yields_what_is_left() {
+        let q = RawMpsc::new(4);
+        // Wrap the indices so the remaining values cross the end of the ring
+        for i in 0..4 {
+            q.push(i).unwrap();
+        }
+        assert_eq!(q.pop(), Some(0));
+        assert_eq!(q.pop(), Some(1));
+        q.push(4).unwrap();
+        q.push(5).unwrap();
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.into_iter().collect::<Vec<_>>(), [3, 4, 5]);
+    }
+
+    #[test]
+    fn test_into_iter_drops_the_rest_once() {
+        let dropped = AtomicUsize::new(0);
+        let q = RawMpsc::new(4);
+        for _ in 0..4 {
+            q.push(DropCounter(&dropped))
+                .unwrap_or_else(|_| unreachable!());
+        }
+        let mut iter = q.into_iter();
+        drop(iter.next());
+        assert_eq!(dropped.load(Relaxed), 1);
+        drop(iter);
+        assert_eq!(dropped.load(Relaxed), 4);
+    }
+
+    #[test]
+    fn test_clear_drops_each_value_once() {
+        let dropped = AtomicUsize::new(0);
+        let count = || dropped.load(Relaxed);
+        let q = RawMpsc::new(4);
+
+        // Wrap the indices first so the cleared run crosses the end of the ring
+        for _ in 0..3 {
+            q.push(DropCounter(&dropped))
+                .unwrap_or_else(|_| unreachable!());
+        }
+        drop(q.pop());
+        drop(q.pop());
+        for _ in 0..3 {
+            q.push(DropCounter(&dropped))
+                .unwrap_or_else(|_| unreachable!());
+        }
+        assert_eq!(count(), 2);
+
+        q.clear();
+        assert_eq!(count(), 6);
+        assert!(q.is_empty());
+        assert!(q.pop().is_none());
+
+        // The queue is reusable at full capacity afterwards
+        for _ in 0..4 {
+            q.push(DropCounter(&dropped))
+                .unwrap_or_else(|_| unreachable!());
+        }
+        assert!(q.is_full());
+        drop(q);
+        assert_eq!(count(), 10);
+    }
+
+    #[test]
+    fn test_drop_releases_remaining_values() {
+        let dropped = AtomicUsize::new(0);
+        {
+            let q = RawMpsc::new(8);
+            for _ in 0..5 {
+                q.push(DropCounter(&dropped))
+                    .unwrap_or_else(|_| unreachable!());
+            }
+            drop(q.pop());
+        }
+        assert_eq!(dropped.load(Relaxed), 5);
+    }
+
+    #[test]
+    fn free_drop_test() {
+        let q = RawMpsc::new(10);
+        for i in 0..10 {
+            assert!(q.push(i).is_ok())
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_push_blocking_is_unparked_by_pop() {
+        let q = Arc::new(RawMpsc::new(2));
+        let producers: Vec<_> = (0..3)
+            .map(|p| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        q.push_blocking(p * 100 + i);
+                    }
+                })
+            })
+            .collect();
+
+        let mut received = Vec::new();
+        while received.len() < 300 {
+            match q.pop() {
+                Some(value) => received.push(value),
+                None => thread::yield_now(),
+            }
+        }
+        for p in producers {
+            p.join().unwrap();
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..300).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_stats_count_a_known_workload() {
+        use crate::mpsc::stats::QueueStats;
+
+        let q = RawMpsc::new(2);
+        assert_eq!(q.stats(), QueueStats::default());
+
+        assert!(q.push(1).is_ok());
+        assert!(q.push(2).is_ok());
+        assert_eq!(q.push(3), Err(3));
+        assert_eq!(q.pop(), Some(1));
+        // Only one of the two fits
+        assert_eq!(q.push_bulk(&mut vec![4, 5]), 1);
+        assert_eq!(q.push_bulk(&mut vec![6]), 0);
+        let mut out = Vec::new();
+        assert_eq!(q.pop_bulk(&mut out, 1), 1);
+        q.clear();
+
+        let stats = q.stats();
+        assert_eq!(
+            stats,
+            QueueStats {
+                pushes: 3,
+                pops: 3,
+                full: 2,
+                cas_retries: 0,
+            }
+        );
+    }
+
+    #[cfg(feature = "debug-internals")]
+    #[test]
+    fn test_debug_indices_wrap_at_the_slot_count() {
+        let q = RawMpsc::new(2);
+        assert_eq!(q.debug_indices(), (0, 0));
+        assert!(q.push(1).is_ok());
+        assert!(q.push(2).is_ok());
+        assert_eq!(q.debug_indices(), (2, 0));
+        assert_eq!(q.pop(), Some(1));
+        assert!(q.push(3).is_ok());
+        // Three slots back a capacity of two
+        assert_eq!(q.debug_indices(), (0, 1));
+    }
+}
+
+/// Model-checked tests, run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+#[cfg(all(loom, test))]
+mod loom_tests {
+    use super::*;
+    use crate::backoff::SpinOnly;
+    use loom::model::Builder;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    /// Explores every interleaving with at most three preemptions, which is enough to
+    /// interleave both producers' claim and write with the consumer's read while keeping
+    /// the model small.
+    fn model(f: impl Fn() + Sync + Send + 'static) {
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(f);
+    }
+
+    /// Runs two producers pushing `per_producer` values each into a queue of `capacity`,
+    /// and checks the consumer receives every value exactly once, in per-producer order.
+    fn two_producers_one_consumer(capacity: usize, per_producer: usize) {
+        model(move || {
+            let q = Arc::new(RawMpsc::<usize, SpinOnly>::with_backoff(capacity));
+
+            let producers: Vec<_> = (0..2)
+                .map(|p| {
+                    let q = Arc::clone(&q);
+                    thread::spawn(move || {
+                        for i in 0..per_producer {
+                            let mut data = p * per_producer + i;
+                            while let Err(back) = q.push(data) {
+                                data = back;
+                                thread::yield_now();
+                            }
+                        }
+                    })
+                })
+                .collect();
+
+            let mut received = Vec::new();
+            while received.len() < 2 * per_producer {
+                match q.pop() {
+                    Some(data) => received.push(data),
+                    None => thread::yield_now(),
+                }
+            }
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            assert_eq!(q.pop(), None);
+
+            for p in 0..2 {
+                let from_p: Vec<_> = received
+                    .iter()
+                    .copied()
+                    .filter(|data| data / per_producer == p)
+                    .collect();
+                let expected: Vec<_> = (p * per_producer..(p + 1) * per_producer).collect();
+                assert_eq!(from_p, expected);
+            }
+        });
+    }
+
+    #[test]
+    fn loom_two_producers_one_value_each() {
+        two_producers_one_consumer(2, 1);
+    }
+
+    #[test]
+    fn loom_two_producers_through_a_full_queue() {
+        // A single free slot makes producers hit `Full` and the ring wrap around. Producers
+        // drop a value that does not fit instead of retrying: a retry loop would let the
+        // model re-read a stale `tail` forever.
+        model(|| {
+            let q = Arc::new(RawMpsc::<usize, SpinOnly>::with_backoff(1));
+
+            let producers: Vec<_> = (0..2)
+                .map(|p| {
+                    let q = Arc::clone(&q);
+                    thread::spawn(move || {
+                        (2 * p..2 * p + 2)
+                            .filter_map(|data| q.push(data).err())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            let mut received: Vec<_> = (0..2).filter_map(|_| q.pop()).collect();
+            let mut rejected = Vec::new();
+            for producer in producers {
+                rejected.extend(producer.join().unwrap());
+            }
+            received.extend(core::iter::from_fn(|| q.pop()));
+
+            for p in 0..2 {
+                let from_p: Vec<_> = received.iter().filter(|&&data| data / 2 == p).collect();
+                assert!(from_p.is_sorted());
+            }
+            received.extend(rejected);
+            received.sort_unstable();
+            assert_eq!(received, [0, 1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn loom_pop_bulk_sees_every_claimed_value() {
+        model(|| {
+            let q = Arc::new(RawMpsc::<usize, SpinOnly>::with_backoff(2));
+            let producers: Vec<_> = (0..2)
+                .map(|p| {
+                    let q = Arc::clone(&q);
+                    thread::spawn(move || {
+                        while q.push(p).is_err() {
+                            thread::yield_now();
+                        }
+                    })
+                })
+                .collect();
+
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                if q.pop_bulk(&mut received, 2) == 0 {
+                    thread::yield_now();
+                }
+            }
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            received.sort_unstable();
+            assert_eq!(received, [0, 1]);
+        });
+    }
+
+    // A slot the consumer is working on in place is not handed to a producer until it is done
+    #[test]
+    fn loom_pop_with_holds_the_slot_against_a_push() {
+        model(|| {
+            let q = Arc::new(RawMpsc::<usize, SpinOnly>::with_backoff(1));
+            q.push(0).unwrap();
+
+            let pusher = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.push(1).is_ok())
+            };
+
+            let first = q.pop_with(|v| {
+                thread::yield_now();
+                *v
+            });
+            assert_eq!(first, Some(0));
+            let pushed = pusher.join().unwrap();
+            assert_eq!(q.pop(), pushed.then_some(1));
+        });
+    }
+
+    // The consumer neither waits on a reserved slot nor gets stuck once it is handed back
+    #[test]
+    fn loom_reservation_races_a_pop() {
+        model(|| {
+            let q = Arc::new(RawMpsc::<usize, SpinOnly>::with_backoff(2));
+
+            let reserver = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    let first = q.reserve().unwrap();
+                    q.push(1).unwrap();
+                    drop(q.reserve());
+                    first.fill(0);
+                })
+            };
+
+            let mut received: Vec<_> = (0..2).filter_map(|_| q.pop()).collect();
+            reserver.join().unwrap();
+            received.extend(core::iter::from_fn(|| q.pop()));
+            assert_eq!(received, [0, 1]);
+            assert!(q.is_empty());
+        });
+    }
+
+    // A released run is either handed back or skipped, never read as a value
+    #[test]
+    fn loom_producer_handle_release_races_a_push() {
+        model(|| {
+            let q = Arc::new(RawMpsc::<usize, SpinOnly>::with_backoff(PRODUCER_RUN + 2));
+
+            let handle = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    let mut tx = q.producer();
+                    tx.push(0).unwrap();
+                })
+            };
+            let pusher = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.push(1).is_ok())
+            };
+
+            let mut received: Vec<_> = (0..2).filter_map(|_| q.pop()).collect();
+            handle.join().unwrap();
+            let pushed = pusher.join().unwrap();
+            received.extend(core::iter::from_fn(|| q.pop()));
+            received.sort_unstable();
+            assert_eq!(received, if pushed { vec![0, 1] } else { vec![0] });
+        });
     }
 }
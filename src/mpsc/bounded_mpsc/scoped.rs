@@ -0,0 +1,186 @@
+//! Borrowing halves of a [`RawMpsc`], for producers and a consumer that live inside
+//! `std::thread::scope`.
+//!
+//! [`RawMpsc::split`] hands out a [`Producer`] and a [`Consumer`] that hold a plain
+//! reference to the queue instead of an `Arc`, so sharing it costs no reference counting.
+//! The lifetime ties both halves to the queue, and the `&mut` borrow taken by `split` makes
+//! the `Consumer` the only way to pop while they are alive.
+
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use super::allocator::{Alloc, Global};
+use super::raw_mpsc::{ProducerHandle, RawMpsc};
+use crate::backoff::{Backoff, GlobalBackoff};
+
+/// The producing half of a split [`RawMpsc`].
+///
+/// It is `Copy`, so every scoped thread can take its own.
+pub struct Producer<'a, T, B = GlobalBackoff, A: Alloc = Global> {
+    queue: &'a RawMpsc<T, B, A>,
+}
+
+impl<'a, T, B, A: Alloc> Producer<'a, T, B, A> {
+    pub(super) fn new(queue: &'a RawMpsc<T, B, A>) -> Self {
+        Self { queue }
+    }
+}
+
+impl<'a, T, B: Backoff, A: Alloc> Producer<'a, T, B, A> {
+    /// Attempts to push data into the queue, returning it back in `Err` if the queue is full.
+    pub fn push(&self, data: T) -> Result<(), T> {
+        self.queue.push(data)
+    }
+
+    /// Attempts a single push without retrying; see [`RawMpsc::try_push_weak`].
+    pub fn try_push_weak(&self, data: T) -> Result<(), T> {
+        self.queue.try_push_weak(data)
+    }
+
+    /// Pushes as many values from the front of `items` as fit; see [`RawMpsc::push_bulk`].
+    pub fn push_bulk(&self, items: &mut Vec<T>) -> usize {
+        self.queue.push_bulk(items)
+    }
+
+    /// Pushes data into the queue, parking the calling thread while the queue is full.
+    #[cfg(feature = "std")]
+    pub fn push_blocking(&self, data: T) {
+        self.queue.push_blocking(data)
+    }
+
+    /// Returns a [`ProducerHandle`] for a burst of values; see [`RawMpsc::producer`].
+    pub fn producer(&self) -> ProducerHandle<'a, T, B, A> {
+        self.queue.producer()
+    }
+
+    /// Returns the number of values currently buffered in the queue.
+    ///
+    /// Only a snapshot while other producers are running; see [`RawMpsc::len`].
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns `true` if the queue holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns the maximum number of values the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+}
+
+impl<T, B, A: Alloc> Clone for Producer<'_, T, B, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, B, A: Alloc> Copy for Producer<'_, T, B, A> {}
+
+/// The consuming half of a split [`RawMpsc`].
+///
+/// It can be moved to another thread but is neither `Clone` nor `Sync`, so only one thread
+/// can ever pop.
+pub struct Consumer<'a, T, B = GlobalBackoff, A: Alloc = Global> {
+    queue: &'a RawMpsc<T, B, A>,
+    /// Opts out of `Sync` so a `&Consumer` can't be shared between consumers.
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<'a, T, B, A: Alloc> Consumer<'a, T, B, A> {
+    pub(super) fn new(queue: &'a RawMpsc<T, B, A>) -> Self {
+        Self {
+            queue,
+            _not_sync: PhantomData,
+        }
+    }
+}
+
+impl<T, B: Backoff, A: Alloc> Consumer<'_, T, B, A> {
+    /// Attempts to pop a value from the queue, returning `None` if it is empty.
+    pub fn pop(&self) -> Option<T> {
+        self.queue.pop()
+    }
+
+    /// Runs `f` on the oldest value in place, then drops it; see [`RawMpsc::pop_with`].
+    pub fn pop_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.queue.pop_with(f)
+    }
+
+    /// Pops up to `max` values into `out`; see [`RawMpsc::pop_bulk`].
+    pub fn pop_bulk(&self, out: &mut Vec<T>, max: usize) -> usize {
+        self.queue.pop_bulk(out, max)
+    }
+
+    /// Returns the number of values currently buffered in the queue.
+    ///
+    /// Only a snapshot while producers are running; see [`RawMpsc::len`].
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns `true` if the queue holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns the maximum number of values the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_split_halves_share_the_queue() {
+        let mut q = RawMpsc::new(2);
+        {
+            let (tx, rx) = q.split();
+            let tx2 = tx;
+            assert_eq!(tx.push(1), Ok(()));
+            assert_eq!(tx2.push(2), Ok(()));
+            assert_eq!(tx.push(3), Err(3));
+            assert_eq!((rx.len(), rx.capacity()), (2, 2));
+            assert_eq!(rx.pop_with(|v| *v * 10), Some(10));
+            assert_eq!(rx.pop(), Some(2));
+            assert!(tx.is_empty());
+        }
+        // The queue is usable on its own again once both halves are gone
+        q.push(4).unwrap();
+        assert_eq!(q.pop(), Some(4));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_scoped_producers() {
+        const PRODUCERS: usize = 4;
+        const PER_PRODUCER: usize = 1000;
+
+        let mut q = RawMpsc::new(16);
+        let (tx, rx) = q.split();
+        let mut received = Vec::new();
+        thread::scope(|s| {
+            for p in 0..PRODUCERS {
+                s.spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        tx.push_blocking(p * PER_PRODUCER + i);
+                    }
+                });
+            }
+            while received.len() < PRODUCERS * PER_PRODUCER {
+                if rx.pop_bulk(&mut received, 16) == 0 {
+                    thread::yield_now();
+                }
+            }
+        });
+        received.sort_unstable();
+        assert!(received.into_iter().eq(0..PRODUCERS * PER_PRODUCER));
+    }
+}
@@ -1,31 +1,89 @@
-use std::{
-    alloc::{Layout, alloc, dealloc},
-    ptr::NonNull,
-    sync::atomic::Ordering::Release,
-};
-
-use crate::mpsc::slot::READY;
+use alloc::alloc::handle_alloc_error;
+use core::alloc::Layout;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
 
+use super::super::errors::AllocError;
 use super::super::slot::Slot;
+use super::allocator::{Alloc, Global};
+
+/// Returns how many slots a ring holding `capacity` values needs: one more, so a full ring
+/// can be told apart from an empty one.
+pub fn slots_for(capacity: usize) -> usize {
+    try_slots_for(capacity).unwrap_or_else(|_| capacity_overflow())
+}
+
+/// Like [`slots_for`], but reports an overflow instead of panicking.
+pub fn try_slots_for(capacity: usize) -> Result<usize, AllocError> {
+    capacity.checked_add(1).ok_or(AllocError::CapacityOverflow)
+}
 
-pub struct SlotArr<T> {
+/// Panics for a capacity whose slots can't be addressed.
+#[cold]
+#[track_caller]
+pub(super) fn capacity_overflow() -> ! {
+    panic!("capacity overflow")
+}
+
+pub struct SlotArr<T, A: Alloc = Global> {
     pub(super) ptr: NonNull<Slot<T>>,
     pub(super) capacity: usize,
+    alloc: A,
 }
 
 impl<T> SlotArr<T> {
     pub fn new(capacity: usize) -> Self {
-        let layout = Layout::array::<Slot<T>>(capacity).unwrap();
-        let ptr = unsafe { NonNull::new(alloc(layout) as _).unwrap() };
+        Self::new_in(capacity, Global)
+    }
+}
+
+impl<T, A: Alloc> SlotArr<T, A> {
+    /// Allocates `capacity` slots from `alloc`, which also frees them on drop.
+    pub fn new_in(capacity: usize, alloc: A) -> Self {
+        let layout = Self::layout(capacity);
+        Self::allocate_in(capacity, layout, alloc).unwrap_or_else(|| handle_alloc_error(layout))
+    }
+
+    /// Like [`new_in`](Self::new_in), but reports failure instead of panicking on an
+    /// overflowing capacity or aborting when `alloc` is out of memory.
+    pub fn try_new_in(capacity: usize, alloc: A) -> Result<Self, AllocError> {
+        let layout =
+            Layout::array::<Slot<T>>(capacity).map_err(|_| AllocError::CapacityOverflow)?;
+        Self::allocate_in(capacity, layout, alloc).ok_or(AllocError::OutOfMemory)
+    }
+
+    fn allocate_in(capacity: usize, layout: Layout, alloc: A) -> Option<Self> {
+        // An all-zero slot is an empty `READY` one, so fresh zeroed memory needs no further
+        // setup. Loom's cells and atomics carry tracking state and are built one by one.
+        #[cfg(not(all(loom, test)))]
+        let ptr = alloc.allocate_zeroed(layout);
+        #[cfg(all(loom, test))]
+        let ptr = alloc.allocate(layout);
+        let ptr = ptr?.cast();
+        #[cfg(all(loom, test))]
         Self::init_slots(ptr, capacity);
-        Self { ptr, capacity }
+        Some(Self {
+            ptr,
+            capacity,
+            alloc,
+        })
+    }
+
+    /// Layout of `capacity` slots, which must not take more than `isize::MAX` bytes.
+    #[track_caller]
+    fn layout(capacity: usize) -> Layout {
+        Layout::array::<Slot<T>>(capacity).unwrap_or_else(|_| capacity_overflow())
     }
 
+    /// Returns the allocator the slots live in.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    #[cfg(all(loom, test))]
     fn init_slots(ptr: NonNull<Slot<T>>, capacity: usize) {
         for idx in 0..capacity {
-            unsafe {
-                (&*ptr.as_ptr().add(idx)).state.store(READY, Release);
-            }
+            unsafe { ptr.as_ptr().add(idx).write(Slot::new()) };
         }
     }
 
@@ -33,16 +91,164 @@ impl<T> SlotArr<T> {
         unsafe { (&*self.ptr.as_ptr().add(index)).set(data) }
     }
 
+    /// # Safety
+    ///
+    /// See [`Slot::peek`].
+    pub unsafe fn peek(&self, index: usize) -> Option<&T> {
+        unsafe { (&*self.ptr.as_ptr().add(index)).peek() }
+    }
+
     pub fn unset(&self, index: usize) -> Result<T, ()> {
         unsafe { (&*self.ptr.as_ptr().add(index)).unset() }
     }
+
+    /// See [`Slot::begin_take`].
+    pub fn begin_take(&self, index: usize) -> bool {
+        unsafe { (&*self.ptr.as_ptr().add(index)).begin_take() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::taken_ptr`].
+    pub unsafe fn taken_ptr(&self, index: usize) -> *mut T {
+        unsafe { (&*self.ptr.as_ptr().add(index)).taken_ptr() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::finish_take`].
+    pub unsafe fn finish_take(&self, index: usize) -> T {
+        unsafe { (&*self.ptr.as_ptr().add(index)).finish_take() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::drop_taken`].
+    pub unsafe fn drop_taken(&self, index: usize) {
+        unsafe { (&*self.ptr.as_ptr().add(index)).drop_taken() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::abort_take`].
+    pub unsafe fn abort_take(&self, index: usize) {
+        unsafe { (&*self.ptr.as_ptr().add(index)).abort_take() }
+    }
+
+    /// See [`Slot::skip`].
+    pub fn skip(&self, index: usize) -> bool {
+        unsafe { (&*self.ptr.as_ptr().add(index)).skip() }
+    }
+
+    /// See [`Slot::hold`].
+    pub fn hold(&self, index: usize) -> bool {
+        unsafe { (&*self.ptr.as_ptr().add(index)).hold() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::fill_held`].
+    pub unsafe fn fill_held(&self, index: usize, data: T) {
+        unsafe { (&*self.ptr.as_ptr().add(index)).fill_held(data) }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::held_ptr`].
+    pub unsafe fn held_ptr(&self, index: usize) -> *mut MaybeUninit<T> {
+        unsafe { (&*self.ptr.as_ptr().add(index)).held_ptr() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::commit_held`].
+    pub unsafe fn commit_held(&self, index: usize) {
+        unsafe { (&*self.ptr.as_ptr().add(index)).commit_held() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::release_held`].
+    pub unsafe fn release_held(&self, index: usize, skip: bool) {
+        unsafe { (&*self.ptr.as_ptr().add(index)).release_held(skip) }
+    }
+
+    /// See [`Slot::is_skipped`].
+    pub fn is_skipped(&self, index: usize) -> bool {
+        unsafe { (&*self.ptr.as_ptr().add(index)).is_skipped() }
+    }
+
+    /// See [`Slot::is_held`].
+    pub fn is_held(&self, index: usize) -> bool {
+        unsafe { (&*self.ptr.as_ptr().add(index)).is_held() }
+    }
+
+    /// See [`Slot::clear_skip`].
+    pub fn clear_skip(&self, index: usize) -> bool {
+        unsafe { (&*self.ptr.as_ptr().add(index)).clear_skip() }
+    }
+
+    /// Puts every slot back into the `READY` state; see [`Slot::reset`].
+    pub fn reset(&mut self) {
+        for idx in 0..self.capacity {
+            unsafe { (*self.ptr.as_ptr().add(idx)).reset() };
+        }
+    }
+
+    /// Writes a value without touching the slot state.
+    ///
+    /// # Safety
+    ///
+    /// See [`Slot::unchecked_set`].
+    pub unsafe fn write(&self, index: usize, data: T) {
+        unsafe { (&*self.ptr.as_ptr().add(index)).unchecked_set(data) }
+    }
+
+    /// Reads a value out without touching the slot state.
+    ///
+    /// # Safety
+    ///
+    /// See [`Slot::unchecked_unset`].
+    pub unsafe fn read(&self, index: usize) -> T {
+        unsafe { (&*self.ptr.as_ptr().add(index)).unchecked_unset() }
+    }
 }
 
-impl<T> Drop for SlotArr<T> {
+impl<T, A: Alloc> Drop for SlotArr<T, A> {
     fn drop(&mut self) {
-        let layout = Layout::array::<Slot<T>>(self.capacity).unwrap();
+        let layout = Self::layout(self.capacity);
         unsafe {
-            dealloc(self.ptr.as_ptr() as _, layout);
+            self.alloc.deallocate(self.ptr.cast(), layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeroed_slots_start_ready() {
+        const CAPACITY: usize = 1 << 16;
+        let arr: SlotArr<u64> = SlotArr::new(CAPACITY);
+        // Only a `READY` slot accepts a value
+        for idx in 0..CAPACITY {
+            assert!(arr.set(idx, idx as u64).is_ok(), "slot {idx} not ready");
+        }
+        for idx in 0..CAPACITY {
+            assert_eq!(arr.unset(idx), Ok(idx as u64));
         }
     }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn test_layout_overflow_panics_clearly() {
+        SlotArr::<u64>::new(isize::MAX as usize / size_of::<Slot<u64>>() + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn test_slot_count_overflow_panics_clearly() {
+        slots_for(usize::MAX);
+    }
 }
@@ -0,0 +1,654 @@
+//! State shared between the sending and receiving halves of a channel.
+
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::sync::atomic::{
+    AtomicBool, AtomicUsize,
+    Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst},
+    fence,
+};
+#[cfg(feature = "std")]
+use core::task::{Context, Poll};
+#[cfg(feature = "std")]
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "std")]
+use crate::backoff::LocalBackoff;
+#[cfg(feature = "std")]
+use crate::mpsc::errors::{RecvError, RecvTimeoutError, SendError};
+use crate::mpsc::errors::{TryRecvError, TrySendError};
+#[cfg(feature = "std")]
+use crate::mpsc::notify::{RecvNotify, SendNotify};
+use crate::mpsc::queue::Ack;
+
+/// The minimal queue interface a channel is built on.
+pub(crate) trait RawQueue {
+    /// The type of the values carried by the queue.
+    type Item;
+
+    /// Attempts to enqueue `data`, handing it back if the queue has no room for it.
+    fn push(&self, data: Self::Item) -> Result<(), Self::Item>;
+
+    /// Dequeues the oldest value, or returns `None` if the queue is empty.
+    ///
+    /// Must only be called from the single consumer.
+    fn pop(&self) -> Option<Self::Item>;
+
+    /// Runs `f` on the oldest value in place and drops it, or returns `None` if the queue is
+    /// empty.
+    ///
+    /// Must only be called from the single consumer.
+    fn pop_with<R>(&self, f: impl FnOnce(&mut Self::Item) -> R) -> Option<R>;
+
+    /// Runs `f` on the oldest value in place, dropping it on [`Ack::Ack`] and leaving it at
+    /// the front on [`Ack::Nack`]. Returns `None` if the queue is empty.
+    ///
+    /// Must only be called from the single consumer.
+    fn pop_ack(&self, f: impl FnOnce(&mut Self::Item) -> Ack) -> Option<Ack>;
+
+    /// Returns how many values are buffered; only a snapshot while producers are running.
+    #[cfg(feature = "std")]
+    fn len(&self) -> usize;
+}
+
+/// The payload behind the `Arc` held by every `Sender` and the `Receiver` of a channel.
+///
+/// `Q` is the raw queue carrying the values; the remaining fields track which endpoints are
+/// still alive so either side can tell when the other has gone away.
+pub(crate) struct Chan<Q> {
+    /// The queue the values travel through.
+    pub(crate) queue: Q,
+    /// Number of live `Sender`s.
+    senders: AtomicUsize,
+    /// Cleared when the `Receiver` is dropped.
+    receiver_alive: AtomicBool,
+    /// Set by `close`; from then on sends fail while buffered values are still delivered.
+    closed: AtomicBool,
+    /// Present on a zero-capacity channel, where the queue is a single handoff slot: even
+    /// while the slot is free, odd from when a sender claims it until the receiver has taken
+    /// the value. A sender waits for the count to move on from the odd value it claimed.
+    handoff: Option<AtomicUsize>,
+    /// Number of sends that got past the `closed` check and may still be pushing.
+    ///
+    /// The receiver only reports a closed channel as disconnected once this drops to zero,
+    /// so a value whose send succeeded is never left behind in the queue.
+    sending: AtomicUsize,
+    /// Where a blocked `Receiver` waits for producers.
+    #[cfg(feature = "std")]
+    recv_notify: RecvNotify,
+    /// Where blocked or async `Sender`s wait for the queue to make room.
+    #[cfg(feature = "std")]
+    send_notify: SendNotify,
+}
+
+impl<Q> Chan<Q> {
+    /// Wraps `queue` in a channel with one sender and a live receiver.
+    pub(crate) fn new(queue: Q) -> Self {
+        Self {
+            queue,
+            senders: AtomicUsize::new(1),
+            receiver_alive: AtomicBool::new(true),
+            closed: AtomicBool::new(false),
+            handoff: None,
+            sending: AtomicUsize::new(0),
+            #[cfg(feature = "std")]
+            recv_notify: RecvNotify::new(),
+            #[cfg(feature = "std")]
+            send_notify: SendNotify::new(),
+        }
+    }
+
+    /// Wraps a single-slot `queue` in a zero-capacity channel, whose senders wait for the
+    /// receiver to take each value.
+    pub(crate) fn rendezvous(queue: Q) -> Self {
+        Self {
+            handoff: Some(AtomicUsize::new(0)),
+            ..Self::new(queue)
+        }
+    }
+
+    /// Returns `true` until the receiving half has been dropped.
+    #[inline]
+    pub(crate) fn is_receiver_alive(&self) -> bool {
+        self.receiver_alive.load(Acquire)
+    }
+
+    /// Records that the receiving half has been dropped.
+    #[inline]
+    pub(crate) fn disconnect_receiver(&self) {
+        self.receiver_alive.store(false, Release);
+        #[cfg(feature = "std")]
+        self.send_notify.notify_all();
+    }
+
+    /// Records a new `Sender` handle.
+    ///
+    /// New handles are only ever cloned from a live one, so the count is at least one here
+    /// and can't be brought back from zero; as with `Arc`, `Relaxed` is enough.
+    #[inline]
+    pub(crate) fn acquire_sender(&self) {
+        self.senders.fetch_add(1, Relaxed);
+    }
+
+    /// Records a new `Sender` handle unless every sender is already gone, returning whether
+    /// it did.
+    ///
+    /// Unlike [`acquire_sender`](Self::acquire_sender) this doesn't need a live handle, so
+    /// it must not bring a disconnected channel back to life.
+    #[inline]
+    pub(crate) fn try_acquire_sender(&self) -> bool {
+        self.senders
+            .fetch_update(Relaxed, Relaxed, |senders| {
+                senders.checked_add(1).filter(|&n| n > 1)
+            })
+            .is_ok()
+    }
+
+    /// Records that a `Sender` handle has been dropped, waking the receiver if it was the
+    /// last one.
+    ///
+    /// The `Release` half publishes every value the sender pushed before the receiver can
+    /// observe the count reaching zero.
+    #[inline]
+    pub(crate) fn release_sender(&self) {
+        if self.senders.fetch_sub(1, AcqRel) == 1 {
+            self.wake_receiver();
+        }
+    }
+
+    /// Returns `true` once every `Sender` has been dropped.
+    #[inline]
+    pub(crate) fn is_disconnected(&self) -> bool {
+        self.senders.load(Acquire) == 0
+    }
+
+    /// Closes the channel: every later send fails with [`TrySendError::Closed`], while the
+    /// receiver goes on draining what is already buffered.
+    ///
+    /// Parked senders are woken so they fail too, and a parked receiver so it can notice the
+    /// channel ending once it is empty.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, SeqCst);
+        #[cfg(feature = "std")]
+        self.send_notify.notify_all();
+        self.wake_receiver();
+    }
+
+    /// Returns `true` once [`close`](Self::close) has been called.
+    #[inline]
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Acquire)
+    }
+
+    /// Returns `true` once no more values can arrive: every sender has been dropped, or the
+    /// channel was closed and no send that beat the close is still pushing.
+    ///
+    /// The `SeqCst` accesses pair with those in `try_send`: a sender either sees `closed` and
+    /// backs off, or its increment of `sending` is seen here.
+    #[inline]
+    fn is_finished(&self) -> bool {
+        self.is_disconnected() || (self.closed.load(SeqCst) && self.sending.load(SeqCst) == 0)
+    }
+
+    /// Unparks the receiver if it is blocked; without `std` nothing can block.
+    #[inline]
+    fn wake_receiver(&self) {
+        #[cfg(feature = "std")]
+        self.recv_notify.notify();
+    }
+
+    /// Wakes one sender waiting for room, if there is any; without `std` nothing can wait.
+    ///
+    /// On a zero-capacity channel every sender is woken instead, since the one waiting for
+    /// its value to be taken may be anywhere in line.
+    #[inline]
+    fn wake_sender(&self) {
+        #[cfg(feature = "std")]
+        if self.handoff.is_some() {
+            self.send_notify.notify_all();
+        } else {
+            self.send_notify.notify_one();
+        }
+    }
+
+    /// Returns `true` while the receiver is blocked waiting for a value.
+    #[inline]
+    fn is_receiver_waiting(&self) -> bool {
+        #[cfg(feature = "std")]
+        {
+            self.recv_notify.is_waiting()
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            false
+        }
+    }
+
+    /// Lets a sender of a zero-capacity channel know that the receiver has started waiting,
+    /// so one that could not hand its value over can retry.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn offer_to_rendezvous_sender(&self) {
+        if self.handoff.is_some() {
+            self.send_notify.notify_one();
+        }
+    }
+
+    /// Registers an async sender to be woken once a slot frees up or the receiver is dropped.
+    ///
+    /// The caller must retry its send afterwards, before returning `Poll::Pending`.
+    #[cfg(feature = "async")]
+    pub(crate) fn register_sender(&self, waker: &core::task::Waker) {
+        self.send_notify.register_waker(waker);
+    }
+
+    /// Takes an async sender registered with `waker` off the wait list, for a send that
+    /// stops waiting.
+    ///
+    /// If a wake-up had already taken it off, that wake-up may have been meant for the next
+    /// producer in line, so it is handed on.
+    #[cfg(feature = "async")]
+    pub(crate) fn unregister_sender(&self, waker: &core::task::Waker) {
+        if !self.send_notify.cancel_waker(waker) {
+            self.send_notify.notify_one();
+        }
+    }
+}
+
+impl<Q: RawQueue> Chan<Q> {
+    /// Attempts to enqueue a value without waiting.
+    ///
+    /// On a zero-capacity channel the value is only accepted while the receiver is blocked
+    /// waiting for one, and is then handed straight to it.
+    pub(crate) fn try_send(&self, data: Q::Item) -> Result<(), TrySendError<Q::Item>> {
+        self.send_with(data, |data| match &self.handoff {
+            None => self.queue.push(data),
+            Some(handoff) if self.is_receiver_waiting() => self.hand_off(handoff, data).map(drop),
+            Some(_) => Err(data),
+        })
+    }
+
+    /// Claims the free handoff slot of a zero-capacity channel and puts `data` in it,
+    /// returning the odd count that stays put until the receiver takes the value.
+    fn hand_off(&self, handoff: &AtomicUsize, data: Q::Item) -> Result<usize, Q::Item> {
+        let free = handoff.load(Acquire);
+        if free % 2 == 1
+            || handoff
+                .compare_exchange(free, free + 1, AcqRel, Relaxed)
+                .is_err()
+        {
+            return Err(data);
+        }
+        // The `Acquire` above saw the receiver's bump, made after it emptied the slot.
+        if self.queue.push(data).is_err() {
+            unreachable!("a claimed handoff slot is always empty");
+        }
+        Ok(free + 1)
+    }
+
+    /// Common part of every send: fails once the receiver is gone or the channel is closed,
+    /// and otherwise enqueues with `push`, reporting its failure as [`TrySendError::Full`].
+    fn send_with(
+        &self,
+        data: Q::Item,
+        push: impl FnOnce(Q::Item) -> Result<(), Q::Item>,
+    ) -> Result<(), TrySendError<Q::Item>> {
+        match self.begin_send() {
+            Ok(()) => {}
+            Err(TrySendError::Closed(())) => return Err(TrySendError::Closed(data)),
+            Err(_) => return Err(TrySendError::Disconnected(data)),
+        }
+        let result = push(data).map_err(TrySendError::Full);
+        self.end_send(result.is_ok());
+        result
+    }
+
+    /// Counts a send in as in flight, or fails if the receiver is gone or the channel is
+    /// closed.
+    ///
+    /// Every successful call must be paired with an [`end_send`](Self::end_send) once the
+    /// value has been pushed, or given up; until then the receiver does not report a closed
+    /// channel as disconnected.
+    pub(crate) fn begin_send(&self) -> Result<(), TrySendError<()>> {
+        if !self.is_receiver_alive() {
+            return Err(TrySendError::Disconnected(()));
+        }
+        self.sending.fetch_add(1, SeqCst);
+        if self.closed.load(SeqCst) {
+            self.end_send(false);
+            return Err(TrySendError::Closed(()));
+        }
+        Ok(())
+    }
+
+    /// Counts a send begun with [`begin_send`](Self::begin_send) out again, waking the
+    /// receiver if it `pushed` a value.
+    pub(crate) fn end_send(&self, pushed: bool) {
+        // `Release` publishes the pushed value to a receiver that sees the count drop.
+        self.sending.fetch_sub(1, Release);
+        // After a failure only a receiver waiting for the close to settle cares.
+        if pushed || self.closed.load(Relaxed) {
+            self.wake_receiver();
+        }
+    }
+
+    /// Issues a `Release` fence and unparks the receiver once, if it is blocked.
+    pub(crate) fn publish(&self) {
+        fence(Release);
+        self.wake_receiver();
+    }
+
+    /// Returns `true` for a zero-capacity channel, whose values are handed over directly.
+    #[inline]
+    pub(crate) fn is_rendezvous(&self) -> bool {
+        self.handoff.is_some()
+    }
+
+    /// Attempts to receive a value without waiting.
+    pub(crate) fn try_recv(&self) -> Result<Q::Item, TryRecvError> {
+        if let Some(data) = self.take() {
+            return Ok(data);
+        }
+        if self.is_finished() {
+            // The last sender may have pushed right before dropping or the close settling;
+            // having observed that, everything it sent is visible, so look once more.
+            return self.take().ok_or(TryRecvError::Disconnected);
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    /// Runs `f` on the oldest value without moving it out of the queue, then drops it.
+    ///
+    /// Returns `None` if no value is ready. On a zero-capacity channel the sender of the
+    /// value is only released once `f` has returned.
+    pub(crate) fn try_recv_with<R>(&self, f: impl FnOnce(&mut Q::Item) -> R) -> Option<R> {
+        let ret = self.queue.pop_with(f)?;
+        self.took();
+        Some(ret)
+    }
+
+    /// Runs `f` on the oldest value in place and drops it unless `f` asks for it to be
+    /// handed out again.
+    ///
+    /// Returns `None` if no value is ready. Senders only hear about room, and the sender on a
+    /// zero-capacity channel is only released, once a value is acknowledged.
+    pub(crate) fn try_recv_ack(&self, f: impl FnOnce(&mut Q::Item) -> Ack) -> Option<Ack> {
+        let ack = self.queue.pop_ack(f)?;
+        if ack == Ack::Ack {
+            self.took();
+        }
+        Some(ack)
+    }
+
+    /// Closes the channel like [`close`](Self::close), then drops every value buffered in it.
+    ///
+    /// Sends that got past the `closed` check before it was set may still land afterwards;
+    /// they are received or dropped along with the receiver as usual.
+    pub(crate) fn close_and_clear(&self) {
+        self.close();
+        while self.take().is_some() {}
+    }
+
+    /// Pops a value and lets the senders know there is room again.
+    fn take(&self) -> Option<Q::Item> {
+        let data = self.queue.pop()?;
+        self.took();
+        Some(data)
+    }
+
+    /// Lets the senders know a value was taken out of the queue.
+    fn took(&self) {
+        if let Some(handoff) = &self.handoff {
+            // Frees the handoff slot and releases the sender waiting on this value.
+            handoff.fetch_add(1, Release);
+        }
+        self.wake_sender();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Q: RawQueue> Chan<Q> {
+    /// Registers the calling thread to be unparked by the next send or the last sender
+    /// going away; see [`RecvNotify::prepare_park`].
+    pub(crate) fn prepare_park(&self) {
+        self.recv_notify.prepare_park();
+        self.offer_to_rendezvous_sender();
+    }
+
+    /// Withdraws a registration made by [`prepare_park`](Self::prepare_park).
+    pub(crate) fn cancel_park(&self) {
+        self.recv_notify.cancel_park();
+    }
+
+    /// Sends a value, parking the calling thread while the queue is full.
+    ///
+    /// Fails with the value handed back once the receiver has been dropped, including when
+    /// that happens while the sender is parked. On a zero-capacity channel this only returns
+    /// once the receiver has taken the value.
+    pub(crate) fn send_blocking(&self, data: Q::Item) -> Result<(), SendError<Q::Item>> {
+        let Some(handoff) = &self.handoff else {
+            return self.send_blocking_with(data, |data| self.queue.push(data));
+        };
+
+        let mut claimed = 0;
+        self.send_blocking_with(data, |data| {
+            claimed = self.hand_off(handoff, data)?;
+            Ok(())
+        })?;
+        self.send_notify.park_until(|| {
+            if handoff.load(Acquire) != claimed {
+                return Some(Ok(()));
+            }
+            if !self.is_receiver_alive() {
+                // No one else pops once the receiver is gone, and no other sender can claim
+                // the slot, so take the value back; `None` means it was received after all.
+                return Some(self.queue.pop().map_or(Ok(()), |data| Err(SendError(data))));
+            }
+            None
+        })
+    }
+
+    /// Parks the calling thread until `push`, run through [`send_with`](Self::send_with),
+    /// succeeds, or the channel can no longer accept the value.
+    fn send_blocking_with(
+        &self,
+        data: Q::Item,
+        mut push: impl FnMut(Q::Item) -> Result<(), Q::Item>,
+    ) -> Result<(), SendError<Q::Item>> {
+        let mut data = Some(data);
+        self.send_notify.park_until(|| {
+            match self.send_with(data.take().expect("retried after completion"), &mut push) {
+                Ok(()) => Some(Ok(())),
+                Err(TrySendError::Disconnected(back) | TrySendError::Closed(back)) => {
+                    Some(Err(SendError(back)))
+                }
+                Err(TrySendError::Full(back)) => {
+                    data = Some(back);
+                    None
+                }
+            }
+        })
+    }
+
+    /// Receives a value, blocking the calling thread until one is available or every sender
+    /// has been dropped.
+    pub(crate) fn recv(&self) -> Result<Q::Item, RecvError> {
+        self.recv_until(None).map_err(|_| RecvError)
+    }
+
+    /// Hands every value to `f` as it arrives, returning once no more can: every sender has
+    /// been dropped, or the channel closed, and the queue is drained.
+    pub(crate) fn recv_until_disconnect(&self, mut f: impl FnMut(Q::Item)) {
+        while let Ok(data) = self.recv() {
+            f(data);
+        }
+    }
+
+    /// Blocks until at least one value is available, then moves it and up to `max - 1` more
+    /// that are ready right away into `out`, returning how many were moved.
+    ///
+    /// A `max` of zero returns `Ok(0)` straight away.
+    pub(crate) fn recv_batch(
+        &self,
+        max: usize,
+        out: &mut Vec<Q::Item>,
+    ) -> Result<usize, RecvError> {
+        if max == 0 {
+            return Ok(0);
+        }
+        let first = self.recv()?;
+        Ok(self.take_batch(first, max, out))
+    }
+
+    /// Like [`recv_batch`](Self::recv_batch), but waits for the first value for at most
+    /// `timeout`.
+    pub(crate) fn recv_many_timeout(
+        &self,
+        max: usize,
+        timeout: Duration,
+        out: &mut Vec<Q::Item>,
+    ) -> Result<usize, RecvTimeoutError> {
+        if max == 0 {
+            return Ok(0);
+        }
+        let first = self.recv_timeout(timeout)?;
+        Ok(self.take_batch(first, max, out))
+    }
+
+    /// Moves `first` and up to `max - 1` more values that are ready right away into `out`,
+    /// returning how many were moved.
+    fn take_batch(&self, first: Q::Item, max: usize, out: &mut Vec<Q::Item>) -> usize {
+        out.push(first);
+        let mut received = 1;
+        while received < max
+            && let Some(data) = self.take()
+        {
+            out.push(data);
+            received += 1;
+        }
+        received
+    }
+
+    /// Blocks until at least `n` values are queued.
+    ///
+    /// Fails once no more values can arrive while fewer than `n` are queued. Every send
+    /// wakes the receiver, and it goes straight back to sleep until the count is reached.
+    pub(crate) fn wait_for(&self, n: usize) -> Result<(), RecvError> {
+        loop {
+            if self.queue.len() >= n {
+                return Ok(());
+            }
+            if self.is_finished() {
+                // Whatever the last sender pushed before leaving is visible by now.
+                return if self.queue.len() >= n {
+                    Ok(())
+                } else {
+                    Err(RecvError)
+                };
+            }
+            self.prepare_park();
+            // Same race as in `recv_until`: a send may have landed before the registration.
+            if self.queue.len() < n && !self.is_finished() {
+                thread::park();
+            }
+            self.recv_notify.cancel_park();
+        }
+    }
+
+    /// Receives a value, blocking for at most `timeout`.
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Result<Q::Item, RecvTimeoutError> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.recv_deadline(deadline),
+            // A timeout too large to represent is as good as waiting forever.
+            None => self.recv().map_err(RecvTimeoutError::from),
+        }
+    }
+
+    /// Receives a value, blocking until `deadline` at the latest.
+    ///
+    /// A deadline that has already passed polls the queue exactly once without parking.
+    pub(crate) fn recv_deadline(&self, deadline: Instant) -> Result<Q::Item, RecvTimeoutError> {
+        if Instant::now() >= deadline {
+            return self.try_recv().map_err(|err| match err {
+                TryRecvError::Empty => RecvTimeoutError::Timeout,
+                TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            });
+        }
+        self.recv_until(Some(deadline))
+    }
+
+    /// Shared blocking core of the receive operations.
+    ///
+    /// The queue is re-polled under a [`LocalBackoff`] first, since a value is usually only a
+    /// few cycles away under load; once the backoff [is completed](LocalBackoff::is_completed)
+    /// the receiver parks until a producer unparks it or `deadline` passes. Wake-ups can be
+    /// spurious, so the remaining time is recomputed from `deadline` before every park.
+    fn recv_until(&self, deadline: Option<Instant>) -> Result<Q::Item, RecvTimeoutError> {
+        loop {
+            let backoff = LocalBackoff::new();
+            loop {
+                match self.try_recv() {
+                    Ok(data) => return Ok(data),
+                    Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                    Err(TryRecvError::Empty) if backoff.is_completed() => break,
+                    Err(TryRecvError::Empty) => backoff.wait(),
+                }
+            }
+
+            self.prepare_park();
+            // A push or disconnect may have landed between the last poll and the
+            // registration above, so look again before going to sleep.
+            let polled = self.try_recv();
+            if !matches!(polled, Err(TryRecvError::Empty)) {
+                self.recv_notify.cancel_park();
+                return polled.map_err(|_| RecvTimeoutError::Disconnected);
+            }
+            match deadline {
+                None => thread::park(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        self.recv_notify.cancel_park();
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+            }
+            self.recv_notify.cancel_park();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Q: RawQueue> Chan<Q> {
+    /// Polls for the next value from an async context.
+    ///
+    /// Resolves to `None` once every sender has been dropped and the queue is drained. When
+    /// the queue is empty the task's waker is registered, and the next send or the last
+    /// sender going away wakes it.
+    pub(crate) fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Q::Item>> {
+        match self.try_recv() {
+            Ok(data) => return Poll::Ready(Some(data)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        self.recv_notify.register_waker(cx.waker());
+        self.offer_to_rendezvous_sender();
+        // Same race as in `recv_until`: look again now that producers can see the waker.
+        match self.try_recv() {
+            Ok(data) => {
+                self.recv_notify.cancel_park();
+                Poll::Ready(Some(data))
+            }
+            Err(TryRecvError::Disconnected) => {
+                self.recv_notify.cancel_park();
+                Poll::Ready(None)
+            }
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Error types returned by the channel endpoints and the fallible queue constructors.
+//!
+//! The shapes follow `std::sync::mpsc`: sending errors hand the rejected value back so it is
+//! never lost, and receiving errors tell an empty channel apart from a disconnected one.
+
+use core::error::Error;
+use core::fmt;
+
+/// An error returned from a channel's `Sender::send` when the value could not be enqueued.
+///
+/// The unsent value is handed back so it is not lost.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Returns the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a full, closed or disconnected channel")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// An error returned from a channel's `Sender::try_send`.
+///
+/// Both variants hand the rejected value back.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The channel has no room for the value right now.
+    Full(T),
+    /// The receiving half has been dropped, so the value can never be delivered.
+    Disconnected(T),
+    /// The channel has been closed with `close`, so it accepts no more values.
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(data)
+            | TrySendError::Disconnected(data)
+            | TrySendError::Closed(data) => data,
+        }
+    }
+
+    /// Returns `true` if the send failed because the channel was full.
+    pub fn is_full(&self) -> bool {
+        matches!(self, TrySendError::Full(_))
+    }
+
+    /// Returns `true` if the send failed because the receiver was dropped.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, TrySendError::Disconnected(_))
+    }
+
+    /// Returns `true` if the send failed because the channel was closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TrySendError::Closed(_))
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("Full(..)"),
+            TrySendError::Disconnected(_) => f.write_str("Disconnected(..)"),
+            TrySendError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("sending on a full channel"),
+            TrySendError::Disconnected(_) => f.write_str("sending on a disconnected channel"),
+            TrySendError::Closed(_) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+impl<T> From<TrySendError<T>> for SendError<T> {
+    /// Collapses either failure reason into a [`SendError`] carrying the value.
+    fn from(err: TrySendError<T>) -> Self {
+        SendError(err.into_inner())
+    }
+}
+
+/// An error returned from a channel's `Receiver::recv` once every sender has been dropped,
+/// or the channel has been closed, and no buffered values remain.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on an empty and disconnected channel")
+    }
+}
+
+impl Error for RecvError {}
+
+/// An error returned from a channel's `Receiver::try_recv`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// The channel is currently empty but senders are still connected.
+    Empty,
+    /// Every sender has been dropped, or the channel has been closed, and no buffered values
+    /// remain.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => {
+                f.write_str("receiving on an empty and disconnected channel")
+            }
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+impl From<RecvError> for TryRecvError {
+    fn from(_: RecvError) -> Self {
+        TryRecvError::Disconnected
+    }
+}
+
+/// An error returned from a channel's `Receiver::recv_timeout`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// No value arrived before the timeout elapsed; senders are still connected.
+    Timeout,
+    /// Every sender has been dropped, or the channel has been closed, and no buffered values
+    /// remain.
+    Disconnected,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => f.write_str("timed out waiting on channel"),
+            RecvTimeoutError::Disconnected => {
+                f.write_str("receiving on an empty and disconnected channel")
+            }
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+impl From<RecvError> for RecvTimeoutError {
+    fn from(_: RecvError) -> Self {
+        RecvTimeoutError::Disconnected
+    }
+}
+
+/// An error returned from a queue's `try_new` when its storage can't be allocated.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AllocError {
+    /// The requested capacity needs more than `isize::MAX` bytes.
+    CapacityOverflow,
+    /// The allocator failed to provide the memory.
+    OutOfMemory,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::CapacityOverflow => f.write_str("capacity overflow"),
+            AllocError::OutOfMemory => f.write_str("memory allocation failed"),
+        }
+    }
+}
+
+impl Error for AllocError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_send_error_into_inner() {
+        assert_eq!(TrySendError::Full(1).into_inner(), 1);
+        assert_eq!(TrySendError::Disconnected(2).into_inner(), 2);
+        assert!(TrySendError::Full(()).is_full());
+        assert!(TrySendError::Disconnected(()).is_disconnected());
+        assert_eq!(TrySendError::Closed(3).into_inner(), 3);
+        assert!(TrySendError::Closed(()).is_closed());
+    }
+
+    #[test]
+    fn test_send_error_from_try_send_error() {
+        let err: SendError<_> = TrySendError::Full(3).into();
+        assert_eq!(err.into_inner(), 3);
+    }
+
+    #[test]
+    fn test_display_and_debug_do_not_need_debug_payload() {
+        struct Opaque;
+        assert_eq!(
+            TrySendError::Full(Opaque).to_string(),
+            "sending on a full channel"
+        );
+        assert_eq!(format!("{:?}", TrySendError::Full(Opaque)), "Full(..)");
+        assert_eq!(format!("{:?}", SendError(Opaque)), "SendError { .. }");
+        assert_eq!(TryRecvError::from(RecvError), TryRecvError::Disconnected);
+    }
+}
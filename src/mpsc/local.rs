@@ -0,0 +1,226 @@
+//! A FIFO queue for a single thread, free of atomics.
+//!
+//! [`LocalMpsc`] offers the `push`/`pop` surface of the raw queues to values that can't
+//! leave their thread, such as `Rc`s. Any number of producers may share it, through an `Rc`
+//! or plain references, as long as they all run on the thread that created it; the queue
+//! itself is neither `Send` nor `Sync`, so handing it to another thread is a compile error:
+//!
+//! ```compile_fail
+//! use lock_free_mpsc::mpsc::local::LocalMpsc;
+//!
+//! let q = LocalMpsc::<u32>::new();
+//! std::thread::spawn(move || q.push(1));
+//! ```
+//!
+//! ```compile_fail
+//! use lock_free_mpsc::mpsc::local::LocalMpsc;
+//!
+//! let q = LocalMpsc::<u32>::new();
+//! std::thread::scope(|s| {
+//!     s.spawn(|| q.push(1));
+//! });
+//! ```
+
+use alloc::collections::VecDeque;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::mpsc::queue::Queue;
+use crate::mpsc::snapshot::QueueSnapshot;
+
+/// An unbounded single-threaded FIFO queue.
+///
+/// Pushing and popping only touch a ring buffer behind an `UnsafeCell`, with no atomics or
+/// backoff, which makes it the fast path for pipelines that never leave one thread.
+///
+/// ```
+/// use lock_free_mpsc::mpsc::local::LocalMpsc;
+/// use std::rc::Rc;
+///
+/// let q = Rc::new(LocalMpsc::new());
+/// let producer = Rc::clone(&q);
+/// producer.push(Rc::new(1));
+/// producer.push(Rc::new(2));
+/// assert_eq!(q.pop().as_deref(), Some(&1));
+/// assert_eq!(q.len(), 1);
+/// ```
+pub struct LocalMpsc<T> {
+    buf: UnsafeCell<VecDeque<T>>,
+    /// Keeps the queue on its thread, whatever `T` is.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> LocalMpsc<T> {
+    /// Creates a new, empty queue. Does not allocate until the first push.
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new(VecDeque::new()),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Creates a new, empty queue with room for at least `capacity` values before it
+    /// reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: UnsafeCell::new(VecDeque::with_capacity(capacity)),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Creates a queue holding the values of `snapshot`, in order.
+    pub fn from_snapshot(snapshot: QueueSnapshot<T>) -> Self {
+        snapshot.items.into_iter().collect()
+    }
+
+    /// Runs `f` on the buffer.
+    ///
+    /// `f` must not run any code of `T`, such as a `Drop`, which could reenter the queue
+    /// while the buffer is borrowed.
+    #[inline]
+    fn with_buf<R>(&self, f: impl FnOnce(&mut VecDeque<T>) -> R) -> R {
+        // SAFETY: the queue is `!Sync`, so this thread is the only one with access, and no
+        // caller lets a borrow of the buffer escape or reenters while it is live.
+        f(unsafe { &mut *self.buf.get() })
+    }
+
+    /// Pushes `data` to the back of the queue.
+    #[inline]
+    pub fn push(&self, data: T) {
+        self.with_buf(|buf| buf.push_back(data));
+    }
+
+    /// Pops the oldest value, or returns `None` if the queue is empty.
+    #[inline]
+    pub fn pop(&self) -> Option<T> {
+        self.with_buf(VecDeque::pop_front)
+    }
+
+    /// Returns the number of values in the queue. Exact, unlike for the concurrent queues.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.with_buf(|buf| buf.len())
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes every value out of the queue into a [`QueueSnapshot`], so the queue can be
+    /// rebuilt with [`from_snapshot`](Self::from_snapshot).
+    ///
+    /// The snapshot records no capacity.
+    pub fn drain(&self) -> QueueSnapshot<T> {
+        let buf = self.with_buf(mem::take);
+        QueueSnapshot {
+            capacity: None,
+            items: buf.into(),
+        }
+    }
+
+    /// Drops every value in the queue.
+    ///
+    /// The values are taken out first, so one whose `Drop` pushes into the queue again
+    /// leaves its value behind rather than corrupting the buffer.
+    pub fn clear(&self) {
+        drop(self.with_buf(mem::take));
+    }
+}
+
+impl<T> Default for LocalMpsc<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Queue<T> for LocalMpsc<T> {
+    /// Pushes `data`; a local queue always has room.
+    #[inline]
+    fn try_push(&self, data: T) -> Result<(), T> {
+        self.push(data);
+        Ok(())
+    }
+
+    #[inline]
+    fn try_pop(&self) -> Option<T> {
+        self.pop()
+    }
+}
+
+impl<T> fmt::Debug for LocalMpsc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalMpsc")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> FromIterator<T> for LocalMpsc<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            buf: UnsafeCell::new(iter.into_iter().collect()),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_rc_values_round_trip() {
+        let q = Rc::new(LocalMpsc::new());
+        let value = Rc::new(7);
+        let producers: Vec<_> = (0..3).map(|_| Rc::clone(&q)).collect();
+        for (i, producer) in producers.iter().enumerate() {
+            producer.push((i, Rc::clone(&value)));
+        }
+        assert_eq!(q.len(), 3);
+        assert_eq!(Rc::strong_count(&value), 4);
+
+        let (i, popped) = q.pop().unwrap();
+        assert_eq!((i, *popped), (0, 7));
+        drop(popped);
+        let rest = q.drain();
+        assert!(q.is_empty());
+        assert_eq!(
+            rest.items.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        drop(rest);
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_drop_that_pushes_during_clear() {
+        struct Requeue(Rc<LocalMpsc<Option<Requeue>>>);
+
+        impl Drop for Requeue {
+            fn drop(&mut self) {
+                self.0.push(None);
+            }
+        }
+
+        let q = Rc::new(LocalMpsc::new());
+        q.push(Some(Requeue(Rc::clone(&q))));
+        q.push(Some(Requeue(Rc::clone(&q))));
+        q.clear();
+        assert_eq!(q.len(), 2);
+        assert!(q.drain().items.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let q: LocalMpsc<_> = (0..5).collect();
+        let q = LocalMpsc::from_snapshot(q.drain());
+        q.push(5);
+        assert_eq!(q.drain().items, [0, 1, 2, 3, 4, 5]);
+    }
+}
@@ -1,4 +1,16 @@
-mod bounded_mpsc;
-mod unbounded_mpsc;
+pub mod bounded_mpsc;
+pub mod errors;
+pub mod local;
+pub mod priority;
+pub mod queue;
+pub mod select;
+pub mod snapshot;
+#[cfg(feature = "metrics")]
+pub mod stats;
+pub mod unbounded_mpsc;
 
+mod chan;
+#[cfg(feature = "std")]
+mod notify;
 mod slot;
+mod sync;
@@ -0,0 +1,320 @@
+//! Wake-up signalling between producers and a parked consumer.
+//!
+//! The consumer advertises that it is about to sleep by storing its [`Thread`] handle (or,
+//! for an async consumer, its task's `Waker`) and raising `waiting`; producers check
+//! `waiting` after every successful push and only then take the slow path of unparking it.
+//! Both sides put a `SeqCst` fence between their write (the pushed value, respectively the
+//! `waiting` flag) and their read (the flag, respectively the queue), so at least one of them
+//! always observes the other: either the producer sees the consumer waiting and unparks it,
+//! or the consumer's final re-check sees the value.
+//!
+//! [`SendNotify`] applies the same handshake in the other direction so producers waiting on a
+//! full bounded queue, parked or as async tasks, are woken as the consumer frees slots.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering::{Relaxed, SeqCst},
+    fence,
+};
+use std::task::Waker;
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// Longest a producer waiting for room parks before checking the queue again on its own.
+const SEND_PARK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Whoever has to be woken up once it can make progress.
+enum Waiter {
+    /// A thread blocked in a receive or a blocking send.
+    Thread(Thread),
+    /// An async task polling the channel.
+    Task(Waker),
+}
+
+impl Waiter {
+    fn wake(self) {
+        match self {
+            Waiter::Thread(thread) => thread.unpark(),
+            Waiter::Task(waker) => waker.wake(),
+        }
+    }
+
+    fn wake_by_ref(&self) {
+        match self {
+            Waiter::Thread(thread) => thread.unpark(),
+            Waiter::Task(waker) => waker.wake_by_ref(),
+        }
+    }
+}
+
+/// Parking slot for the single consumer of a channel.
+pub(crate) struct RecvNotify {
+    /// Raised while the consumer is parked (or about to park).
+    waiting: AtomicBool,
+    /// The consumer to wake; only touched on the slow path.
+    waiter: Mutex<Option<Waiter>>,
+}
+
+impl RecvNotify {
+    pub(crate) const fn new() -> Self {
+        Self {
+            waiting: AtomicBool::new(false),
+            waiter: Mutex::new(None),
+        }
+    }
+
+    /// Registers the calling thread as the one to wake up.
+    ///
+    /// The caller must re-check the queue after this returns and before parking, otherwise
+    /// a value pushed in between could go unnoticed.
+    pub(crate) fn prepare_park(&self) {
+        *self.waiter.lock().unwrap() = Some(Waiter::Thread(thread::current()));
+        self.waiting.store(true, Relaxed);
+        fence(SeqCst);
+    }
+
+    /// Registers `waker` as the task to wake up.
+    ///
+    /// As with [`prepare_park`](Self::prepare_park), the caller must re-check the queue after
+    /// this returns and before returning `Poll::Pending`.
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        {
+            let mut waiter = self.waiter.lock().unwrap();
+            if !matches!(&*waiter, Some(Waiter::Task(current)) if current.will_wake(waker)) {
+                *waiter = Some(Waiter::Task(waker.clone()));
+            }
+        }
+        self.waiting.store(true, Relaxed);
+        fence(SeqCst);
+    }
+
+    /// Returns `true` while the consumer is parked, or about to park.
+    ///
+    /// The fence orders this after whatever the caller published before looking, as in
+    /// [`notify`](Self::notify).
+    #[inline]
+    pub(crate) fn is_waiting(&self) -> bool {
+        fence(SeqCst);
+        self.waiting.load(Relaxed)
+    }
+
+    /// Withdraws a registration made by [`prepare_park`](Self::prepare_park).
+    #[inline]
+    pub(crate) fn cancel_park(&self) {
+        self.waiting.store(false, Relaxed);
+    }
+
+    /// Wakes the consumer if it is parked, or about to park.
+    ///
+    /// Must be called after the state change the consumer is waiting for (a push, a sender
+    /// disconnecting) has been made.
+    #[inline]
+    pub(crate) fn notify(&self) {
+        fence(SeqCst);
+        if self.waiting.load(Relaxed)
+            && self.waiting.swap(false, SeqCst)
+            && let Some(waiter) = self.waiter.lock().unwrap().as_ref()
+        {
+            waiter.wake_by_ref();
+        }
+    }
+}
+
+/// Wait list for producers that found a bounded queue full.
+pub(crate) struct SendNotify {
+    /// Raised while `waiters` is non-empty; only changed with the lock held.
+    waiting: AtomicBool,
+    /// The waiting producers, oldest first.
+    waiters: Mutex<VecDeque<Waiter>>,
+}
+
+impl SendNotify {
+    pub(crate) const fn new() -> Self {
+        Self {
+            waiting: AtomicBool::new(false),
+            waiters: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Runs `attempt` until it returns `Some`, parking the calling thread on the wait list
+    /// whenever it returns `None`.
+    ///
+    /// Every park is bounded by `SEND_PARK_TIMEOUT`, so a producer re-checks even if it was
+    /// never woken.
+    pub(crate) fn park_until<R>(&self, mut attempt: impl FnMut() -> Option<R>) -> R {
+        if let Some(done) = attempt() {
+            return done;
+        }
+        let done = loop {
+            self.register_thread();
+            // A slot freed between the failed attempt and the registration must not be
+            // slept through.
+            if let Some(done) = attempt() {
+                break done;
+            }
+            thread::park_timeout(SEND_PARK_TIMEOUT);
+        };
+        if !self.cancel_thread() {
+            // The wake-up meant for the next producer in line may have come to us.
+            self.notify_one();
+        }
+        done
+    }
+
+    /// Adds the calling thread to the wait list, unless it is already on it.
+    fn register_thread(&self) {
+        let current = thread::current();
+        {
+            let mut waiters = self.waiters.lock().unwrap();
+            let queued = waiters
+                .iter()
+                .any(|waiter| matches!(waiter, Waiter::Thread(t) if t.id() == current.id()));
+            if !queued {
+                waiters.push_back(Waiter::Thread(current));
+            }
+            self.waiting.store(true, Relaxed);
+        }
+        fence(SeqCst);
+    }
+
+    /// Takes the calling thread off the wait list, returning `false` if a wake-up already
+    /// did.
+    fn cancel_thread(&self) -> bool {
+        let current = thread::current().id();
+        let mut waiters = self.waiters.lock().unwrap();
+        let len = waiters.len();
+        waiters.retain(|waiter| !matches!(waiter, Waiter::Thread(t) if t.id() == current));
+        self.waiting.store(!waiters.is_empty(), Relaxed);
+        waiters.len() != len
+    }
+
+    /// Adds `waker` to the wait list.
+    ///
+    /// The caller must retry its send after this returns and before returning
+    /// `Poll::Pending`, otherwise a slot freed in between could go unnoticed.
+    #[cfg(feature = "async")]
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        {
+            let mut waiters = self.waiters.lock().unwrap();
+            let queued = waiters
+                .iter()
+                .any(|waiter| matches!(waiter, Waiter::Task(w) if w.will_wake(waker)));
+            if !queued {
+                waiters.push_back(Waiter::Task(waker.clone()));
+            }
+            self.waiting.store(true, Relaxed);
+        }
+        fence(SeqCst);
+    }
+
+    /// Takes `waker` off the wait list, returning `false` if a wake-up already did.
+    #[cfg(feature = "async")]
+    pub(crate) fn cancel_waker(&self, waker: &Waker) -> bool {
+        let mut waiters = self.waiters.lock().unwrap();
+        let len = waiters.len();
+        waiters.retain(|waiter| !matches!(waiter, Waiter::Task(w) if w.will_wake(waker)));
+        self.waiting.store(!waiters.is_empty(), Relaxed);
+        waiters.len() != len
+    }
+
+    /// Wakes the longest waiting producer, if any.
+    ///
+    /// Must be called after a slot has been freed.
+    #[inline]
+    pub(crate) fn notify_one(&self) {
+        fence(SeqCst);
+        if !self.waiting.load(Relaxed) {
+            return;
+        }
+        let waiter = {
+            let mut waiters = self.waiters.lock().unwrap();
+            let waiter = waiters.pop_front();
+            self.waiting.store(!waiters.is_empty(), Relaxed);
+            waiter
+        };
+        if let Some(waiter) = waiter {
+            waiter.wake();
+        }
+    }
+
+    /// Wakes every waiting producer, e.g. once the receiver is gone.
+    pub(crate) fn notify_all(&self) {
+        fence(SeqCst);
+        if !self.waiting.load(Relaxed) {
+            return;
+        }
+        let waiters = {
+            let mut waiters = self.waiters.lock().unwrap();
+            self.waiting.store(false, Relaxed);
+            core::mem::take(&mut *waiters)
+        };
+        waiters.into_iter().for_each(Waiter::wake);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_notify_without_waiter_is_noop() {
+        let notify = RecvNotify::new();
+        notify.notify();
+        assert!(!notify.waiting.load(Relaxed));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_notify_unparks_registered_thread() {
+        let notify = Arc::new(RecvNotify::new());
+        let flag = Arc::new(AtomicUsize::new(0));
+
+        let waiter = {
+            let notify = Arc::clone(&notify);
+            let flag = Arc::clone(&flag);
+            thread::spawn(move || {
+                loop {
+                    notify.prepare_park();
+                    if flag.load(SeqCst) == 1 {
+                        notify.cancel_park();
+                        return;
+                    }
+                    thread::park();
+                }
+            })
+        };
+
+        flag.store(1, SeqCst);
+        notify.notify();
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn test_notify_wakes_registered_task() {
+        use std::task::Wake;
+
+        struct CountingWaker(AtomicUsize);
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, SeqCst);
+            }
+        }
+
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&counter));
+        let notify = RecvNotify::new();
+
+        notify.register_waker(&waker);
+        notify.notify();
+        assert_eq!(counter.0.load(SeqCst), 1);
+
+        // Only one wake-up per registration
+        notify.notify();
+        assert_eq!(counter.0.load(SeqCst), 1);
+    }
+}
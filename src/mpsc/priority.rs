@@ -0,0 +1,192 @@
+//! A multi-producer single-consumer queue that hands out values by priority.
+//!
+//! [`PriorityMpsc`] keeps one unbounded FIFO [`RawMpsc`] per priority level. A push goes to
+//! the sub-queue of its level, and a pop scans the sub-queues from the highest level down,
+//! taking the first value it finds. Values of the same level keep their FIFO order; values
+//! of different levels come out highest level first.
+//!
+//! Ordering across levels is only as strong as a single scan: a value pushed at a high level
+//! while a pop is already looking at lower ones is picked up by the next pop, not that one.
+//!
+//! # Starvation
+//!
+//! A pop always prefers the highest non-empty level, so as long as producers keep a level
+//! busy, nothing below it is handed out. Low levels are only drained once the consumer
+//! catches up with everything above them. Use this for traffic where high-priority values
+//! are the exception, such as control messages next to bulk data.
+
+use core::fmt;
+
+use crate::mpsc::unbounded_mpsc::RawMpsc;
+
+/// Number of priority levels of a [`PriorityMpsc`] unless picked otherwise.
+pub const PRIORITY_LEVELS: usize = 4;
+
+/// An unbounded lock-free multi-producer single-consumer queue with `K` priority levels.
+///
+/// Levels run from `0`, the lowest, to `K - 1`, the highest. Only one thread may `pop` at a
+/// time. See the [module docs](self) for the ordering it guarantees and how it can starve
+/// low levels.
+///
+/// ```
+/// use lock_free_mpsc::mpsc::priority::PriorityMpsc;
+///
+/// let q = PriorityMpsc::<&str, 3>::new();
+/// q.push(0, "bulk");
+/// q.push(2, "urgent");
+/// q.push(1, "normal");
+/// assert_eq!(q.pop(), Some("urgent"));
+/// assert_eq!(q.pop(), Some("normal"));
+/// assert_eq!(q.pop(), Some("bulk"));
+/// assert_eq!(q.pop(), None);
+/// ```
+pub struct PriorityMpsc<T, const K: usize = PRIORITY_LEVELS> {
+    /// One FIFO per level, indexed by priority.
+    levels: [RawMpsc<T>; K],
+}
+
+impl<T, const K: usize> PriorityMpsc<T, K> {
+    /// Creates a new, empty queue.
+    ///
+    /// Every level starts with a small segment that grows with it, so idle levels hold
+    /// little memory.
+    pub fn new() -> Self {
+        const { assert!(K > 0, "a priority queue needs at least one level") };
+        Self {
+            levels: core::array::from_fn(|_| RawMpsc::growing()),
+        }
+    }
+
+    /// Pushes `data` at `priority`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `priority` is not below `K`.
+    pub fn push(&self, priority: usize, data: T) {
+        assert!(
+            priority < K,
+            "priority {priority} out of range for {K} levels"
+        );
+        self.levels[priority].push(data);
+    }
+
+    /// Pops the oldest value of the highest non-empty level, or returns `None` if every
+    /// level is empty.
+    ///
+    /// Must only be called from the single consumer.
+    pub fn pop(&self) -> Option<T> {
+        self.pop_with_priority().map(|(_, data)| data)
+    }
+
+    /// Like [`pop`](Self::pop), but also returns the level the value was pushed at.
+    pub fn pop_with_priority(&self) -> Option<(usize, T)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(priority, level)| level.pop().map(|data| (priority, data)))
+    }
+
+    /// Returns the approximate number of values buffered across all levels.
+    ///
+    /// Each level's count is only an estimate under concurrent use, and so is their sum.
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(RawMpsc::len).sum()
+    }
+
+    /// Returns the approximate number of values buffered at `priority`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `priority` is not below `K`.
+    pub fn len_at(&self, priority: usize) -> usize {
+        self.levels[priority].len()
+    }
+
+    /// Returns `true` if every level appears empty.
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(RawMpsc::is_empty)
+    }
+}
+
+impl<T, const K: usize> Default for PriorityMpsc<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const K: usize> fmt::Debug for PriorityMpsc<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lens = [0; K];
+        for (len, level) in lens.iter_mut().zip(&self.levels) {
+            *len = level.len();
+        }
+        f.debug_struct("PriorityMpsc")
+            .field("len", &self.len())
+            .field("levels", &lens)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_interleaved_priorities_pop_highest_first() {
+        let q = PriorityMpsc::<u32, 3>::new();
+        q.push(0, 1);
+        q.push(2, 2);
+        q.push(1, 3);
+        q.push(0, 4);
+        q.push(2, 5);
+        assert_eq!(q.len(), 5);
+        assert_eq!(q.pop_with_priority(), Some((2, 2)));
+        // A later high-priority push still overtakes what is buffered below it
+        q.push(1, 6);
+        q.push(2, 7);
+        let popped: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(popped, [5, 7, 3, 6, 1, 4]);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "priority 4 out of range for 4 levels")]
+    fn test_out_of_range_priority_panics() {
+        PriorityMpsc::<u8>::new().push(4, 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_levels_stay_fifo_across_producers() {
+        const PER_PRODUCER: usize = 1000;
+        let q = Arc::new(PriorityMpsc::<usize, 2>::new());
+        let handles: Vec<_> = (0..2)
+            .map(|priority| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        q.push(priority, i);
+                    }
+                })
+            })
+            .collect();
+
+        let mut next = [0; 2];
+        while next != [PER_PRODUCER; 2] {
+            match q.pop_with_priority() {
+                Some((priority, i)) => {
+                    assert_eq!(i, next[priority], "level {priority} out of order");
+                    next[priority] += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(q.pop(), None);
+    }
+}
@@ -0,0 +1,105 @@
+//! A common interface over the bounded and unbounded queues.
+//!
+//! The two [`RawMpsc`](crate::mpsc::bounded_mpsc::RawMpsc)s differ in how a push can fail:
+//! the bounded one hands the value back when it is full, the unbounded one never fails.
+//! [`Queue`] papers over that so code can be written once for either.
+//!
+//! [`Ack`] is what a consumer handing values to a fallible handler, as with
+//! `Receiver::recv_ack`, reports back about each one.
+
+/// A multi-producer single-consumer queue of `T`s.
+///
+/// ```
+/// use lock_free_mpsc::mpsc::queue::Queue;
+/// use lock_free_mpsc::mpsc::{bounded_mpsc, unbounded_mpsc};
+///
+/// fn round_trip<Q: Queue<u32>>(queue: &Q) -> Option<u32> {
+///     queue.try_push(7).ok()?;
+///     queue.try_pop()
+/// }
+///
+/// assert_eq!(round_trip(&bounded_mpsc::RawMpsc::new(1)), Some(7));
+/// assert_eq!(round_trip(&unbounded_mpsc::RawMpsc::new()), Some(7));
+/// ```
+pub trait Queue<T> {
+    /// Attempts to enqueue `data`, handing it back if the queue has no room for it.
+    ///
+    /// Unbounded queues always succeed.
+    fn try_push(&self, data: T) -> Result<(), T>;
+
+    /// Dequeues the oldest value, or returns `None` if the queue is empty.
+    ///
+    /// Like the queues' own `pop`, this must only be called from the single consumer.
+    fn try_pop(&self) -> Option<T>;
+}
+
+/// Whether a consumer is done with the value it was handed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Ack {
+    /// The value was dealt with and can be dropped.
+    Ack,
+    /// The value couldn't be dealt with; it stays at the front of the queue and is handed
+    /// out again next time.
+    Nack,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpsc::{bounded_mpsc, unbounded_mpsc};
+    use std::sync::Arc;
+    use std::thread;
+
+    const PRODUCERS: usize = 4;
+    const PER_PRODUCER: usize = 1000;
+
+    /// Every producer pushes its own run of values, retrying while the queue is full; the
+    /// consumer must see each run complete and in order.
+    fn producer_consumer<Q: Queue<usize> + Send + Sync + 'static>(queue: Q) {
+        let queue = Arc::new(queue);
+        let handles: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        let mut data = p * PER_PRODUCER + i;
+                        while let Err(back) = queue.try_push(data) {
+                            data = back;
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut next = [0; PRODUCERS];
+        let mut received = 0;
+        while received < PRODUCERS * PER_PRODUCER {
+            match queue.try_pop() {
+                Some(data) => {
+                    let (p, i) = (data / PER_PRODUCER, data % PER_PRODUCER);
+                    assert_eq!(i, next[p], "producer {p} out of order");
+                    next[p] += 1;
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(queue.try_pop(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_bounded_queue() {
+        producer_consumer(bounded_mpsc::RawMpsc::new(16));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_unbounded_queue() {
+        producer_consumer(unbounded_mpsc::RawMpsc::<_, 8>::with_segment_size());
+    }
+}
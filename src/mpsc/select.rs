@@ -0,0 +1,239 @@
+//! Receiving from whichever of several channels has a value first.
+//!
+//! A [`Select`] borrows any number of receivers carrying the same item type, bounded or
+//! unbounded, and hands out the next value from one of them together with the index it was
+//! added under.
+//!
+//! # Fairness
+//!
+//! Channels are polled round-robin: each call starts at the channel right after the one that
+//! produced the previous value, so a channel that always has something ready cannot starve
+//! the others.
+
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(feature = "std")]
+use core::hint::spin_loop;
+#[cfg(feature = "std")]
+use std::thread;
+
+#[cfg(feature = "std")]
+use crate::mpsc::errors::RecvError;
+use crate::mpsc::errors::TryRecvError;
+
+/// Number of times a blocking select re-polls every channel before parking.
+#[cfg(feature = "std")]
+const SELECT_SPIN_ROUNDS: u32 = 64;
+
+pub(crate) mod sealed {
+    use crate::mpsc::errors::TryRecvError;
+
+    /// The hooks [`Select`](super::Select) needs from a receiver; not implementable outside
+    /// this crate.
+    pub trait Sealed<T> {
+        fn try_recv(&self) -> Result<T, TryRecvError>;
+
+        /// Registers the calling thread to be unparked by the next send or disconnect.
+        #[cfg(feature = "std")]
+        fn prepare_park(&self);
+
+        /// Withdraws a registration made by `prepare_park`.
+        #[cfg(feature = "std")]
+        fn cancel_park(&self);
+    }
+}
+
+/// A receiver that can take part in a [`Select`].
+///
+/// Implemented by the `Receiver` of both the bounded and the unbounded channel.
+pub trait Selectable<T>: sealed::Sealed<T> {}
+
+impl<T, R: sealed::Sealed<T>> Selectable<T> for R {}
+
+/// What one round-robin pass over the channels found.
+enum Polled<T> {
+    /// A value, with the index of the channel it came from.
+    Ready(usize, T),
+    /// Every channel was empty, and at least one can still receive values.
+    Empty,
+    /// Every channel is empty and disconnected.
+    Disconnected,
+}
+
+/// A set of receivers to take values from, whichever has one first.
+///
+/// # Examples
+///
+/// ```
+/// use lock_free_mpsc::mpsc::bounded_mpsc::channel;
+/// use lock_free_mpsc::mpsc::select::Select;
+/// use lock_free_mpsc::mpsc::unbounded_mpsc::unbounded;
+///
+/// let (tx1, rx1) = channel(4);
+/// let (tx2, rx2) = unbounded();
+/// let mut sel = Select::new();
+/// sel.add(&rx1);
+/// sel.add(&rx2);
+///
+/// tx2.send("second").unwrap();
+/// assert_eq!(sel.try_select(), Some((1, "second")));
+/// tx1.send("first").unwrap();
+/// assert_eq!(sel.select(), Ok((0, "first")));
+/// ```
+pub struct Select<'a, T> {
+    receivers: Vec<&'a dyn Selectable<T>>,
+    /// Index of the channel the next pass starts at.
+    start: Cell<usize>,
+}
+
+impl<'a, T> Select<'a, T> {
+    /// Creates an empty selection.
+    pub fn new() -> Self {
+        Self {
+            receivers: Vec::new(),
+            start: Cell::new(0),
+        }
+    }
+
+    /// Adds a receiver and returns the index its values will be reported under.
+    pub fn add<R: Selectable<T>>(&mut self, rx: &'a R) -> usize {
+        self.receivers.push(rx);
+        self.receivers.len() - 1
+    }
+
+    /// Takes a value from the first channel that has one, without waiting.
+    ///
+    /// Returns the index of that channel alongside the value, or `None` if every channel is
+    /// currently empty.
+    pub fn try_select(&self) -> Option<(usize, T)> {
+        match self.poll() {
+            Polled::Ready(idx, data) => Some((idx, data)),
+            Polled::Empty | Polled::Disconnected => None,
+        }
+    }
+
+    /// Takes a value from the first channel that has one, blocking until any does.
+    ///
+    /// Returns [`RecvError`] once every channel is disconnected and drained, or straight
+    /// away if no channel was added.
+    #[cfg(feature = "std")]
+    pub fn select(&self) -> Result<(usize, T), RecvError> {
+        loop {
+            for _ in 0..SELECT_SPIN_ROUNDS {
+                match self.poll() {
+                    Polled::Ready(idx, data) => return Ok((idx, data)),
+                    Polled::Disconnected => return Err(RecvError),
+                    Polled::Empty => spin_loop(),
+                }
+            }
+
+            self.receivers.iter().for_each(|rx| rx.prepare_park());
+            // A send landing in any channel since the last pass must not be slept through.
+            let polled = self.poll();
+            if !matches!(polled, Polled::Empty) {
+                self.receivers.iter().for_each(|rx| rx.cancel_park());
+            }
+            match polled {
+                Polled::Ready(idx, data) => return Ok((idx, data)),
+                Polled::Disconnected => return Err(RecvError),
+                Polled::Empty => {}
+            }
+            thread::park();
+            self.receivers.iter().for_each(|rx| rx.cancel_park());
+        }
+    }
+
+    /// Polls every channel once, starting at `start`, and moves `start` past the channel a
+    /// value was taken from.
+    fn poll(&self) -> Polled<T> {
+        let len = self.receivers.len();
+        let start = self.start.get();
+        let mut connected = false;
+        for offset in 0..len {
+            let idx = (start + offset) % len;
+            match self.receivers[idx].try_recv() {
+                Ok(data) => {
+                    self.start.set((idx + 1) % len);
+                    return Polled::Ready(idx, data);
+                }
+                Err(TryRecvError::Empty) => connected = true,
+                Err(TryRecvError::Disconnected) => {}
+            }
+        }
+        if connected {
+            Polled::Empty
+        } else {
+            Polled::Disconnected
+        }
+    }
+}
+
+impl<T> Default for Select<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpsc::bounded_mpsc::channel;
+    use crate::mpsc::unbounded_mpsc::unbounded;
+    use std::time::Duration;
+
+    #[test]
+    fn test_try_select_empty() {
+        let (_tx, rx) = channel::<u8>(1);
+        let mut sel = Select::new();
+        assert_eq!(sel.try_select(), None);
+        sel.add(&rx);
+        assert_eq!(sel.try_select(), None);
+    }
+
+    #[test]
+    fn test_busy_channel_does_not_starve_others() {
+        let (busy_tx, busy_rx) = unbounded();
+        let (quiet_tx, quiet_rx) = unbounded();
+        let mut sel = Select::new();
+        let busy = sel.add(&busy_rx);
+        let quiet = sel.add(&quiet_rx);
+
+        for i in 0..10 {
+            busy_tx.send(i).unwrap();
+        }
+        quiet_tx.send(100).unwrap();
+        quiet_tx.send(101).unwrap();
+
+        // The quiet channel gets a turn right after every value from the busy one
+        assert_eq!(sel.try_select(), Some((busy, 0)));
+        assert_eq!(sel.try_select(), Some((quiet, 100)));
+        assert_eq!(sel.try_select(), Some((busy, 1)));
+        assert_eq!(sel.try_select(), Some((quiet, 101)));
+        for i in 2..10 {
+            assert_eq!(sel.try_select(), Some((busy, i)));
+        }
+        assert_eq!(sel.try_select(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_select_parks_until_any_channel_sends() {
+        let (tx1, rx1) = channel::<u32>(1);
+        let (tx2, rx2) = channel(1);
+        let mut sel = Select::new();
+        sel.add(&rx1);
+        sel.add(&rx2);
+
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            tx2.send(7).unwrap();
+        });
+        assert_eq!(sel.select(), Ok((1, 7)));
+        handle.join().unwrap();
+
+        // One channel is disconnected now, the other is still open
+        assert_eq!(sel.try_select(), None);
+        drop(tx1);
+        assert_eq!(sel.select(), Err(RecvError));
+    }
+}
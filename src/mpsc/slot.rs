@@ -1,10 +1,11 @@
-use std::cell::UnsafeCell;
-use std::mem::MaybeUninit;
-use std::sync::atomic::fence;
-use std::sync::atomic::{
-    AtomicU8,
-    Ordering::{AcqRel, Acquire, Relaxed, Release},
-};
+use core::mem::MaybeUninit;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+
+use super::sync::{AtomicU8, UnsafeCell, fence};
+use crate::backoff::LocalBackoff;
+
+/// Number of backoff rounds `unset_wait` spins on a `RESERVED` slot before yielding.
+const UNSET_SPIN_ROUNDS: u32 = 64;
 
 /// A slot in a concurrent queue or stack, representing a cell that can store a value of type `T`.
 ///
@@ -17,6 +18,10 @@ use std::sync::atomic::{
 /// - `READY` (0): The slot is empty and ready to be written.
 /// - `RESERVED` (1): The slot is reserved for writing.
 /// - `REGISTERED` (2): The slot contains a value and is occupied.
+/// - `SKIPPED` (3): The slot was claimed by a producer that gave it up without writing it;
+///   the consumer steps over it.
+/// - `HELD` (4): The slot is held by a producer that has not got its value yet; the consumer
+///   does not wait for it.
 #[repr(Rust)]
 pub struct Slot<T> {
     /// The storage for the value in the slot. Access is controlled via `UnsafeCell` and `MaybeUninit`.
@@ -26,6 +31,14 @@ pub struct Slot<T> {
 }
 
 impl<T> Slot<T> {
+    /// Creates an empty slot in the `READY` state.
+    pub fn new() -> Self {
+        Self {
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            state: AtomicU8::new(READY),
+        }
+    }
+
     /// Attempts to write a value into the slot.
     ///
     /// Transitions the slot from `READY` to `REGISTERED`. If successful, writes `data` into the slot
@@ -41,6 +54,10 @@ impl<T> Slot<T> {
     /// * `Err(data)` if the slot was reserved/registered.
     ///
     pub fn set(&self, data: T) -> Result<(), T> {
+        // Strong on purpose: every caller has just won the slot's index and treats a failure
+        // here as a broken invariant, not as a reason to retry. A weak CAS may fail
+        // spuriously on LL/SC targets such as aarch64 without LSE, and on x86 or with LSE
+        // both compile to the same instruction anyway.
         if self
             .state
             .compare_exchange(READY, RESERVED, AcqRel, Relaxed)
@@ -65,19 +82,222 @@ impl<T> Slot<T> {
     /// * `Err(())` if the slot was not registered.
     ///
     pub fn unset(&self) -> Result<T, ()> {
-        if self
-            .state
+        if self.begin_take() {
+            Ok(unsafe { self.finish_take() })
+        } else {
+            Err(())
+        }
+    }
+
+    /// Like [`unset`](Self::unset), but waits out a producer that is mid-write.
+    ///
+    /// While the slot is `RESERVED` its value is only a write away, so this backs off with
+    /// [`LocalBackoff`], then yields, until the producer publishes it. Only fails with
+    /// `Err(())` if the slot is `READY`, i.e. nobody has started writing it yet.
+    pub fn unset_wait(&self) -> Result<T, ()> {
+        if self.begin_take_wait() {
+            Ok(unsafe { self.finish_take() })
+        } else {
+            Err(())
+        }
+    }
+
+    /// Transitions the slot from `REGISTERED` to `RESERVED`, so the consumer can work on its
+    /// value in place.
+    ///
+    /// Returns `false` if the slot was not `REGISTERED`. On success the slot stays
+    /// `RESERVED` until [`finish_take`](Self::finish_take) or
+    /// [`drop_taken`](Self::drop_taken) is called.
+    #[inline]
+    pub fn begin_take(&self) -> bool {
+        self.state
             .compare_exchange(REGISTERED, RESERVED, AcqRel, Relaxed)
             .is_ok()
-        {
-            let ret = unsafe { self.unchecked_unset() };
-            self.state.store(READY, Release);
-            Ok(ret)
+    }
+
+    /// Like [`begin_take`](Self::begin_take), but waits out a producer that is mid-write, as
+    /// [`unset_wait`](Self::unset_wait) does. Only returns `false` if the slot is `READY`.
+    pub fn begin_take_wait(&self) -> bool {
+        let backoff = LocalBackoff::new();
+        let mut rounds = 0;
+        loop {
+            match self
+                .state
+                .compare_exchange(REGISTERED, RESERVED, AcqRel, Relaxed)
+            {
+                Ok(_) => return true,
+                Err(READY) => return false,
+                Err(_) => {}
+            }
+            // Under loom every retry has to yield, or the model never schedules the producer.
+            if rounds < UNSET_SPIN_ROUNDS && !cfg!(all(loom, test)) {
+                backoff.wait();
+                rounds += 1;
+            } else {
+                // The producer may have been preempted mid-write; stop burning its CPU time.
+                #[cfg(any(feature = "std", all(loom, test)))]
+                super::sync::yield_now();
+                #[cfg(not(any(feature = "std", all(loom, test))))]
+                backoff.wait();
+            }
+        }
+    }
+
+    /// Returns a pointer to the value of a slot the caller has begun taking.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have made a successful [`begin_take`](Self::begin_take) call and not
+    /// finished the take yet. The pointer is only valid until then.
+    #[inline]
+    pub unsafe fn taken_ptr(&self) -> *mut T {
+        self.value
+            .with_mut(|value| unsafe { (*value).as_mut_ptr() })
+    }
+
+    /// Moves the value out of a slot the caller has begun taking and marks it `READY`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`taken_ptr`](Self::taken_ptr).
+    #[inline]
+    pub unsafe fn finish_take(&self) -> T {
+        let data = unsafe { self.unchecked_unset() };
+        self.state.store(READY, Release);
+        data
+    }
+
+    /// Drops the value of a slot the caller has begun taking in place and marks it `READY`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`taken_ptr`](Self::taken_ptr).
+    #[inline]
+    pub unsafe fn drop_taken(&self) {
+        self.value
+            .with_mut(|value| unsafe { (*value).assume_init_drop() });
+        self.state.store(READY, Release);
+    }
+
+    /// Hands a slot the caller has begun taking back as it was, value included, so it can be
+    /// taken again.
+    ///
+    /// # Safety
+    ///
+    /// As for [`taken_ptr`](Self::taken_ptr).
+    #[inline]
+    pub unsafe fn abort_take(&self) {
+        self.state.store(REGISTERED, Release);
+    }
+
+    /// Marks a claimed but never written slot as `SKIPPED`, so the consumer steps over it
+    /// instead of waiting for a value that will never come.
+    ///
+    /// Returns `false` if the slot was not `READY`.
+    pub fn skip(&self) -> bool {
+        self.state
+            .compare_exchange(READY, SKIPPED, Release, Relaxed)
+            .is_ok()
+    }
+
+    /// Marks a claimed slot as `HELD` by a producer that will only write it later, so the
+    /// consumer does not wait for it in the meantime.
+    ///
+    /// Returns `false` if the slot was not `READY`.
+    pub fn hold(&self) -> bool {
+        self.state
+            .compare_exchange(READY, HELD, Relaxed, Relaxed)
+            .is_ok()
+    }
+
+    /// Writes `data` into a slot the caller has [`hold`](Self::hold)ed.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have made a successful `hold` call and not released the slot since.
+    pub unsafe fn fill_held(&self, data: T) {
+        unsafe { self.unchecked_set(data) };
+        self.state.store(REGISTERED, Release);
+    }
+
+    /// Returns a pointer to the storage of a slot the caller has [`hold`](Self::hold)ed, for
+    /// the value to be built in place.
+    ///
+    /// # Safety
+    ///
+    /// As for [`fill_held`](Self::fill_held). The pointer is only valid until the slot is
+    /// committed or released.
+    #[inline]
+    pub unsafe fn held_ptr(&self) -> *mut MaybeUninit<T> {
+        self.value.with_mut(|value| value)
+    }
+
+    /// Marks a slot the caller has [`hold`](Self::hold)ed and written through
+    /// [`held_ptr`](Self::held_ptr) as `REGISTERED`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`fill_held`](Self::fill_held), and the value must be fully initialised.
+    #[inline]
+    pub unsafe fn commit_held(&self) {
+        self.state.store(REGISTERED, Release);
+    }
+
+    /// Releases a slot the caller has [`hold`](Self::hold)ed without writing it, marking it
+    /// `SKIPPED` if `skip`, or `READY` otherwise.
+    ///
+    /// # Safety
+    ///
+    /// As for [`fill_held`](Self::fill_held).
+    pub unsafe fn release_held(&self, skip: bool) {
+        self.state
+            .store(if skip { SKIPPED } else { READY }, Release);
+    }
+
+    /// Returns `true` if the slot is `SKIPPED`.
+    #[inline]
+    pub fn is_skipped(&self) -> bool {
+        self.state.load(Acquire) == SKIPPED
+    }
+
+    /// Returns `true` if the slot is `HELD`.
+    #[inline]
+    pub fn is_held(&self) -> bool {
+        self.state.load(Acquire) == HELD
+    }
+
+    /// Turns a `SKIPPED` slot back into a `READY` one, returning `false` if it was not
+    /// skipped.
+    pub fn clear_skip(&self) -> bool {
+        self.state
+            .compare_exchange(SKIPPED, READY, AcqRel, Relaxed)
+            .is_ok()
+    }
+
+    /// Returns a reference to the value in the slot, or `None` if it is not `REGISTERED`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only one allowed to `unset` this slot, and must not do so while
+    /// the returned reference is alive.
+    #[inline]
+    pub unsafe fn peek(&self) -> Option<&T> {
+        if self.state.load(Acquire) == REGISTERED {
+            Some(
+                self.value
+                    .with(|value| unsafe { (*value).assume_init_ref() }),
+            )
         } else {
-            Err(())
+            None
         }
     }
 
+    /// Puts the slot back into the `READY` state, forgetting any value it holds.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.state.store(READY, Relaxed);
+    }
+
     /// Writes a value into the slot without checking or updating the state.
     ///
     /// # Safety
@@ -86,7 +306,7 @@ impl<T> Slot<T> {
     /// when the caller has exclusive access to the slot and the slot's state is known.
     #[inline(always)]
     pub unsafe fn unchecked_set(&self, data: T) {
-        unsafe { (&mut *self.value.get()).write(data) };
+        self.value.with_mut(|value| unsafe { (*value).write(data) });
         fence(Release);
     }
 
@@ -99,14 +319,17 @@ impl<T> Slot<T> {
     #[inline(always)]
     pub unsafe fn unchecked_unset(&self) -> T {
         fence(Acquire);
-        unsafe { (&*self.value.get()).assume_init_read() }
+        self.value
+            .with(|value| unsafe { (*value).assume_init_read() })
     }
 }
 
 // Atomic state constants
-pub(super) const READY: u8 = 0; // Slot is empty
+const READY: u8 = 0; // Slot is empty; must stay 0, `SlotArr` and segments allocate slots zeroed
 const RESERVED: u8 = 1; // Slot is reserved for writing
 const REGISTERED: u8 = 2; // Slot contains data
+const SKIPPED: u8 = 3; // Slot was given up without data
+const HELD: u8 = 4; // Slot is held for a value to come
 
 #[cfg(test)]
 mod tests {
@@ -155,6 +378,52 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_unset_wait_fails_only_when_ready() {
+        let slot = Slot::<i32>::new();
+        assert_eq!(slot.unset_wait(), Err(()));
+        slot.set(5).unwrap();
+        assert_eq!(slot.unset_wait(), Ok(5));
+        assert_eq!(slot.state.load(Relaxed), READY);
+    }
+
+    /// Lets a test share a bare slot between threads, as the queues do for their slots.
+    struct Shared<T>(Slot<T>);
+
+    unsafe impl<T: Send> Sync for Shared<T> {}
+
+    // Once a producer has reserved the slot, the value is never reported missing
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_unset_wait_races_a_reserving_producer() {
+        use std::thread;
+
+        for i in 0..1_000 {
+            let slot = &Shared(Slot::new());
+            thread::scope(|s| {
+                s.spawn(move || slot.0.set(i).unwrap());
+                while slot.0.state.load(Acquire) == READY {
+                    thread::yield_now();
+                }
+                assert_eq!(slot.0.unset_wait(), Ok(i));
+            });
+        }
+    }
+
+    #[test]
+    fn test_skip_only_from_ready() {
+        let slot = Slot::new();
+        assert!(!slot.clear_skip());
+        assert!(slot.skip());
+        assert_eq!(slot.set(1), Err(1));
+        assert_eq!(slot.unset(), Err(()));
+        assert!(slot.clear_skip());
+
+        slot.set(2).unwrap();
+        assert!(!slot.skip());
+        assert_eq!(slot.unset(), Ok(2));
+    }
+
     #[test]
     fn test_unchecked_set_and_unset() {
         let slot = Slot {
@@ -169,3 +438,32 @@ mod tests {
         }
     }
 }
+
+/// Model-checked tests, run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+#[cfg(all(loom, test))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    struct Shared<T>(Slot<T>);
+
+    unsafe impl<T: Send> Sync for Shared<T> {}
+
+    #[test]
+    fn loom_unset_wait_sees_a_reserved_value() {
+        loom::model(|| {
+            let slot = Arc::new(Shared(Slot::new()));
+            let producer = {
+                let slot = Arc::clone(&slot);
+                thread::spawn(move || slot.0.set(7).unwrap())
+            };
+            let reserved = slot.0.state.load(Acquire) != READY;
+            match slot.0.unset_wait() {
+                Ok(value) => assert_eq!(value, 7),
+                Err(()) => assert!(!reserved, "a reserved value was reported missing"),
+            }
+            producer.join().unwrap();
+        });
+    }
+}
@@ -0,0 +1,108 @@
+//! The buffered values of a queue, taken out so they can be stored and queued again later.
+//!
+//! The queues themselves are made of atomics and raw slots, so they are not serializable.
+//! Instead, `drain` on either raw queue moves the buffered values into a [`QueueSnapshot`].
+//! With the `serde` feature the snapshot can be serialized, for example to persist in-flight
+//! work across a restart. `from_snapshot` builds a queue from it again.
+
+use alloc::vec::Vec;
+
+/// Values drained from a queue, oldest first, together with the capacity of that queue.
+///
+/// Made by `drain` on a raw queue, or collected from an iterator, and turned back into a
+/// queue with `from_snapshot`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueueSnapshot<T> {
+    /// Capacity of the bounded queue the values came from, or `None` for an unbounded queue
+    /// or a snapshot collected from an iterator.
+    pub capacity: Option<usize>,
+    /// The buffered values, oldest first.
+    pub items: Vec<T>,
+}
+
+impl<T> FromIterator<T> for QueueSnapshot<T> {
+    /// Collects the values into a snapshot with no capacity recorded.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            capacity: None,
+            items: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> IntoIterator for QueueSnapshot<T> {
+    type Item = T;
+    type IntoIter = alloc::vec::IntoIter<T>;
+
+    /// Yields the values, oldest first.
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpsc::{bounded_mpsc, unbounded_mpsc};
+
+    #[test]
+    fn test_bounded_drain_and_rebuild() {
+        let q = bounded_mpsc::RawMpsc::new(4);
+        for i in 0..3 {
+            q.push(i).unwrap();
+        }
+        let snapshot = q.drain();
+        assert!(q.is_empty());
+        assert_eq!(
+            snapshot,
+            QueueSnapshot {
+                capacity: Some(4),
+                items: vec![0, 1, 2]
+            }
+        );
+
+        let q = bounded_mpsc::RawMpsc::from_snapshot(snapshot);
+        assert_eq!(q.capacity(), 4);
+        assert_eq!(q.snapshot(), [0, 1, 2]);
+
+        // A recorded capacity too small for the values is raised to fit them
+        let q = bounded_mpsc::RawMpsc::from_snapshot(QueueSnapshot {
+            capacity: Some(1),
+            items: vec![7, 8],
+        });
+        assert_eq!(q.capacity(), 2);
+        assert_eq!(q.snapshot(), [7, 8]);
+    }
+
+    #[test]
+    fn test_unbounded_drain_and_rebuild() {
+        let q: unbounded_mpsc::RawMpsc<_> = (0..5).collect();
+        let snapshot = q.drain();
+        assert!(q.is_empty());
+        assert_eq!(snapshot.capacity, None);
+
+        let q: unbounded_mpsc::RawMpsc<_> = unbounded_mpsc::RawMpsc::from_snapshot(snapshot);
+        assert_eq!(q.snapshot(), [0, 1, 2, 3, 4]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let q = bounded_mpsc::RawMpsc::new(8);
+        for word in ["a", "b", "c"] {
+            q.push(String::from(word)).unwrap();
+        }
+
+        let json = serde_json::to_string(&q.drain()).unwrap();
+        assert_eq!(json, r#"{"capacity":8,"items":["a","b","c"]}"#);
+
+        let snapshot: QueueSnapshot<String> = serde_json::from_str(&json).unwrap();
+        let q = bounded_mpsc::RawMpsc::from_snapshot(snapshot);
+        assert_eq!(q.capacity(), 8);
+        assert_eq!(q.pop().as_deref(), Some("a"));
+        assert_eq!(q.pop().as_deref(), Some("b"));
+        assert_eq!(q.pop().as_deref(), Some("c"));
+        assert_eq!(q.pop(), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Runtime counters for the queues, compiled in only with the `metrics` feature.
+//!
+//! Every counter is bumped with a `Relaxed` increment on its own cache line, so producers
+//! don't contend on a shared line just to be counted. A snapshot reads each counter
+//! separately; taken while the queue is in use, it need not add up exactly.
+
+use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+use crate::cache_padded::CachePadded;
+
+/// A snapshot of a bounded queue's counters, returned by
+/// [`RawMpsc::stats`](crate::mpsc::bounded_mpsc::RawMpsc::stats).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Values enqueued.
+    pub pushes: u64,
+    /// Values taken out by the consumer, including those dropped by `clear`.
+    pub pops: u64,
+    /// Pushes rejected because the queue was full.
+    pub full: u64,
+    /// Times a producer lost the CAS on the head index and had to retry.
+    pub cas_retries: u64,
+}
+
+/// The live counters behind [`QueueStats`].
+pub(crate) struct QueueCounters {
+    pushes: CachePadded<AtomicU64>,
+    pops: CachePadded<AtomicU64>,
+    full: CachePadded<AtomicU64>,
+    cas_retries: CachePadded<AtomicU64>,
+}
+
+impl QueueCounters {
+    pub(crate) fn new() -> Self {
+        Self {
+            pushes: CachePadded::new(AtomicU64::new(0)),
+            pops: CachePadded::new(AtomicU64::new(0)),
+            full: CachePadded::new(AtomicU64::new(0)),
+            cas_retries: CachePadded::new(AtomicU64::new(0)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pushed(&self, n: usize) {
+        self.pushes.fetch_add(n as u64, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn popped(&self, n: usize) {
+        self.pops.fetch_add(n as u64, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn rejected_full(&self) {
+        self.full.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn cas_retried(&self) {
+        self.cas_retries.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> QueueStats {
+        QueueStats {
+            pushes: self.pushes.load(Relaxed),
+            pops: self.pops.load(Relaxed),
+            full: self.full.load(Relaxed),
+            cas_retries: self.cas_retries.load(Relaxed),
+        }
+    }
+}
+
+/// A snapshot of an unbounded queue's segment counters, returned by
+/// [`RawMpsc::segment_stats`](crate::mpsc::unbounded_mpsc::RawMpsc::segment_stats).
+///
+/// A high allocation rate relative to the message rate suggests a larger segment size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SegmentStats {
+    /// Segments allocated and linked by producers because the last one was full. The segments
+    /// a queue is created with are not counted.
+    pub segments_allocated: u64,
+    /// Drained segments the consumer has unlinked and handed over to be freed.
+    pub segments_reclaimed: u64,
+}
+
+/// The live counters behind [`SegmentStats`].
+pub(crate) struct SegmentCounters {
+    allocated: CachePadded<AtomicU64>,
+    reclaimed: CachePadded<AtomicU64>,
+}
+
+impl SegmentCounters {
+    pub(crate) fn new() -> Self {
+        Self {
+            allocated: CachePadded::new(AtomicU64::new(0)),
+            reclaimed: CachePadded::new(AtomicU64::new(0)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn allocated(&self) {
+        self.allocated.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn reclaimed(&self) {
+        self.reclaimed.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> SegmentStats {
+        SegmentStats {
+            segments_allocated: self.allocated.load(Relaxed),
+            segments_reclaimed: self.reclaimed.load(Relaxed),
+        }
+    }
+}
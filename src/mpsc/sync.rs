@@ -0,0 +1,43 @@
+//! The atomics and cells the queues synchronise through.
+//!
+//! Under `--cfg loom` test builds these are loom's model-checked stand-ins, so the loom tests
+//! explore every interleaving and every value a load is allowed to observe under the
+//! orderings the queues ask for, and flag a slot read that is not ordered after its write.
+
+#[cfg(not(all(loom, test)))]
+pub(crate) use core::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, fence};
+#[cfg(all(loom, test))]
+pub(crate) use loom::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, fence};
+
+#[cfg(all(loom, test))]
+pub(crate) use loom::cell::UnsafeCell;
+#[cfg(all(loom, test))]
+pub(crate) use loom::thread::yield_now;
+
+#[cfg(all(feature = "std", not(all(loom, test))))]
+pub(crate) use std::thread::yield_now;
+
+/// An `UnsafeCell` with loom's closure-based access API, so the same code can be checked by
+/// loom's cell access tracking.
+#[cfg(not(all(loom, test)))]
+#[derive(Debug)]
+#[repr(transparent)]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(all(loom, test)))]
+impl<T> UnsafeCell<T> {
+    #[inline(always)]
+    pub(crate) const fn new(data: T) -> Self {
+        Self(core::cell::UnsafeCell::new(data))
+    }
+
+    #[inline(always)]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    #[inline(always)]
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
@@ -0,0 +1,1027 @@
+//! A `Sender`/`Receiver` split over the unbounded [`RawMpsc`] queue.
+//!
+//! Mirrors the bounded channel so either flavour can be swapped in by changing the
+//! constructor: [`unbounded`] hands out any number of cloneable [`Sender`]s but exactly one
+//! [`Receiver`].
+
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(feature = "async")]
+use core::future::Future;
+use core::marker::PhantomData;
+#[cfg(feature = "async")]
+use core::pin::Pin;
+#[cfg(feature = "std")]
+use core::task::{Context, Poll};
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use super::raw_mpsc::RawMpsc;
+use crate::mpsc::chan::{Chan, RawQueue};
+#[cfg(feature = "std")]
+use crate::mpsc::errors::{RecvError, RecvTimeoutError};
+use crate::mpsc::errors::{SendError, TryRecvError, TrySendError};
+use crate::mpsc::queue::Ack;
+use crate::mpsc::select::sealed;
+
+impl<T> RawQueue for RawMpsc<T> {
+    type Item = T;
+
+    #[inline]
+    fn push(&self, data: T) -> Result<(), T> {
+        RawMpsc::push(self, data);
+        Ok(())
+    }
+
+    #[inline]
+    fn pop(&self) -> Option<T> {
+        RawMpsc::pop(self)
+    }
+
+    #[inline]
+    fn pop_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        RawMpsc::pop_with(self, f)
+    }
+
+    #[inline]
+    fn pop_ack(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        RawMpsc::pop_ack(self, f)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    fn len(&self) -> usize {
+        RawMpsc::len(self)
+    }
+}
+
+/// Creates an unbounded channel.
+///
+/// # Examples
+///
+/// ```
+/// use lock_free_mpsc::mpsc::unbounded_mpsc::unbounded;
+///
+/// let (tx, rx) = unbounded();
+/// tx.send(1).unwrap();
+/// assert_eq!(rx.recv(), Ok(1));
+/// ```
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Chan::new(RawMpsc::new()));
+    let sender = Sender {
+        inner: Arc::clone(&inner),
+    };
+    let receiver = Receiver {
+        inner,
+        _not_sync: PhantomData,
+    };
+    (sender, receiver)
+}
+
+/// The sending half of an unbounded channel.
+///
+/// Senders can be cloned freely and moved to other threads. The channel is disconnected
+/// once every `Sender` has been dropped.
+pub struct Sender<T> {
+    inner: Arc<Chan<RawMpsc<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value into the channel.
+    ///
+    /// The queue never fills up, so this only fails once the [`Receiver`] has been dropped or
+    /// the channel has been closed, in which case the value is handed back inside a
+    /// [`SendError`].
+    pub fn send(&self, data: T) -> Result<(), SendError<T>> {
+        self.try_send(data).map_err(SendError::from)
+    }
+
+    /// Sends a value into the channel without waiting.
+    ///
+    /// Never fails with [`TrySendError::Full`]; it is provided so code can switch between the
+    /// bounded and unbounded flavours without changes.
+    pub fn try_send(&self, data: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(data)
+    }
+
+    /// Closes the channel for every sender.
+    ///
+    /// Later sends fail with [`TrySendError::Closed`], and senders parked on a full channel
+    /// give up. Values already sent are still delivered: the [`Receiver`] drains them and
+    /// only then reports the channel as disconnected. Either side can close the channel.
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Returns `true` once the channel has been closed by either side.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Creates a [`WeakSender`] for this channel.
+    ///
+    /// The weak handle doesn't count as a sender: once every `Sender` has been dropped the
+    /// receiver sees the channel as disconnected, and the weak handle can no longer be
+    /// upgraded.
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Returns `true` if `other` sends into the same channel as `self`.
+    pub fn same_channel(&self, other: &Sender<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Returns `true` if `recv` receives from the channel `self` sends into.
+    pub fn same_channel_as(&self, recv: &Receiver<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &recv.inner)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.inner.acquire_sender();
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.inner.release_sender();
+    }
+}
+
+/// A handle to an unbounded channel that doesn't keep it connected.
+///
+/// Created by [`Sender::downgrade`]. It can't send by itself; [`upgrade`](Self::upgrade)
+/// turns it back into a [`Sender`] as long as at least one other `Sender` is still alive.
+pub struct WeakSender<T> {
+    inner: Weak<Chan<RawMpsc<T>>>,
+}
+
+impl<T> WeakSender<T> {
+    /// Returns a new [`Sender`] for the channel, or `None` if every `Sender` has been
+    /// dropped.
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        let inner = self.inner.upgrade()?;
+        // Built lazily: a `Sender` dropped on the failure path would release a count it
+        // never acquired.
+        inner.try_acquire_sender().then(|| Sender { inner })
+    }
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Weak::clone(&self.inner),
+        }
+    }
+}
+
+/// The receiving half of an unbounded channel.
+///
+/// There is exactly one `Receiver` per channel: it can be moved to another thread but is
+/// neither `Clone` nor `Sync`, so only one thread can ever pop from the queue.
+pub struct Receiver<T> {
+    inner: Arc<Chan<RawMpsc<T>>>,
+    /// Opts out of `Sync` so a `&Receiver` can't be shared between consumers.
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<T> Receiver<T> {
+    /// Attempts to receive a value without waiting.
+    ///
+    /// Returns [`TryRecvError::Empty`] if the channel is currently empty, or
+    /// [`TryRecvError::Disconnected`] once every sender is gone, or the channel is closed,
+    /// and the queue is drained.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// Runs `f` on the next value without moving it out of the channel, then drops it and
+    /// returns what `f` returned.
+    ///
+    /// Returns `None` without waiting if no value is ready, whether or not the channel is
+    /// still connected. Meant for large messages that are only needed by reference.
+    pub fn recv_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.inner.try_recv_with(f)
+    }
+
+    /// Runs `f` on the next value without moving it out of the channel. The value is dropped
+    /// if `f` returns [`Ack::Ack`], and handed out again by the next receive if it returns
+    /// [`Ack::Nack`].
+    ///
+    /// Returns `None` without waiting if no value is ready. A panic in `f` drops the value,
+    /// so a message that always fails its handler that way is not redelivered forever.
+    pub fn recv_ack(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        self.inner.try_recv_ack(f)
+    }
+
+    /// Closes the channel, so every later send fails with [`TrySendError::Closed`].
+    ///
+    /// Values already buffered are still received; once they are drained, receiving reports
+    /// the channel as disconnected even while senders are alive.
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Closes the channel like [`close`](Self::close), and drops every value already buffered
+    /// instead of leaving it to be received.
+    ///
+    /// Meant for a consumer that is shutting down and wants its memory back right away. Unlike
+    /// dropping the receiver, the `Sender`s keep a channel that cleanly reports
+    /// [`TrySendError::Closed`] to every later send. A send racing the close
+    /// may still land a value afterwards, which the next receive hands out.
+    pub fn close_and_clear(&self) {
+        self.inner.close_and_clear();
+    }
+
+    /// Returns `true` once the channel has been closed by either side.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Receives a value, blocking the current thread until one is available.
+    ///
+    /// After a short spin the thread parks and is unparked by the next send, so an idle
+    /// receiver does not burn CPU.
+    ///
+    /// Returns [`RecvError`] once every sender has been dropped and the queue is drained;
+    /// values sent before the last sender was dropped are still delivered first.
+    #[cfg(feature = "std")]
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.inner.recv()
+    }
+
+    /// Receives every value until the senders are done, calling `f` on each in order.
+    ///
+    /// Blocks like [`recv`](Self::recv) between values, and returns once every sender has
+    /// been dropped, or the channel closed, and every value sent before that has been passed
+    /// to `f`. The usual "consume until the producers finish" loop, in one call.
+    #[cfg(feature = "std")]
+    pub fn recv_until_disconnect(&self, f: impl FnMut(T)) {
+        self.inner.recv_until_disconnect(f);
+    }
+
+    /// Receives a batch of values, blocking until at least one is available.
+    ///
+    /// Once the first value arrives, up to `max - 1` more that are ready right away are taken
+    /// without waiting, so a busy pipeline pays for one wake-up per batch rather than per
+    /// value. The values are appended to `out` in FIFO order and their number is returned; a
+    /// `max` of zero returns `Ok(0)` without blocking.
+    ///
+    /// Returns [`RecvError`] if the channel is disconnected, or closed, and empty.
+    #[cfg(feature = "std")]
+    pub fn recv_batch(&self, max: usize, out: &mut Vec<T>) -> Result<usize, RecvError> {
+        self.inner.recv_batch(max, out)
+    }
+
+    /// Blocks until at least `n` values are queued, so a consumer can wait for a minimum
+    /// batch before draining it with [`recv_batch`](Self::recv_batch).
+    ///
+    /// An `n` of zero returns `Ok(())` without blocking.
+    ///
+    /// Returns [`RecvError`] if the channel is disconnected, or closed, with fewer than `n`
+    /// values left.
+    #[cfg(feature = "std")]
+    pub fn wait_for(&self, n: usize) -> Result<(), RecvError> {
+        self.inner.wait_for(n)
+    }
+
+    /// Receives a value, blocking for at most `timeout`.
+    ///
+    /// Returns [`RecvTimeoutError::Timeout`] if nothing arrived in time, or
+    /// [`RecvTimeoutError::Disconnected`] once every sender is gone, or the channel is closed,
+    /// and the queue is drained.
+    #[cfg(feature = "std")]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.inner.recv_timeout(timeout)
+    }
+
+    /// Receives a batch of values, waiting at most `timeout` for the first one.
+    ///
+    /// Like [`recv_batch`](Self::recv_batch), up to `max - 1` more values that are ready
+    /// once the first has arrived are taken without waiting, and appended to `out` in FIFO
+    /// order. Bounds the latency of a batching consumer: flush whatever came in within
+    /// `timeout`, or as soon as `max` values are there. A `max` of zero returns `Ok(0)`
+    /// without blocking.
+    ///
+    /// Returns [`RecvTimeoutError::Timeout`] only if no value arrived in time, or
+    /// [`RecvTimeoutError::Disconnected`] once every sender is gone, or the channel is closed,
+    /// and the queue is drained.
+    #[cfg(feature = "std")]
+    pub fn recv_many_timeout(
+        &self,
+        max: usize,
+        timeout: Duration,
+        out: &mut Vec<T>,
+    ) -> Result<usize, RecvTimeoutError> {
+        self.inner.recv_many_timeout(max, timeout, out)
+    }
+
+    /// Receives a value, blocking until `deadline` at the latest.
+    ///
+    /// Behaves like [`recv_timeout`](Self::recv_timeout) but takes an absolute wake-up time.
+    /// If `deadline` has already passed the channel is polled exactly once, without parking.
+    #[cfg(feature = "std")]
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        self.inner.recv_deadline(deadline)
+    }
+
+    /// Returns an iterator over the values that can be received without waiting.
+    ///
+    /// The iterator stops at the first moment the queue is observed empty, so it drains only
+    /// what is visible at the time; producers may keep adding values afterwards, which a later
+    /// call will pick up. It never blocks or spins.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+
+    /// Removes every value currently buffered and returns them in FIFO order.
+    ///
+    /// Like [`try_iter`](Self::try_iter) this never blocks and only sees what is visible at
+    /// call time, but it collects the batch into a `Vec` in one call.
+    pub fn drain(&self) -> Vec<T> {
+        self.try_iter().collect()
+    }
+
+    /// Polls for the next value, for executors driving the receiver by hand.
+    ///
+    /// Returns `Poll::Ready(Some(_))` with a value, `Poll::Ready(None)` once every sender has
+    /// been dropped (or the channel was closed) and the buffered values are drained, and
+    /// otherwise registers the task's waker and returns `Poll::Pending`; the next send, or the
+    /// channel ending, wakes it. Only the most recently registered waker is woken.
+    ///
+    /// Needs no external crate, so it works with any runtime or a hand-rolled one without the
+    /// `async` feature. It does need `std`, since the waker is kept behind a
+    /// `std::sync::Mutex`.
+    #[cfg(feature = "std")]
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.inner.poll_recv(cx)
+    }
+
+    /// Turns the receiver into an [`AsyncReceiver`] for use from async code.
+    #[cfg(feature = "async")]
+    pub fn into_async(self) -> AsyncReceiver<T> {
+        AsyncReceiver { rx: self }
+    }
+}
+
+impl<T> sealed::Sealed<T> for Receiver<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    #[cfg(feature = "std")]
+    fn prepare_park(&self) {
+        self.inner.prepare_park();
+    }
+
+    #[cfg(feature = "std")]
+    fn cancel_park(&self) {
+        self.inner.cancel_park();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.disconnect_receiver();
+    }
+}
+
+/// A non-blocking iterator over the values currently buffered in a channel.
+///
+/// Created by [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// A blocking iterator that owns the [`Receiver`].
+///
+/// Each call to `next` waits for the next value and returns `None` once every sender has
+/// been dropped and the queue is drained. Created by `Receiver::into_iter`, which makes
+/// `for value in receiver { .. }` the natural consumer loop.
+#[cfg(feature = "std")]
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+#[cfg(feature = "std")]
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
+
+/// The receiving half of a channel, for use from async code.
+///
+/// Created by [`Receiver::into_async`]. It implements [`Stream`], yielding values until every
+/// sender has been dropped and the queue is drained, and [`recv`](Self::recv) gives a future
+/// for `rx.recv().await` without pulling in a stream combinator crate. Waiting never blocks
+/// the executor thread: an empty channel registers the task's waker, and the next send
+/// wakes it.
+#[cfg(feature = "async")]
+pub struct AsyncReceiver<T> {
+    rx: Receiver<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncReceiver<T> {
+    /// Returns a future that resolves to the next value, or to `None` once every sender has
+    /// been dropped and the queue is drained.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { rx: self }
+    }
+
+    /// Turns this back into a blocking [`Receiver`].
+    pub fn into_inner(self) -> Receiver<T> {
+        self.rx
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Stream for AsyncReceiver<T> {
+    type Item = T;
+
+    /// Returns `Poll::Ready(None)` once every sender has been dropped and the queue is
+    /// drained; values sent before that are still delivered first.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.rx.inner.poll_recv(cx)
+    }
+}
+
+/// Future returned by [`AsyncReceiver::recv`].
+#[cfg(feature = "async")]
+pub struct Recv<'a, T> {
+    rx: &'a mut AsyncReceiver<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Pin::new(&mut *self.rx).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn test_recv_ack_redelivers_on_nack() {
+        let (tx, rx) = unbounded();
+        tx.send(String::from("retry me")).unwrap();
+        tx.send(String::from("next")).unwrap();
+
+        let mut attempts = 0;
+        let mut handle = |msg: &mut String| {
+            attempts += 1;
+            if attempts < 3 {
+                msg.push('!');
+                Ack::Nack
+            } else {
+                assert_eq!(msg, "retry me!!");
+                Ack::Ack
+            }
+        };
+        assert_eq!(rx.recv_ack(&mut handle), Some(Ack::Nack));
+        assert_eq!(rx.recv_ack(&mut handle), Some(Ack::Nack));
+        assert_eq!(rx.recv_ack(&mut handle), Some(Ack::Ack));
+        assert_eq!(rx.try_recv().as_deref(), Ok("next"));
+        assert_eq!(rx.recv_ack(|_| Ack::Ack), None);
+    }
+
+    #[test]
+    fn test_same_channel() {
+        let (tx, rx) = unbounded::<i32>();
+        let tx2 = tx.clone();
+        assert!(tx.same_channel(&tx2));
+        assert!(tx2.same_channel_as(&rx));
+
+        let (other_tx, other_rx) = unbounded::<i32>();
+        assert!(!tx.same_channel(&other_tx));
+        assert!(!tx.same_channel_as(&other_rx));
+        assert!(!other_tx.same_channel_as(&rx));
+    }
+
+    #[test]
+    fn test_send_recv() {
+        let (tx, rx) = unbounded();
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+        for i in 0..1000 {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_recv_with() {
+        let (tx, rx) = unbounded();
+        tx.send(vec![1, 2, 3]).unwrap();
+        assert_eq!(rx.recv_with(|v| v.iter().sum::<i32>()), Some(6));
+        assert_eq!(rx.recv_with(|v| v.len()), None);
+        drop(tx);
+        assert_eq!(rx.recv_with(|v| v.len()), None);
+    }
+
+    #[test]
+    fn test_recv_disconnected_after_drain() {
+        let (tx, rx) = unbounded();
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        tx2.send(2).unwrap();
+        drop(tx2);
+
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_send_after_receiver_dropped() {
+        let (tx, rx) = unbounded();
+        drop(rx);
+        assert_eq!(tx.send(7), Err(SendError(7)));
+        assert_eq!(tx.try_send(8), Err(TrySendError::Disconnected(8)));
+    }
+
+    // A dropped receiver turns sends away before they reach the queue
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_send_after_receiver_dropped_allocates_nothing() {
+        let (tx, rx) = unbounded();
+        drop(rx);
+        for i in 0..1_000 {
+            assert_eq!(tx.send(i), Err(SendError(i)));
+        }
+        assert_eq!(tx.inner.queue.segment_stats().segments_allocated, 0);
+        assert!(tx.inner.queue.is_empty());
+    }
+
+    #[test]
+    fn test_close_then_drain() {
+        let (tx, rx) = unbounded();
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        tx.close();
+        assert_eq!(tx.send(3), Err(SendError(3)));
+        assert_eq!(tx.try_send(4), Err(TrySendError::Closed(4)));
+
+        assert_eq!(rx.drain(), [0, 1, 2]);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_close_and_clear() {
+        let (tx, rx) = unbounded();
+        let value = Arc::new(());
+        for _ in 0..300 {
+            tx.send(Arc::clone(&value)).unwrap();
+        }
+        rx.close_and_clear();
+        assert_eq!(Arc::strong_count(&value), 1);
+        assert_eq!(tx.send(value), Err(SendError(Arc::new(()))));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_close_races_senders_without_losing_values() {
+        for _ in 0..20 {
+            let (tx, rx) = unbounded();
+            let accepted = Arc::new(AtomicUsize::new(0));
+            let handles: Vec<_> = (0..3)
+                .map(|_| {
+                    let (tx, accepted) = (tx.clone(), Arc::clone(&accepted));
+                    thread::spawn(move || {
+                        while tx.send(0u8).is_ok() {
+                            accepted.fetch_add(1, Ordering::Relaxed);
+                        }
+                    })
+                })
+                .collect();
+            thread::yield_now();
+            rx.close();
+
+            // Every send that succeeded is received before the channel reports its end
+            let mut received = 0;
+            while rx.recv().is_ok() {
+                received += 1;
+            }
+            for h in handles {
+                h.join().unwrap();
+            }
+            assert_eq!(received, accepted.load(Ordering::Relaxed));
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_sender_clone_drop_churn() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 100;
+
+        let (tx, rx) = unbounded();
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..ROUNDS {
+                        let clone = tx.clone();
+                        clone.send(t * ROUNDS + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = 0;
+        while rx.recv().is_ok() {
+            received += 1;
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(received, THREADS * ROUNDS);
+        assert!(rx.inner.is_disconnected());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_weak_sender() {
+        let (tx, rx) = unbounded();
+        let weak = tx.downgrade();
+
+        let upgraded = weak.upgrade().unwrap();
+        upgraded.send(1).unwrap();
+        drop(upgraded);
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        drop(tx);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        drop(rx);
+        assert!(weak.clone().upgrade().is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_cloned_senders_across_threads() {
+        const SENDERS: usize = 4;
+        const ITEMS: usize = 1000;
+
+        let (tx, rx) = unbounded();
+        let handles: Vec<_> = (0..SENDERS)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..ITEMS {
+                        tx.send(t * ITEMS + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Ok(value) = rx.recv() {
+            received.push(value);
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        received.sort_unstable();
+        assert_eq!(received, (0..SENDERS * ITEMS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_parks_until_send() {
+        let (tx, rx) = unbounded();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(5).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        });
+        assert_eq!(rx.recv(), Ok(5));
+        // The sender is dropped while the receiver is parked
+        assert_eq!(rx.recv(), Err(RecvError));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_batch_blocks_for_the_first_value_only() {
+        let (tx, rx) = unbounded();
+        let producer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            for i in 0..5 {
+                tx.send(i).unwrap();
+            }
+            tx
+        });
+
+        let mut out = Vec::new();
+        let first = rx.recv_batch(3, &mut out).unwrap();
+        assert!((1..=3).contains(&first));
+        let tx = producer.join().unwrap();
+        while out.len() < 5 {
+            rx.recv_batch(3, &mut out).unwrap();
+        }
+        assert_eq!(out, [0, 1, 2, 3, 4]);
+        assert_eq!(rx.recv_batch(0, &mut out), Ok(0));
+
+        drop(tx);
+        assert_eq!(rx.recv_batch(3, &mut out), Err(RecvError));
+        assert_eq!(out.len(), 5);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_until_disconnect_sees_every_value() {
+        const PRODUCERS: usize = 4;
+        const PER_PRODUCER: usize = 500;
+        let (tx, rx) = unbounded();
+        let handles: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        tx.send((p, i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut next = [0; PRODUCERS];
+        let mut calls = 0;
+        rx.recv_until_disconnect(|(p, i)| {
+            assert_eq!(i, next[p], "producer {p} out of order");
+            next[p] += 1;
+            calls += 1;
+        });
+        assert_eq!(calls, PRODUCERS * PER_PRODUCER);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_many_timeout() {
+        let (tx, rx) = unbounded();
+        let mut out = Vec::new();
+        assert_eq!(
+            rx.recv_many_timeout(8, Duration::from_millis(10), &mut out),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(1).unwrap();
+        });
+        assert_eq!(
+            rx.recv_many_timeout(8, Duration::from_secs(5), &mut out),
+            Ok(1)
+        );
+        handle.join().unwrap();
+        assert_eq!(out, [1]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_wait_for_blocks_until_the_threshold() {
+        let (tx, rx) = unbounded();
+        assert_eq!(rx.wait_for(0), Ok(()));
+
+        let producer = thread::spawn(move || {
+            for i in 0..4 {
+                thread::sleep(Duration::from_millis(2));
+                tx.send(i).unwrap();
+            }
+            tx
+        });
+        assert_eq!(rx.wait_for(4), Ok(()));
+        let mut out = Vec::new();
+        assert_eq!(rx.recv_batch(4, &mut out), Ok(4));
+        assert_eq!(out, [0, 1, 2, 3]);
+
+        // The last sender leaving below the threshold wakes the parked receiver
+        let tx = producer.join().unwrap();
+        let handle = thread::spawn(move || {
+            tx.send(4).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        });
+        assert_eq!(rx.wait_for(2), Err(RecvError));
+        assert_eq!(rx.wait_for(1), Ok(()));
+        assert_eq!(rx.recv(), Ok(4));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_timeout() {
+        let (tx, rx) = unbounded();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(9).unwrap();
+        });
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_millis(100)), Ok(9));
+        handle.join().unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_recv_deadline() {
+        let (tx, rx) = unbounded();
+        let past = Instant::now();
+
+        // A passed deadline still picks up what is already buffered
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_deadline(past), Ok(1));
+        assert_eq!(rx.recv_deadline(past), Err(RecvTimeoutError::Timeout));
+
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(2).unwrap();
+        });
+        let deadline = Instant::now() + Duration::from_millis(500);
+        assert_eq!(rx.recv_deadline(deadline), Ok(2));
+        handle.join().unwrap();
+        assert_eq!(rx.recv_deadline(past), Err(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn test_try_iter_drains_available() {
+        let (tx, rx) = unbounded();
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.try_iter().count(), 5);
+        assert_eq!(rx.try_iter().next(), None);
+
+        tx.send(5).unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![5]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_into_iter_ends_on_disconnect() {
+        let (tx, rx) = unbounded();
+        let handles: Vec<_> = (0..2)
+            .map(|t| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..4 {
+                        tx.send(t * 4 + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received: Vec<_> = rx.into_iter().collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_drain_across_segments() {
+        let (tx, rx) = unbounded();
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.drain(), (0..1000).collect::<Vec<_>>());
+        assert!(rx.drain().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_poll_recv_wakes_the_registered_waker() {
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct CountingWaker(AtomicUsize);
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&counter));
+        let mut cx = Context::from_waker(&waker);
+        let (tx, rx) = unbounded();
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+
+        let producer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(1).unwrap();
+        });
+        while counter.0.load(Ordering::SeqCst) == 0 {
+            thread::yield_now();
+        }
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(1)));
+        producer.join().unwrap();
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        use std::sync::Arc;
+        use std::task::{Wake, Waker};
+
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let mut fut = std::pin::pin!(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_async_recv_wakes_on_send_and_ends_on_disconnect() {
+        let (tx, rx) = unbounded();
+        let mut rx = rx.into_async();
+        let handle = thread::spawn(move || {
+            for i in 0..3 {
+                thread::sleep(Duration::from_millis(5));
+                tx.send(i).unwrap();
+            }
+        });
+
+        let received = block_on(async {
+            let mut received = Vec::new();
+            while let Some(value) = rx.recv().await {
+                received.push(value);
+            }
+            received
+        });
+        handle.join().unwrap();
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+}
@@ -1,2 +1,14 @@
+mod channel;
 mod raw_mpsc;
+mod reclaim;
 mod segment_arr;
+mod storage;
+
+#[cfg(feature = "std")]
+pub use channel::IntoIter;
+#[cfg(feature = "async")]
+pub use channel::{AsyncReceiver, Recv};
+pub use channel::{Receiver, Sender, TryIter, WeakSender, unbounded};
+pub use raw_mpsc::RawMpsc;
+pub use segment_arr::SEGMENT_SIZE;
+pub use storage::{HeapSegment, InlineSegment, SegmentStorage};
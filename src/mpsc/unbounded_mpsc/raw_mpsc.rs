@@ -1,138 +1,721 @@
-use std::{
-    fmt::Debug, hint::spin_loop, mem::transmute, sync::atomic::{
-        fence, AtomicBool, AtomicPtr, Ordering::{AcqRel, Acquire, Relaxed, Release}
-    }
-};
+//! A low-level unbounded Multi-Producer Single-Consumer (MPSC) queue.
+//!
+//! The queue is a singly linked list of [`Segment`]s, each with a fixed number of slots.
+//! Producers claim slots in the segment pointed to by `tail` and, once it is exhausted, append
+//! a fresh segment with a CAS on its `next` pointer and swing `tail` forward, while the single
+//! consumer walks the list from `head`, retiring every segment it has fully drained. No thread
+//! ever waits for another to finish linking: whoever finds `tail` lagging behind a linked
+//! segment advances it. Retired segments are freed by a [`Reclaimer`] once no producer that
+//! might still hold a pointer to one is running; see its module for the race this closes.
+//!
+//! A segment is filled once per trip through the list: its slot indices only count up, so it
+//! is finished as soon as the consumer's index reaches its slot count. Once no producer can
+//! reach a retired segment any more, the consumer empties it and keeps it for producers to
+//! link again instead of allocating, one at a time; any further ones are freed.
+
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst};
+use core::{fmt, ptr};
 
+#[cfg(feature = "metrics")]
+use crate::mpsc::stats::{SegmentCounters, SegmentStats};
 use crate::{
     backoff::LocalBackoff,
-    mpsc::unbounded_mpsc::segment_arr::{SEGMENT_SIZE, Segment},
+    cache_padded::CachePadded,
+    mpsc::queue::{Ack, Queue},
+    mpsc::snapshot::QueueSnapshot,
+    mpsc::sync::{AtomicPtr, AtomicUsize},
+    mpsc::unbounded_mpsc::{
+        reclaim::Reclaimer,
+        segment_arr::{OwnedSegment, SEGMENT_SIZE, Segment, SegmentRef},
+        storage::{HeapSegment, SegmentStorage},
+    },
 };
 
-pub struct RawMpsc<T> {
-    head: AtomicPtr<Segment<T>>,
-    tail: AtomicPtr<Segment<T>>,
-    segment_allocation_pending: AtomicBool,
+/// Number of slots in the first segment of a [`growing`](RawMpsc::growing) queue.
+const MIN_SEGMENT_SIZE: usize = 4;
+
+/// An unbounded lock-free multi-producer single-consumer (MPSC) queue.
+///
+/// `RawMpsc<T>` never rejects a `push`; when the current segment is full a new one is
+/// allocated and linked behind it. Only one thread may `pop` at a time.
+///
+/// `SEG` is the number of slots per segment. Small segments keep the memory held by a
+/// nearly empty queue low, large ones allocate less often under sustained throughput. A
+/// [`growing`](Self::growing) queue starts small and works its way up to `SEG`.
+///
+/// `S` picks where the first segment lives: on the heap like the others ([`HeapSegment`]),
+/// or inline in the queue ([`InlineSegment`](super::InlineSegment)).
+///
+/// Like the bounded queue, it is only `Send` and `Sync` when `T: Send`:
+///
+/// ```compile_fail
+/// use lock_free_mpsc::mpsc::unbounded_mpsc::RawMpsc;
+/// use std::rc::Rc;
+///
+/// let q = RawMpsc::<Rc<i32>>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| q.push(Rc::new(1)));
+/// });
+/// ```
+pub struct RawMpsc<T, const SEG: usize = SEGMENT_SIZE, S: SegmentStorage = HeapSegment> {
+    /// The segment the consumer is currently draining.
+    head: CachePadded<AtomicPtr<Segment<T, SEG>>>,
+    /// The segment producers are currently filling.
+    tail: CachePadded<AtomicPtr<Segment<T, SEG>>>,
+    /// The first segment, if `S` keeps it inline. Never freed on its own, but recycled like
+    /// a heap one once the consumer has moved past it.
+    inline: S::Inline<T, SEG>,
+    /// An empty segment kept for the next producer that needs one; null if there is none.
+    recycled: AtomicPtr<Segment<T, SEG>>,
+    /// Total number of values pushed, only used to report [`len`](Self::len).
+    pushed: CachePadded<AtomicUsize>,
+    /// Total number of values popped, only used to report [`len`](Self::len).
+    popped: CachePadded<AtomicUsize>,
+    /// Number of segments currently linked, only used for `Debug` output.
+    segments: AtomicUsize,
+    /// Recycles drained segments once no producer can still be looking at them; `None`
+    /// stands for the inline one.
+    reclaimer: Reclaimer<Option<OwnedSegment<T, SEG>>>,
+    /// Counters reported by [`segment_stats`](Self::segment_stats).
+    #[cfg(feature = "metrics")]
+    metrics: SegmentCounters,
 }
 
-impl<T: Debug> RawMpsc<T> {
+impl<T> RawMpsc<T> {
+    /// Creates a new, empty unbounded MPSC queue with a single pre-allocated segment of the
+    /// default size.
     pub fn new() -> Self {
-        let segment_ptr = Box::into_raw(Box::new(Segment::new()));
-        let head = AtomicPtr::new(segment_ptr);
-        let tail = AtomicPtr::new(segment_ptr);
-        let segment_allocation_pending = AtomicBool::new(false);
-        Self {
-            head,
-            tail,
-            segment_allocation_pending,
-        }
+        Self::with_segment_size()
     }
 
-    #[inline]
-    fn wait_for_seg_alloc(&self){
-        while self.segment_allocation_pending.load(Acquire){
-            spin_loop();
+    /// Creates a new, empty unbounded MPSC queue with `segments` segments of the default
+    /// size already allocated and linked.
+    ///
+    /// Pushes fill the reserved segments before allocating, so a workload that knows it will
+    /// buffer about `segments * 128` values does not pay for allocation on the `push` path.
+    /// Reserved segments are freed as the consumer drains them, like any other. At least one
+    /// segment is always allocated.
+    pub fn with_segments(segments: usize) -> Self {
+        Self::with_linked_segments(SEGMENT_SIZE, segments)
+    }
+}
+
+impl<T, const SEG: usize, S: SegmentStorage> RawMpsc<T, SEG, S> {
+    /// Creates a new, empty unbounded MPSC queue whose segments hold `SEG` values each.
+    ///
+    /// The size is picked through the type, e.g. `RawMpsc::<u32, 16>::with_segment_size()`,
+    /// as is the first segment's storage, e.g. `RawMpsc::<u32, 16, InlineSegment>`.
+    /// A segment too large to allocate is rejected at compile time:
+    ///
+    /// ```compile_fail
+    /// use lock_free_mpsc::mpsc::unbounded_mpsc::RawMpsc;
+    ///
+    /// // Each segment would take 2^70 bytes.
+    /// let q = RawMpsc::<[u8; 1 << 20], { 1 << 50 }>::with_segment_size();
+    /// ```
+    pub fn with_segment_size() -> Self {
+        Self::with_linked_segments(SEG, 1)
+    }
+
+    /// Creates a new, empty unbounded MPSC queue whose segments grow along with it.
+    ///
+    /// The first segment holds 4 values, or `SEG` if that is fewer, and every segment
+    /// allocated after it twice as many as the one it follows, up to `SEG`. A queue that
+    /// stays small holds little memory, while one that bursts allocates about as rarely as a
+    /// `Vec` would. An inline first segment always has `SEG` slots, leaving nothing to grow.
+    pub fn growing() -> Self {
+        Self::with_linked_segments(MIN_SEGMENT_SIZE.min(SEG), 1)
+    }
+
+    /// Creates a queue holding the values of `snapshot`, in order.
+    ///
+    /// The queue is unbounded, so any capacity recorded in the snapshot is ignored.
+    pub fn from_snapshot(snapshot: QueueSnapshot<T>) -> Self {
+        snapshot.items.into_iter().collect()
+    }
+
+    /// Creates a queue whose list starts out with `segments` (at least one) empty segments,
+    /// the first of `first_len` slots and the others of `SEG`.
+    ///
+    /// With an inline first segment only the ones after it are allocated, and `first_len`
+    /// is ignored.
+    fn with_linked_segments(first_len: usize, segments: usize) -> Self {
+        let inline = S::new_inline();
+        let (first, mut last) = match S::inline(&inline) {
+            Some(segment) => (Self::INLINE, segment),
+            None => {
+                let segment = OwnedSegment::new(first_len).into_raw();
+                (segment, unsafe { SegmentRef::new(segment) })
+            }
+        };
+        for _ in 1..segments {
+            let next = OwnedSegment::new(SEG).into_raw();
+            last.next.store(next, Relaxed);
+            // SAFETY: just allocated, and only freed once the queue is.
+            last = unsafe { SegmentRef::new(next) };
+        }
+        Self {
+            head: CachePadded::new(AtomicPtr::new(first)),
+            tail: CachePadded::new(AtomicPtr::new(first)),
+            inline,
+            recycled: AtomicPtr::new(ptr::null_mut()),
+            pushed: CachePadded::new(AtomicUsize::new(0)),
+            popped: CachePadded::new(AtomicUsize::new(0)),
+            segments: AtomicUsize::new(segments.max(1)),
+            reclaimer: Reclaimer::new(),
+            #[cfg(feature = "metrics")]
+            metrics: SegmentCounters::new(),
         }
     }
 
+    /// Pushes data into the queue, allocating a new segment if the current one is full.
     pub fn push(&self, mut data: T) {
-        loop{
-            self.wait_for_seg_alloc();
-            let tail = self.tail.load(Acquire);
-            let segment = unsafe{&*tail};
-            match Self::segment_push(segment, data){
-                Ok(_)=>{return;}
-                Err(d)=>{
+        // Keeps every segment reachable from `tail` from here on alive until the push is done.
+        let _guard = self.reclaimer.pin();
+        // A segment taken for a `next` another producer linked first, kept for the next full
+        // segment instead of being given back straight away.
+        let mut spare = None;
+        loop {
+            let tail = self.tail.load(SeqCst);
+            let segment = unsafe { self.segment(tail) };
+            match Self::segment_push(segment, data) {
+                Ok(_) => {
+                    self.pushed.fetch_add(1, Relaxed);
+                    if let Some(spare) = spare {
+                        self.keep_recycled(spare);
+                    }
+                    return;
+                }
+                Err(d) => {
                     data = d;
-                    match self.segment_allocation_pending.compare_exchange(false, true, AcqRel, Relaxed){
-                        Ok(_)=>{
-                            let new_block = Box::into_raw(Box::new(Segment::new()));
-                            segment.next.set(new_block);
-                            fence(Release);
-                            self.tail.store(new_block, Release);
-                            self.segment_allocation_pending.store(false, Release);
-                        },
-                        Err(_)=>continue
+                    let mut next = segment.next.load(Acquire);
+                    if next.is_null() {
+                        // Each segment is twice as long as the one it follows, up to `SEG`, so
+                        // only a queue that started out with a shorter one grows.
+                        let len = (segment.len * 2).min(SEG);
+                        let new_block = spare.take().unwrap_or_else(|| self.take_segment(len));
+                        match segment.next.compare_exchange(
+                            ptr::null_mut(),
+                            new_block,
+                            AcqRel,
+                            Acquire,
+                        ) {
+                            Ok(_) => {
+                                self.segments.fetch_add(1, Relaxed);
+                                next = new_block;
+                            }
+                            Err(linked) => {
+                                spare = Some(new_block);
+                                next = linked;
+                            }
+                        }
+                    }
+                    self.advance_tail(tail, next);
+                }
+            }
+        }
+    }
+
+    /// Pushes every value of `items` in order, leaving it empty, and returns how many that
+    /// was.
+    ///
+    /// Enough segments for the whole batch are linked up front with
+    /// [`reserve`](Self::reserve), so the values are written without allocating in between.
+    /// Values of other producers pushing at the same time may be interleaved with them.
+    pub fn push_bulk(&self, items: &mut Vec<T>) -> usize {
+        let count = items.len();
+        self.reserve(count);
+        for data in items.drain(..) {
+            self.push(data);
+        }
+        count
+    }
+
+    /// Links enough empty segments behind `tail` for at least `additional` more values to be
+    /// pushed without allocating.
+    ///
+    /// Counts the free slots already linked, so reserving again for the same values
+    /// allocates nothing. Every reserved segment holds `SEG` values. Other producers may
+    /// fill the room before the caller gets to it, in which case their pushes simply
+    /// allocated ahead of time.
+    pub fn reserve(&self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+        // Keeps every segment reachable from `tail` alive while the list is walked.
+        let _guard = self.reclaimer.pin();
+        let mut segment = unsafe { self.segment(self.tail.load(SeqCst)) };
+        let mut free = segment.len - segment.next_head.load(Acquire).min(segment.len);
+        loop {
+            let mut next = segment.next.load(Acquire);
+            if next.is_null() {
+                if free >= additional {
+                    return;
+                }
+                let new_block = OwnedSegment::new(SEG).into_raw();
+                match segment
+                    .next
+                    .compare_exchange(ptr::null_mut(), new_block, AcqRel, Acquire)
+                {
+                    Ok(_) => {
+                        self.segments.fetch_add(1, Relaxed);
+                        #[cfg(feature = "metrics")]
+                        self.metrics.allocated();
+                        next = new_block;
+                    }
+                    Err(linked) => {
+                        drop(unsafe { OwnedSegment::from_raw(new_block) });
+                        next = linked;
                     }
                 }
             }
+            // SAFETY: linked behind a segment reachable from `tail`, so kept alive by the pin.
+            segment = unsafe { self.segment(next) };
+            free += segment.len - segment.next_head.load(Acquire).min(segment.len);
         }
     }
 
+    /// Attempts to pop a value from the queue.
+    ///
+    /// Returns `Some(T)` if a value was available, or `None` if the queue is empty.
     pub fn pop(&self) -> Option<T> {
+        let (segment, idx) = self.begin_pop()?;
+        // SAFETY: `begin_pop` began taking the value at `idx`.
+        let data = unsafe { segment.finish_take(idx) };
+        segment.tail.store(idx + 1, Release);
+        self.popped.fetch_add(1, Relaxed);
+        Some(data)
+    }
+
+    /// Runs `f` on the oldest value where it sits in the queue, then drops it and returns
+    /// what `f` returned, or returns `None` if the queue is empty.
+    ///
+    /// Saves moving a large value out of its slot when it is only needed by reference. The
+    /// slot stays claimed while `f` runs and is released even if `f` panics. Like
+    /// [`pop`](Self::pop), this must only be called from the consumer.
+    pub fn pop_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.take_front(|mut taken| f(taken.value()))
+    }
+
+    /// Runs `f` on the oldest value where it sits in the queue and, if it returns
+    /// [`Ack::Ack`], drops it. On [`Ack::Nack`] the value stays at the front of the queue
+    /// for the next pop. Returns `None` if the queue is empty.
+    ///
+    /// If `f` panics the value is dropped, as with [`pop_with`](Self::pop_with). Like
+    /// [`pop`](Self::pop), this must only be called from the consumer.
+    pub fn pop_ack(&self, f: impl FnOnce(&mut T) -> Ack) -> Option<Ack> {
+        self.take_front(|mut taken| {
+            let ack = f(taken.value());
+            if ack == Ack::Nack {
+                taken.put_back();
+            }
+            ack
+        })
+    }
+
+    /// Pops the oldest value only if `pred` accepts it, returning `None` without touching the
+    /// queue if it is empty or `pred` returns `false`.
+    ///
+    /// A rejected value stays at the front for the next pop. If `pred` panics the value is
+    /// dropped, as with [`pop_ack`](Self::pop_ack). Like [`pop`](Self::pop), this must only
+    /// be called from the consumer.
+    pub fn try_pop_if(&self, pred: impl FnOnce(&T) -> bool) -> Option<T> {
+        self.take_front(|mut taken| {
+            if pred(taken.value()) {
+                Some(taken.into_inner())
+            } else {
+                taken.put_back();
+                None
+            }
+        })?
+    }
+
+    /// Begins taking the oldest value and hands it to `f`, which drops it unless it moves
+    /// it out or puts it back untouched in its slot.
+    fn take_front<R>(&self, f: impl FnOnce(Taken<'_, T, SEG, S>) -> R) -> Option<R> {
+        let (segment, idx) = self.begin_pop()?;
+        // `begin_pop` began taking the value at `idx`; `Taken` finishes it.
+        Some(f(Taken {
+            queue: self,
+            segment,
+            idx,
+        }))
+    }
+
+    /// Finds the oldest value and begins taking it, returning its segment and index.
+    ///
+    /// Drained segments on the way are unlinked and retired.
+    fn begin_pop(&self) -> Option<(SegmentRef<'_, T, SEG>, usize)> {
         loop {
             let head = self.head.load(Acquire);
-            println!("{:p}",head);
-            let segment = unsafe { &*head };
-            match Self::segment_pop(segment){
-                Some(data) => return Some(data),
-                None=>{
-                    if self.tail.load(Acquire) != head {
-                        fence(Acquire);
-                        let next = segment.next.get();
-                        self.head.store(next, Release);
-                        let _dropping = unsafe { Box::from_raw(head) };
-                        continue;
-                    }
-                    break None;
+            let segment = unsafe { self.segment(head) };
+            if let Some(idx) = Self::segment_begin_pop(segment) {
+                return Some((segment, idx));
+            }
+            // Only move on once every slot of this segment has been consumed and a producer
+            // has linked its successor.
+            if segment.tail.load(Relaxed) != segment.len {
+                return None;
+            }
+            let next = segment.next.load(Acquire);
+            if next.is_null() {
+                return None;
+            }
+            // The producer that linked `next` may not have moved `tail` yet. It has to be
+            // past this segment before retiring it, so that producers pinning from now on
+            // cannot load it.
+            self.advance_tail(head, next);
+            self.head.store(next, Release);
+            self.segments.fetch_sub(1, Relaxed);
+            let retired = (head != Self::INLINE).then(|| {
+                #[cfg(feature = "metrics")]
+                self.metrics.reclaimed();
+                unsafe { OwnedSegment::from_raw(head) }
+            });
+            // SAFETY: only the consumer retires, and `tail` has moved past this segment.
+            unsafe { self.reclaimer.retire(retired, |segment| self.recycle(segment)) };
+        }
+    }
+
+    /// Takes the recycled segment if there is one, or allocates one of `len` slots.
+    fn take_segment(&self, len: usize) -> *mut Segment<T, SEG> {
+        let recycled = self.recycled.swap(ptr::null_mut(), Acquire);
+        if !recycled.is_null() {
+            return recycled;
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.allocated();
+        OwnedSegment::new(len).into_raw()
+    }
+
+    /// Empties a retired segment no producer can reach any more, `None` for the inline one,
+    /// and keeps it for producers to link again.
+    ///
+    /// Segments shorter than `SEG`, left over from a [`growing`](Self::growing) queue's
+    /// start, are freed instead.
+    fn recycle(&self, segment: Option<OwnedSegment<T, SEG>>) {
+        let segment = match segment {
+            Some(segment) if segment.segment().len < SEG => return,
+            Some(segment) => segment.into_raw(),
+            None => Self::INLINE,
+        };
+        // SAFETY: retired by the consumer, which is the only one recycling.
+        unsafe { self.segment(segment) }.reset();
+        self.keep_recycled(segment);
+    }
+
+    /// Keeps an empty, unlinked segment in `recycled`, unless a heap one is already there; the
+    /// inline segment takes the place of one, since it costs nothing to keep. A segment left
+    /// over is freed.
+    fn keep_recycled(&self, segment: *mut Segment<T, SEG>) {
+        let left_over = if segment == Self::INLINE {
+            self.recycled.swap(segment, AcqRel)
+        } else {
+            match self
+                .recycled
+                .compare_exchange(ptr::null_mut(), segment, Release, Relaxed)
+            {
+                Ok(_) => ptr::null_mut(),
+                Err(_) => segment,
+            }
+        };
+        // There is only one inline segment, so whatever was swapped out is a heap one.
+        if !left_over.is_null() {
+            // SAFETY: unlinked, and taken out of `recycled` or never put there.
+            drop(unsafe { OwnedSegment::from_raw(left_over) });
+        }
+    }
+
+    /// Stands for the inline segment wherever a segment pointer is stored, since its address
+    /// changes when the queue is moved. Segments are aligned, so none starts at an odd address.
+    const INLINE: *mut Segment<T, SEG> = ptr::without_provenance_mut(1);
+
+    /// Resolves a non-null segment pointer, [`INLINE`](Self::INLINE) included.
+    ///
+    /// # Safety
+    ///
+    /// A heap `segment` must not have been freed yet: the caller is the consumer, which is
+    /// the only one retiring segments, or a producer pinned before loading it.
+    #[inline]
+    unsafe fn segment(&self, segment: *mut Segment<T, SEG>) -> SegmentRef<'_, T, SEG> {
+        if segment == Self::INLINE {
+            // Only ever stored when `S` keeps a segment inline.
+            S::inline(&self.inline).expect("inline segment pointer without an inline one")
+        } else {
+            unsafe { SegmentRef::new(segment) }
+        }
+    }
+
+    /// Moves `tail` from `from` to its successor `to`, unless someone already has.
+    ///
+    /// `tail` only ever moves forward along the list, so a failed CAS means it is already at
+    /// `to` or beyond.
+    #[inline]
+    fn advance_tail(&self, from: *mut Segment<T, SEG>, to: *mut Segment<T, SEG>) {
+        let _ = self.tail.compare_exchange(from, to, SeqCst, Relaxed);
+    }
+
+    /// Returns the approximate number of values buffered in the queue.
+    ///
+    /// Producers and the consumer update the underlying counters independently of the
+    /// queue itself, so under concurrent use this is only an estimate; it never underflows.
+    pub fn len(&self) -> usize {
+        let popped = self.popped.load(Relaxed);
+        let pushed = self.pushed.load(Relaxed);
+        pushed.saturating_sub(popped)
+    }
+
+    /// Returns how many segments producers have allocated and the consumer has reclaimed.
+    ///
+    /// Useful to pick a segment size that keeps allocation churn low for a given message
+    /// rate.
+    #[cfg(feature = "metrics")]
+    pub fn segment_stats(&self) -> SegmentStats {
+        self.metrics.snapshot()
+    }
+
+    /// Returns `true` if the queue appears empty.
+    ///
+    /// Built on the same counters as [`len`](Self::len), so it is equally approximate.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns clones of the buffered values in FIFO order, leaving the queue untouched.
+    ///
+    /// Meant for assertions in tests and for diagnostics. Like [`pop`](Self::pop), this must
+    /// only be called from the single consumer, which is also the only one freeing segments,
+    /// so nothing can be taken out or freed while it is being cloned. Values still being
+    /// written by their producers, and any behind them, are left out.
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::with_capacity(self.len());
+        let mut segment = unsafe { self.segment(self.head.load(Acquire)) };
+        loop {
+            let head = segment.next_head.load(Acquire);
+            for idx in segment.tail.load(Relaxed)..head {
+                // SAFETY: the caller is the consumer and doesn't pop until this returns.
+                match unsafe { segment.peek(idx) } {
+                    Some(data) => values.push(data.clone()),
+                    None => return values,
                 }
             }
+            let next = segment.next.load(Acquire);
+            if head != segment.len || next.is_null() {
+                return values;
+            }
+            segment = unsafe { self.segment(next) };
         }
     }
 
-    fn segment_push(segment: &Segment<T>, data: T) -> Result<(), T> {
+    /// Pops every value currently in the queue into a [`QueueSnapshot`], so the queue can be
+    /// rebuilt with [`from_snapshot`](Self::from_snapshot).
+    ///
+    /// The snapshot records no capacity. Must only be called from the single consumer.
+    pub fn drain(&self) -> QueueSnapshot<T> {
+        let mut items = Vec::with_capacity(self.len());
+        while let Some(data) = self.pop() {
+            items.push(data);
+        }
+        QueueSnapshot {
+            capacity: None,
+            items,
+        }
+    }
+
+    /// Pops and drops every value currently in the queue.
+    ///
+    /// Drained segments are freed as usual, but the one the consumer ends up on is kept, so
+    /// the queue stays ready for further pushes. Must only be called from the single consumer.
+    pub fn clear(&self) {
+        while let Some(data) = self.pop() {
+            drop(data);
+        }
+    }
+
+    fn segment_push(segment: SegmentRef<'_, T, SEG>, data: T) -> Result<(), T> {
         let backoff = LocalBackoff::new();
         loop {
             let curr_head = segment.next_head.load(Acquire);
-            let next_unbound = curr_head + 1;
-            // bounding within range without mod for performance
-            let is_bound =
-                unsafe { transmute::<isize, usize>(-((next_unbound < SEGMENT_SIZE) as isize)) };
-            let next_head = next_unbound & is_bound;
-            if segment.tail.load(Acquire) != next_head {
-                match segment
-                    .next_head
-                    .compare_exchange(curr_head, next_head, AcqRel, Relaxed)
-                {
-                    Ok(_) => {
-                        // shodnt pannic if so then there is error in logic
-                        println!("push:{},{}",curr_head,next_head);
-                        segment.set(curr_head, data).unwrap();
-                        return Ok(());
+            if curr_head == segment.len {
+                return Err(data);
+            }
+            // A failed CAS only tells us another producer won the index; the value it read
+            // is discarded and the loop reloads `next_head` with `Acquire`, so a stronger
+            // failure ordering would order nothing. It cannot hand out a stale segment either:
+            // `segment` came from the `SeqCst` load of `tail` under the reclaimer pin, which
+            // keeps it from being recycled, so a won index always names a slot that is still
+            // `READY`. `Slot::set` re-checks that with its own `AcqRel` CAS regardless.
+            match segment
+                .next_head
+                .compare_exchange(curr_head, curr_head + 1, AcqRel, Relaxed)
+            {
+                Ok(_) => {
+                    // The index is ours alone, so the slot must still be `READY`.
+                    if segment.set(curr_head, data).is_err() {
+                        unreachable!("a claimed slot is always ready");
                     }
-                    Err(_) => backoff.wait(),
+                    return Ok(());
                 }
-            } else {
-                return Err(data);
+                Err(_) => backoff.wait(),
             }
         }
     }
-    fn segment_pop(segment: &Segment<T>) -> Option<T> {
+
+    fn segment_begin_pop(segment: SegmentRef<'_, T, SEG>) -> Option<usize> {
         let head = segment.next_head.load(Acquire);
-        let tail = segment.tail.load(Acquire);
-        if head != tail {
-            let ptr = segment.buff.as_ptr();
-            let slot = unsafe { &*ptr.add(tail) };
-            // bounding within range without mod for performance
-            let is_bound = unsafe { transmute::<isize, usize>(-((tail + 1 < SEGMENT_SIZE) as isize)) };
-            let next_tail = (tail + 1) & is_bound;
-            segment.tail.store(next_tail, Release);
-            println!("{},{}",head,tail);
-            // shodnt pannic if so then there is error in logic
-            return Some(slot.unset().unwrap());
-        }
-        return None;
+        let tail = segment.tail.load(Relaxed);
+        // A producer may have claimed this index without having started to write it yet, in
+        // which case we report the queue as empty for now. One that is mid-write is waited
+        // for instead.
+        (head != tail && segment.begin_take(tail)).then_some(tail)
+    }
+}
+
+/// The value [`RawMpsc::pop_with`] is working on, released once it is done, or unwinding.
+struct Taken<'a, T, const SEG: usize, S: SegmentStorage> {
+    queue: &'a RawMpsc<T, SEG, S>,
+    segment: SegmentRef<'a, T, SEG>,
+    idx: usize,
+}
+
+impl<T, const SEG: usize, S: SegmentStorage> Taken<'_, T, SEG, S> {
+    /// The value, where it sits in its slot.
+    fn value(&mut self) -> &mut T {
+        // SAFETY: the value at `idx` was begun taking, and `self` is the only handle on it.
+        unsafe { &mut *self.segment.taken_ptr(self.idx) }
+    }
+
+    /// Moves the value out and pops it.
+    fn into_inner(self) -> T {
+        let (queue, segment, idx) = (self.queue, self.segment, self.idx);
+        core::mem::forget(self);
+        // SAFETY: as in `value`, and `self` no longer drops it.
+        let data = unsafe { segment.finish_take(idx) };
+        segment.tail.store(idx + 1, Release);
+        queue.popped.fetch_add(1, Relaxed);
+        data
+    }
+
+    /// Leaves the value untouched in its slot, still at the front of the queue.
+    fn put_back(self) {
+        let (segment, idx) = (self.segment, self.idx);
+        core::mem::forget(self);
+        // SAFETY: as in `value`; the segment's `tail` stays on the value.
+        unsafe { segment.abort_take(idx) };
+    }
+}
+
+impl<T, const SEG: usize, S: SegmentStorage> Drop for Taken<'_, T, SEG, S> {
+    fn drop(&mut self) {
+        // SAFETY: the value at `idx` was begun taking and nothing else finishes it.
+        unsafe { self.segment.drop_taken(self.idx) };
+        self.segment.tail.store(self.idx + 1, Release);
+        self.queue.popped.fetch_add(1, Relaxed);
+    }
+}
+
+impl<T, const SEG: usize, S: SegmentStorage> Default for RawMpsc<T, SEG, S> {
+    /// Creates an empty queue with a single segment, like [`with_segment_size`](Self::with_segment_size).
+    fn default() -> Self {
+        Self::with_segment_size()
     }
 }
 
+impl<T, const SEG: usize, S: SegmentStorage> Queue<T> for RawMpsc<T, SEG, S> {
+    /// Pushes `data`; an unbounded queue always has room.
+    #[inline]
+    fn try_push(&self, data: T) -> Result<(), T> {
+        self.push(data);
+        Ok(())
+    }
+
+    #[inline]
+    fn try_pop(&self) -> Option<T> {
+        self.pop()
+    }
+}
 
+impl<T, const SEG: usize, S: SegmentStorage> fmt::Debug for RawMpsc<T, SEG, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawMpsc")
+            .field("len", &self.len())
+            .field("segments", &self.segments.load(Relaxed))
+            .field("segment_size", &SEG)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, const SEG: usize, S: SegmentStorage> FromIterator<T> for RawMpsc<T, SEG, S> {
+    /// Builds a queue holding the iterator's values in order.
+    ///
+    /// Enough segments for the iterator's lower size bound are linked up front, so an exact
+    /// size hint fills the queue without allocating segment by segment; values beyond the
+    /// hint grow the queue as `push` would.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let queue = Self::with_linked_segments(SEG, iter.size_hint().0.div_ceil(SEG));
+        for data in iter {
+            queue.push(data);
+        }
+        queue
+    }
+}
+
+impl<T, const SEG: usize, S: SegmentStorage> Extend<T> for RawMpsc<T, SEG, S> {
+    /// Pushes every value from the iterator, in order.
+    ///
+    /// Like [`from_iter`](Self::from_iter), segments for the iterator's lower size bound are
+    /// [`reserve`](RawMpsc::reserve)d before the first value is written.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for data in iter {
+            self.push(data);
+        }
+    }
+}
+
+// SAFETY: values move from the producers' threads to the consumer's, so both need
+// `T: Send`. Segments are shared through atomic pointers, and the reclaimer's retired list is
+// only touched by the single consumer, as `pop` already requires.
+unsafe impl<T: Send, const SEG: usize, S: SegmentStorage> Send for RawMpsc<T, SEG, S> {}
+unsafe impl<T: Send, const SEG: usize, S: SegmentStorage> Sync for RawMpsc<T, SEG, S> {}
+
+impl<T, const SEG: usize, S: SegmentStorage> Drop for RawMpsc<T, SEG, S> {
+    /// Drops the queue, every value still buffered in it and all segments linked from `head`.
+    fn drop(&mut self) {
+        // `&mut self` already orders this after every producer and the consumer.
+        let mut curr = self.head.load(Relaxed);
+        while !curr.is_null() {
+            if curr == Self::INLINE {
+                // The inline segment drops its own values with the queue.
+                curr = unsafe { self.segment(curr) }.next.load(Relaxed);
+                continue;
+            }
+            // `Segment::drop` takes care of the values still buffered in it.
+            let segment = unsafe { OwnedSegment::from_raw(curr) };
+            curr = segment.segment().next.load(Relaxed);
+        }
+        let recycled = self.recycled.load(Relaxed);
+        if !recycled.is_null() && recycled != Self::INLINE {
+            drop(unsafe { OwnedSegment::from_raw(recycled) });
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::RawMpsc;
+    use crate::mpsc::unbounded_mpsc::segment_arr::SEGMENT_SIZE;
+    use crate::mpsc::unbounded_mpsc::storage::InlineSegment;
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::{Arc, Barrier};
     use std::thread;
-    use std::collections::BTreeSet;
     use std::time::Duration;
 
     // Basic test: push then pop single element in one thread
@@ -171,7 +754,8 @@ mod tests {
     }
 
     // Concurrent: multiple producers, single consumer, basic check for all messages
-    // #[test]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
     fn test_multi_producer_single_consumer_basic() {
         const PRODUCERS: usize = 4;
         const MSGS_PER_PRODUCER: usize = 1000;
@@ -208,13 +792,19 @@ mod tests {
         // Check all expected messages are received
         for pid in 0..PRODUCERS {
             for val in 0..MSGS_PER_PRODUCER {
-                assert!(seen.contains(&(pid, val)), "Missing message ({}, {})", pid, val);
+                assert!(
+                    seen.contains(&(pid, val)),
+                    "Missing message ({}, {})",
+                    pid,
+                    val
+                );
             }
         }
     }
 
     // Stress test: larger number of producers and messages
     #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
     fn test_stress_multi_producer_single_consumer() {
         const PRODUCERS: usize = 8;
         const MSGS_PER_PRODUCER: usize = 10_000;
@@ -261,6 +851,7 @@ mod tests {
 
     // Test concurrent push with slight delays to simulate contention
     #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
     fn test_multi_producer_with_delay() {
         const PRODUCERS: usize = 4;
         const MSGS_PER_PRODUCER: usize = 1000;
@@ -298,6 +889,240 @@ mod tests {
         assert_eq!(seen.len(), TOTAL_MSGS);
     }
 
+    // `len` tracks pushes and pops, including across segment boundaries
+    #[test]
+    fn test_len_and_is_empty() {
+        let q = RawMpsc::new();
+        assert!(q.is_empty());
+
+        for i in 0..SEGMENT_SIZE * 2 {
+            q.push(i);
+        }
+        assert_eq!(q.len(), SEGMENT_SIZE * 2);
+
+        for _ in 0..SEGMENT_SIZE + 3 {
+            q.pop().unwrap();
+        }
+        assert_eq!(q.len(), SEGMENT_SIZE - 3);
+        assert!(!q.is_empty());
+
+        while q.pop().is_some() {}
+        assert_eq!(q.len(), 0);
+        assert!(q.is_empty());
+    }
+
+    // Tiny segments force a new allocation every few pushes
+    #[test]
+    fn test_small_segments_preserve_order() {
+        let q = RawMpsc::<u32, 4>::with_segment_size();
+        for i in 0..1000 {
+            q.push(i);
+            if i % 3 == 0 {
+                assert_eq!(q.pop(), Some(i / 3));
+            }
+        }
+        let mut expected = 334;
+        while let Some(value) = q.pop() {
+            assert_eq!(value, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 1000);
+    }
+
+    #[test]
+    fn test_snapshot_leaves_the_queue_untouched() {
+        let q = RawMpsc::<String, 2>::with_segment_size();
+        assert!(q.snapshot().is_empty());
+        for i in 0..5 {
+            q.push(i.to_string());
+        }
+        q.pop();
+        let before = q.snapshot();
+        assert_eq!(before, ["1", "2", "3", "4"]);
+        assert_eq!(q.len(), 4);
+        assert_eq!(q.snapshot(), before);
+        for expected in before {
+            assert_eq!(q.pop(), Some(expected));
+        }
+        assert!(q.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_pop_with_across_segments() {
+        let q = RawMpsc::<Vec<usize>, 2>::with_segment_size();
+        for i in 0..5 {
+            q.push(vec![i]);
+        }
+        for i in 0..5 {
+            assert_eq!(q.pop_with(|v| v.pop()), Some(Some(i)));
+            assert_eq!(q.len(), 4 - i);
+        }
+        assert_eq!(q.pop_with(|v| v.len()), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_pop_with_releases_the_slot_when_f_panics() {
+        let q = RawMpsc::<Vec<u8>, 2>::with_segment_size();
+        q.push(vec![1]);
+        q.push(vec![2]);
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            q.pop_with(|_| panic!("consumer failed"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(q.len(), 1);
+        assert_eq!(q.pop(), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_try_pop_if_leaves_rejected_value_at_front() {
+        let q = RawMpsc::<u32, 2>::with_segment_size();
+        assert_eq!(q.try_pop_if(|_| true), None);
+        for i in 1..=3 {
+            q.push(i);
+        }
+        assert_eq!(q.try_pop_if(|v| *v > 1), None);
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.try_pop_if(|v| *v == 1), Some(1));
+        // Across the segment boundary
+        assert_eq!(q.try_pop_if(|v| *v == 2), Some(2));
+        assert_eq!(q.try_pop_if(|v| *v == 2), None);
+        assert_eq!(q.pop(), Some(3));
+    }
+
+    // Payloads don't need to implement `Debug`
+    #[test]
+    fn test_non_debug_payload() {
+        let ran = std::rc::Rc::new(std::cell::Cell::new(false));
+        let q: RawMpsc<Box<dyn FnOnce()>> = RawMpsc::new();
+        {
+            let ran = std::rc::Rc::clone(&ran);
+            q.push(Box::new(move || ran.set(true)));
+        }
+        (q.pop().unwrap())();
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn test_debug_shows_shape_not_values() {
+        struct Opaque;
+
+        let q = RawMpsc::<Opaque, 2>::with_segment_size();
+        assert_eq!(
+            format!("{q:?}"),
+            "RawMpsc { len: 0, segments: 1, segment_size: 2, .. }"
+        );
+        for _ in 0..5 {
+            q.push(Opaque);
+        }
+        assert_eq!(
+            format!("{q:?}"),
+            "RawMpsc { len: 5, segments: 3, segment_size: 2, .. }"
+        );
+        // A drained segment is only unlinked once the consumer looks past it
+        for _ in 0..5 {
+            q.pop();
+        }
+        assert_eq!(
+            format!("{q:?}"),
+            "RawMpsc { len: 0, segments: 1, segment_size: 2, .. }"
+        );
+    }
+
+    // Tiny segments make producers race the consumer across a segment boundary every few
+    // pushes, which is where a lagging producer could touch a segment that was just drained
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_producers_lagging_across_segment_boundaries() {
+        const PRODUCERS: usize = 4;
+        const MSGS_PER_PRODUCER: usize = 5_000;
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let q = Arc::new(RawMpsc::<_, 2>::with_segment_size());
+        let handles: Vec<_> = (0..PRODUCERS)
+            .map(|_| {
+                let q = Arc::clone(&q);
+                let dropped = Arc::clone(&dropped);
+                thread::spawn(move || {
+                    for i in 0..MSGS_PER_PRODUCER {
+                        q.push(ArcDropCounter(Arc::clone(&dropped)));
+                        if i % 64 == 0 {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut received = 0;
+        while received < PRODUCERS * MSGS_PER_PRODUCER {
+            match q.pop() {
+                Some(value) => {
+                    drop(value);
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert!(q.pop().is_none());
+        assert_eq!(
+            dropped.load(Ordering::Relaxed),
+            PRODUCERS * MSGS_PER_PRODUCER
+        );
+    }
+
+    // One-slot segments make nearly every push append a segment, so producers constantly
+    // race to link `next` and to advance `tail` for each other
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns threads")]
+    fn test_many_producers_appending_tiny_segments() {
+        const PRODUCERS: usize = 8;
+        const MSGS_PER_PRODUCER: usize = 2_000;
+
+        let q = Arc::new(RawMpsc::<_, 1>::with_segment_size());
+        let barrier = Arc::new(Barrier::new(PRODUCERS));
+        let handles: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let q = Arc::clone(&q);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    for i in 0..MSGS_PER_PRODUCER {
+                        q.push((p, i));
+                    }
+                })
+            })
+            .collect();
+
+        let mut next_expected = [0; PRODUCERS];
+        let mut received = 0;
+        while received < PRODUCERS * MSGS_PER_PRODUCER {
+            match q.pop() {
+                Some((p, i)) => {
+                    assert_eq!(i, next_expected[p]);
+                    next_expected[p] += 1;
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    struct ArcDropCounter(Arc<AtomicUsize>);
+
+    impl Drop for ArcDropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     // Optional: test with custom struct instead of tuple
     #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
     struct Message {
@@ -309,10 +1134,295 @@ mod tests {
     fn test_custom_struct_message() {
         let q = RawMpsc::new();
 
-        let msg = Message { producer_id: 1, value: 42 };
+        let msg = Message {
+            producer_id: 1,
+            value: 42,
+        };
         q.push(msg);
 
         let popped = q.pop().unwrap();
-        assert_eq!(popped, Message { producer_id: 1, value: 42 });
+        assert_eq!(
+            popped,
+            Message {
+                producer_id: 1,
+                value: 42
+            }
+        );
+    }
+
+    // `clear` drops everything buffered exactly once and leaves the queue usable
+    #[test]
+    fn test_clear_drops_each_value_once() {
+        const ITEMS: usize = 2 * SEGMENT_SIZE + 5;
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let q = RawMpsc::new();
+        for _ in 0..ITEMS {
+            q.push(ArcDropCounter(Arc::clone(&dropped)));
+        }
+
+        q.clear();
+        assert_eq!(dropped.load(Ordering::Relaxed), ITEMS);
+        assert!(q.is_empty());
+        assert!(q.pop().is_none());
+
+        q.push(ArcDropCounter(Arc::clone(&dropped)));
+        assert!(q.pop().is_some());
+        assert_eq!(dropped.load(Ordering::Relaxed), ITEMS + 1);
+    }
+
+    // Dropping the queue must free every segment and every value still buffered in them
+    #[test]
+    fn test_drop_releases_remaining_items() {
+        const ITEMS: usize = 3 * SEGMENT_SIZE + 7;
+        let dropped = Arc::new(AtomicUsize::new(0));
+        {
+            let q = RawMpsc::new();
+            for _ in 0..ITEMS {
+                q.push(ArcDropCounter(Arc::clone(&dropped)));
+            }
+            for _ in 0..SEGMENT_SIZE + 1 {
+                drop(q.pop());
+            }
+            assert_eq!(dropped.load(Ordering::Relaxed), SEGMENT_SIZE + 1);
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), ITEMS);
+    }
+
+    // Values left in the inline segment, and in heap segments behind it, are dropped once
+    #[test]
+    fn test_inline_segment_drops_remaining_values() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        {
+            let q = RawMpsc::<_, 4, InlineSegment>::default();
+            for _ in 0..6 {
+                q.push(ArcDropCounter(Arc::clone(&dropped)));
+            }
+            drop(q.pop());
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 6);
+
+        let q: RawMpsc<_, 4, InlineSegment> = (0..10).collect();
+        assert_eq!(
+            format!("{q:?}"),
+            "RawMpsc { len: 10, segments: 3, segment_size: 4, .. }"
+        );
+    }
+
+    // A drained segment is emptied and linked again where a new one would be allocated, the
+    // inline one like any other
+    #[test]
+    fn test_drained_segments_are_linked_again() {
+        let q = RawMpsc::<usize, 4>::with_segment_size();
+        let first = q.head.load(Ordering::Relaxed);
+        for i in 0..5 {
+            q.push(i);
+        }
+        for i in 0..5 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.recycled.load(Ordering::Relaxed), first);
+        for i in 5..9 {
+            q.push(i);
+        }
+        assert!(q.recycled.load(Ordering::Relaxed).is_null());
+        assert_eq!(q.tail.load(Ordering::Relaxed), first);
+        assert_eq!(q.segments.load(Ordering::Relaxed), 2);
+
+        let mut q = RawMpsc::<usize, 4, InlineSegment>::with_segment_size();
+        for i in 0..100 {
+            q.push(i);
+            assert_eq!(q.pop(), Some(i));
+        }
+        q.extend(100..110);
+        let popped: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(popped, (100..110).collect::<Vec<_>>());
+    }
+
+    // A growing queue doubles each new segment up to SEG, and keeps FIFO order across them
+    #[test]
+    fn test_growing_segments_double_up_to_the_cap() {
+        let q = RawMpsc::<usize, 64>::growing();
+        for i in 0..500 {
+            q.push(i);
+        }
+
+        let mut lens = Vec::new();
+        let mut segment = unsafe { q.segment(q.head.load(Ordering::Relaxed)) };
+        loop {
+            lens.push(segment.len);
+            let next = segment.next.load(Ordering::Relaxed);
+            if next.is_null() {
+                break;
+            }
+            segment = unsafe { q.segment(next) };
+        }
+        // 4 + 8 + 16 + 32 + 64 * 7 slots hold the 500 values
+        assert_eq!(lens, [4, 8, 16, 32, 64, 64, 64, 64, 64, 64, 64]);
+
+        for i in 0..500 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+        assert_eq!(RawMpsc::<u8, 2>::growing().pop(), None);
+    }
+
+    #[test]
+    fn test_default_is_empty_and_usable() {
+        #[derive(Default)]
+        struct Inbox {
+            queue: RawMpsc<Vec<u8>, 4>,
+        }
+
+        let inbox = Inbox::default();
+        assert!(inbox.queue.is_empty());
+        inbox.queue.push(vec![1]);
+        assert_eq!(inbox.queue.pop(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_from_iter_and_extend_preserve_order() {
+        let mut q: RawMpsc<usize, 4> = (0..10).collect();
+        assert_eq!(q.len(), 10);
+        // No size hint at all still grows the queue as needed
+        q.extend((10..20).filter(|_| true));
+
+        let popped: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(popped, (0..20).collect::<Vec<_>>());
+
+        let empty: RawMpsc<usize> = core::iter::empty().collect();
+        assert_eq!(empty.pop(), None);
+    }
+
+    #[test]
+    fn test_extend_reserves_segments_ahead() {
+        let mut q = RawMpsc::<usize, 64>::with_segment_size();
+        q.push(0);
+        q.extend(1..10_000);
+        // 10,000 values need 157 segments of 64; all but the first were linked by `reserve`
+        assert_eq!(q.segments.load(Ordering::Relaxed), 157);
+        let popped: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(popped, (0..10_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_reserve_counts_room_already_linked() {
+        let q = RawMpsc::<u32, 4>::with_segment_size();
+        q.push(0);
+        // Three free slots in the first segment, so five more need two segments
+        q.reserve(8);
+        assert_eq!(q.segments.load(Ordering::Relaxed), 3);
+        q.reserve(11);
+        assert_eq!(q.segments.load(Ordering::Relaxed), 3);
+        q.reserve(12);
+        assert_eq!(q.segments.load(Ordering::Relaxed), 4);
+
+        let mut batch: Vec<_> = (1..15).collect();
+        assert_eq!(q.push_bulk(&mut batch), 14);
+        assert!(batch.is_empty());
+        assert_eq!(q.segments.load(Ordering::Relaxed), 4);
+        let popped: Vec<_> = core::iter::from_fn(|| q.pop()).collect();
+        assert_eq!(popped, (0..15).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_segment_stats_track_allocation_and_reclamation() {
+        use crate::mpsc::stats::SegmentStats;
+
+        let q = RawMpsc::<u32, 4>::with_segment_size();
+        for i in 0..10 {
+            q.push(i);
+        }
+        // 10 values span three 4-slot segments; the first came with the queue
+        assert_eq!(
+            q.segment_stats(),
+            SegmentStats {
+                segments_allocated: 2,
+                segments_reclaimed: 0,
+            }
+        );
+        for _ in 0..10 {
+            q.pop();
+        }
+        assert_eq!(q.segment_stats().segments_reclaimed, 2);
+
+        // Reserved segments are not counted as allocated when producers move onto them
+        let reserved = RawMpsc::<u32, 4>::with_linked_segments(4, 3);
+        for i in 0..12 {
+            reserved.push(i);
+        }
+        assert_eq!(reserved.segment_stats().segments_allocated, 0);
+    }
+}
+
+/// Model-checked tests, run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+#[cfg(all(loom, test))]
+mod loom_tests {
+    use super::RawMpsc;
+    use crate::mpsc::unbounded_mpsc::InlineSegment;
+    use loom::model::Builder;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn loom_producers_racing_across_a_segment_boundary() {
+        // With one-slot segments the producer losing the first slot either fails its
+        // `segment_push` CAS or finds the segment full, and has to link a successor while the
+        // consumer may already be retiring the drained one.
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let q = Arc::new(RawMpsc::<usize, 1>::with_segment_size());
+            let producers: Vec<_> = (0..2)
+                .map(|p| {
+                    let q = Arc::clone(&q);
+                    thread::spawn(move || q.push(p))
+                })
+                .collect();
+
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                match q.pop() {
+                    Some(data) => received.push(data),
+                    None => thread::yield_now(),
+                }
+            }
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            assert_eq!(q.pop(), None);
+            received.sort_unstable();
+            assert_eq!(received, [0, 1]);
+        });
+    }
+
+    #[test]
+    fn loom_producer_relinks_the_recycled_inline_segment() {
+        // With one-slot segments the third push may find the inline segment recycled and link
+        // it behind the heap one, while the consumer pops alongside.
+        let mut builder = Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let q = Arc::new(RawMpsc::<usize, 1, InlineSegment>::with_segment_size());
+            let producer = {
+                let q = Arc::clone(&q);
+                thread::spawn(move || {
+                    for i in 0..3 {
+                        q.push(i);
+                    }
+                })
+            };
+
+            let mut received = Vec::new();
+            while received.len() < 3 {
+                match q.pop() {
+                    Some(data) => received.push(data),
+                    None => thread::yield_now(),
+                }
+            }
+            producer.join().unwrap();
+            assert_eq!(q.pop(), None);
+            assert_eq!(received, [0, 1, 2]);
+        });
     }
 }
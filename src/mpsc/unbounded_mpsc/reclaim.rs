@@ -0,0 +1,238 @@
+//! Deferred freeing of segments the consumer has unlinked.
+//!
+//! A producer loads `tail`, and may then be preempted before it touches the segment. If in
+//! the meantime other producers fill that segment and link a successor, and the consumer
+//! drains it, the consumer would free a segment the sleeping producer is about to read.
+//!
+//! [`Reclaimer`] closes that window with a two-generation epoch. Producers [`pin`] the current
+//! generation for the duration of a push by bumping its counter *before* loading `tail`, and
+//! pin again if the epoch moved on while they did, so the pin always lands in a generation
+//! the consumer still checks. The consumer [`retire`]s unlinked segments instead of freeing
+//! them; a batch of retired segments is freed only after the epoch has been advanced past it
+//! and every producer pinned in the old generation has left. A producer pinning later than that necessarily loads a `tail`
+//! that was moved past the batch before it was retired, so it can never reach one of them.
+//!
+//! [`pin`]: Reclaimer::pin
+//! [`retire`]: Reclaimer::retire
+
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::mem;
+use core::sync::atomic::Ordering::{Release, SeqCst};
+
+use crate::cache_padded::CachePadded;
+use crate::mpsc::sync::{AtomicUsize, fence};
+
+/// Frees values unlinked by the single consumer once no producer can still reach them.
+pub(crate) struct Reclaimer<P> {
+    /// Current generation; only its parity selects a counter in `active`.
+    epoch: AtomicUsize,
+    /// Number of producers pinned in each generation parity.
+    active: [CachePadded<AtomicUsize>; 2],
+    /// Retired values; only ever touched by the consumer.
+    retired: UnsafeCell<Retired<P>>,
+}
+
+/// The consumer's bookkeeping of values waiting to be freed.
+struct Retired<P> {
+    /// Values retired since the last epoch advance.
+    current: Vec<P>,
+    /// Values waiting for every producer pinned in `draining_epoch` to leave.
+    draining: Vec<P>,
+    /// The generation `draining` was retired in.
+    draining_epoch: usize,
+}
+
+/// Keeps the segments reachable at pin time alive until dropped.
+pub(crate) struct Guard<'a> {
+    active: &'a AtomicUsize,
+}
+
+impl<P> Reclaimer<P> {
+    pub(crate) fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            active: [
+                CachePadded::new(AtomicUsize::new(0)),
+                CachePadded::new(AtomicUsize::new(0)),
+            ],
+            retired: UnsafeCell::new(Retired {
+                current: Vec::new(),
+                draining: Vec::new(),
+                draining_epoch: 0,
+            }),
+        }
+    }
+
+    /// Pins the current generation for a producer.
+    ///
+    /// Shared pointers must only be loaded after this returns, with `SeqCst` ordering, so the
+    /// load is ordered after the pin.
+    #[inline]
+    pub(crate) fn pin(&self) -> Guard<'_> {
+        loop {
+            let epoch = self.epoch.load(SeqCst);
+            let active = &self.active[epoch & 1];
+            active.fetch_add(1, SeqCst);
+            // Pairs with the fence after the consumer's advance: either it sees this pin, or
+            // the re-load below sees the advance.
+            fence(SeqCst);
+            // If the consumer advanced the epoch in between, it may already have checked this
+            // counter for the generation read above, and the one after next shares its parity:
+            // a pin left here could go unseen while that generation is freed.
+            if self.epoch.load(SeqCst) == epoch {
+                return Guard { active };
+            }
+            active.fetch_sub(1, Release);
+        }
+    }
+
+    /// Hands an unlinked value over to be passed to `free` once no producer can reach it any
+    /// more, along with any earlier ones that got there meanwhile.
+    ///
+    /// Values still retired when the reclaimer is dropped are simply dropped.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the single consumer, and `value` must already be unreachable
+    /// from the shared pointers producers load after pinning.
+    pub(crate) unsafe fn retire(&self, value: P, free: impl FnMut(P)) {
+        let retired = unsafe { &mut *self.retired.get() };
+        retired.current.push(value);
+        self.collect(retired, free);
+    }
+
+    /// Frees the draining batch if its generation has emptied, and starts draining the
+    /// current batch if nothing else is.
+    fn collect(&self, retired: &mut Retired<P>, mut free: impl FnMut(P)) {
+        // Order the retirement (and the consumer's view of `tail` that justified it) before
+        // the counter loads below.
+        fence(SeqCst);
+        if !retired.draining.is_empty() {
+            if self.active[retired.draining_epoch & 1].load(SeqCst) != 0 {
+                return;
+            }
+            retired.draining.drain(..).for_each(&mut free);
+        }
+        if retired.current.is_empty() {
+            return;
+        }
+        mem::swap(&mut retired.current, &mut retired.draining);
+        retired.draining_epoch = self.epoch.fetch_add(1, SeqCst);
+        fence(SeqCst);
+        if self.active[retired.draining_epoch & 1].load(SeqCst) == 0 {
+            retired.draining.drain(..).for_each(free);
+        }
+    }
+}
+
+impl Drop for Guard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering::Relaxed;
+
+    struct DropFlag<'a>(&'a AtomicUsize);
+
+    impl Drop for DropFlag<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_retired_values_outlive_pinned_producers() {
+        let freed = AtomicUsize::new(0);
+        let reclaimer = Reclaimer::new();
+
+        let guard = reclaimer.pin();
+        unsafe { reclaimer.retire(DropFlag(&freed), drop) };
+        unsafe { reclaimer.retire(DropFlag(&freed), drop) };
+        assert_eq!(freed.load(Relaxed), 0);
+
+        // A producer pinning after the advance does not hold the first batch back
+        let late = reclaimer.pin();
+        drop(guard);
+        unsafe { reclaimer.retire(DropFlag(&freed), drop) };
+        assert_eq!(freed.load(Relaxed), 1);
+
+        drop(late);
+        let last = reclaimer.pin();
+        unsafe { reclaimer.retire(DropFlag(&freed), drop) };
+        assert_eq!(freed.load(Relaxed), 3);
+
+        // Whatever is still retired goes with the reclaimer
+        drop(last);
+        drop(reclaimer);
+        assert_eq!(freed.load(Relaxed), 4);
+    }
+}
+
+/// Model-checked tests, run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+#[cfg(all(loom, test))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::sync::atomic::AtomicBool;
+    use loom::thread;
+
+    /// Stands in for a segment: `tail` points at one of three, and each records being freed.
+    struct Queue {
+        reclaimer: Reclaimer<Segment>,
+        tail: AtomicUsize,
+    }
+
+    // Only the consumer thread retires.
+    unsafe impl Sync for Queue {}
+
+    struct Segment(Arc<[AtomicBool; 3]>, usize);
+
+    impl Drop for Segment {
+        fn drop(&mut self) {
+            self.0[self.1].store(true, SeqCst);
+        }
+    }
+
+    #[test]
+    fn loom_producer_pinned_across_two_retired_batches() {
+        // The consumer moves `tail` on and retires the segment behind it twice, advancing the
+        // epoch twice, while the producer may sit anywhere between reading the epoch, bumping
+        // its counter and loading `tail`.
+        loom::model(|| {
+            let freed = Arc::new([
+                AtomicBool::new(false),
+                AtomicBool::new(false),
+                AtomicBool::new(false),
+            ]);
+            let q = Arc::new(Queue {
+                reclaimer: Reclaimer::new(),
+                tail: AtomicUsize::new(0),
+            });
+
+            let producer = {
+                let (q, freed) = (Arc::clone(&q), Arc::clone(&freed));
+                thread::spawn(move || {
+                    let guard = q.reclaimer.pin();
+                    let tail = q.tail.load(SeqCst);
+                    assert!(
+                        !freed[tail].load(SeqCst),
+                        "segment {tail} freed while pinned"
+                    );
+                    drop(guard);
+                })
+            };
+
+            for segment in 0..2 {
+                q.tail.store(segment + 1, SeqCst);
+                unsafe { q.reclaimer.retire(Segment(Arc::clone(&freed), segment), drop) };
+            }
+            producer.join().unwrap();
+        });
+    }
+}
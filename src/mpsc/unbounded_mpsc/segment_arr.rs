@@ -1,90 +1,387 @@
 use crate::{
     cache_padded::CachePadded,
-    mpsc::slot::{READY, Slot},
+    mpsc::{
+        slot::Slot,
+        sync::{AtomicPtr, AtomicUsize},
+    },
 };
-use std::{alloc::dealloc, cell::Cell, ptr::null_mut, sync::atomic::Ordering::Release};
-use std::{
-    alloc::{Layout, alloc},
-    ptr::NonNull,
-    sync::atomic::AtomicUsize,
+#[cfg(all(loom, test))]
+use alloc::alloc::alloc;
+#[cfg(not(all(loom, test)))]
+use alloc::alloc::alloc_zeroed;
+use alloc::alloc::{Layout, dealloc, handle_alloc_error};
+use core::{
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    ptr::{NonNull, null_mut},
+    sync::atomic::Ordering::Relaxed,
 };
 
-pub(crate) const SEGMENT_SIZE: usize = 128;
+/// Default number of slots per segment.
+pub const SEGMENT_SIZE: usize = 128;
 
-pub struct Segment<T> {
+/// The bookkeeping of a segment, directly followed in memory by its `len` slots.
+///
+/// Segments are only ever reached through a [`SegmentRef`], whose pointer covers the slots
+/// as well as this header. `SEG` is the most slots a segment of the queue may have.
+#[repr(C)]
+pub struct Segment<T, const SEG: usize = SEGMENT_SIZE> {
     pub(crate) next_head: CachePadded<AtomicUsize>,
     pub(crate) tail: CachePadded<AtomicUsize>,
-    pub(crate) buff: NonNull<Slot<T>>,
-    pub(crate) next: Cell<*mut Segment<T>>
+    pub(crate) next: AtomicPtr<Segment<T, SEG>>,
+    /// Number of slots following the header, at most `SEG`.
+    pub(crate) len: usize,
+    /// Aligns the end of the header for the slots that follow it.
+    slots: [Slot<T>; 0],
 }
 
-impl<T> Segment<T> {
-    pub fn new() -> Self {
-        let layout = Self::layout();
-        let buff = NonNull::new(unsafe { alloc(layout) } as *mut _).unwrap();
-        let ptr: *mut Slot<T> = buff.as_ptr();
-        for idx in 0..SEGMENT_SIZE {
-            let slot = unsafe { &*ptr.add(idx) };
-            slot.state.store(READY, Release);
-        }
-        let next_head = CachePadded::new(AtomicUsize::new(0));
-        let tail = CachePadded::new(AtomicUsize::new(0));
-        let next = Cell::new(null_mut());
+impl<T, const SEG: usize> Segment<T, SEG> {
+    /// Layout of the most slots a segment may have.
+    ///
+    /// Evaluated when the queue is instantiated, so a segment too large to allocate is a
+    /// compile error rather than a panic at runtime.
+    const MAX_SLOTS: Layout = match Layout::array::<Slot<T>>(SEG) {
+        Ok(layout) => layout,
+        Err(_) => panic!("segment size overflows isize::MAX bytes"),
+    };
+
+    fn header(len: usize) -> Self {
+        const { assert!(SEG > 0, "a segment needs at least one slot") };
+        debug_assert!(0 < len && len <= SEG);
         Self {
-            next_head,
-            tail,
-            buff,
-            next
+            next_head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            next: AtomicPtr::new(null_mut()),
+            len,
+            slots: [],
         }
     }
 
-    const fn layout() -> Layout {
-        if let Ok(layout) = Layout::array::<Slot<T>>(SEGMENT_SIZE) {
-            layout
-        } else {
-            panic!("Invalid layout for Segment")
+    /// Layout of a heap segment holding `len` slots.
+    fn layout(len: usize) -> Layout {
+        let _ = Self::MAX_SLOTS;
+        let slots = Layout::array::<Slot<T>>(len).expect("checked against MAX_SLOTS");
+        let (layout, offset) = Layout::new::<Self>()
+            .extend(slots)
+            .expect("segment size overflows isize::MAX bytes");
+        // The header's size is a multiple of its alignment, which covers the slots'.
+        debug_assert_eq!(offset, mem::size_of::<Self>());
+        layout.pad_to_align()
+    }
+}
+
+/// A segment's header together with the pointer that reaches its slots.
+pub struct SegmentRef<'a, T, const SEG: usize> {
+    ptr: NonNull<Segment<T, SEG>>,
+    _marker: PhantomData<&'a Segment<T, SEG>>,
+}
+
+impl<T, const SEG: usize> Clone for SegmentRef<'_, T, SEG> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const SEG: usize> Copy for SegmentRef<'_, T, SEG> {}
+
+impl<T, const SEG: usize> Deref for SegmentRef<'_, T, SEG> {
+    type Target = Segment<T, SEG>;
+
+    #[inline]
+    fn deref(&self) -> &Segment<T, SEG> {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<'a, T, const SEG: usize> SegmentRef<'a, T, SEG> {
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and point to a live segment, header and slots alike, for `'a`.
+    #[inline]
+    pub unsafe fn new(ptr: *mut Segment<T, SEG>) -> Self {
+        Self {
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            _marker: PhantomData,
         }
     }
 
     #[inline]
-    pub fn set(&self, index: usize, data: T) -> Result<(), T> {
-        debug_assert!(index<SEGMENT_SIZE);
-        let ptr = self.buff.as_ptr();
-        let slot = unsafe { &*ptr.add(index) };
-        slot.set(data)
+    fn slot(self, index: usize) -> &'a Slot<T> {
+        debug_assert!(index < self.len);
+        // SAFETY: the slots start right after the header, and `ptr` covers them.
+        unsafe { &*self.ptr.as_ptr().add(1).cast::<Slot<T>>().add(index) }
+    }
+
+    #[inline]
+    pub fn set(self, index: usize, data: T) -> Result<(), T> {
+        self.slot(index).set(data)
+    }
+
+    #[inline]
+    pub fn unset(self, index: usize) -> Option<T> {
+        self.slot(index).unset_wait().ok()
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::peek`].
+    #[inline]
+    pub unsafe fn peek(self, index: usize) -> Option<&'a T> {
+        unsafe { self.slot(index).peek() }
+    }
+
+    /// Begins taking the value at `index` in place, waiting out a producer that is
+    /// mid-write; see [`Slot::begin_take_wait`].
+    #[inline]
+    pub fn begin_take(self, index: usize) -> bool {
+        self.slot(index).begin_take_wait()
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::taken_ptr`].
+    #[inline]
+    pub unsafe fn taken_ptr(self, index: usize) -> *mut T {
+        unsafe { self.slot(index).taken_ptr() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::finish_take`].
+    #[inline]
+    pub unsafe fn finish_take(self, index: usize) -> T {
+        unsafe { self.slot(index).finish_take() }
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::drop_taken`].
+    #[inline]
+    pub unsafe fn drop_taken(self, index: usize) {
+        unsafe { self.slot(index).drop_taken() };
+    }
+
+    /// # Safety
+    ///
+    /// See [`Slot::abort_take`].
+    #[inline]
+    pub unsafe fn abort_take(self, index: usize) {
+        unsafe { self.slot(index).abort_take() };
+    }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub unsafe fn set_unchecked(self, index: usize, data: T) {
+        unsafe { self.slot(index).unchecked_set(data) };
+    }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub unsafe fn unset_unchecked(self, index: usize) -> T {
+        unsafe { self.slot(index).unchecked_unset() }
+    }
+
+    /// Empties a drained segment and unlinks it from its successor, to be filled again.
+    ///
+    /// Only for the segment's owner, once every producer and the consumer are done with it.
+    /// Taking a value leaves its slot `READY`, so only the indices need resetting.
+    pub fn reset(self) {
+        debug_assert_eq!(self.tail.load(Relaxed), self.len, "segment not drained");
+        self.next_head.store(0, Relaxed);
+        self.tail.store(0, Relaxed);
+        self.next.store(null_mut(), Relaxed);
+    }
+
+    /// Drops every value still registered between `tail` and `next_head`.
+    ///
+    /// Only for the segment's owner, once every producer and the consumer are done with it.
+    fn drop_values(self) {
+        let head = self.next_head.load(Relaxed);
+        let tail = self.tail.load(Relaxed);
+        for idx in tail..head {
+            drop(self.unset(idx));
+        }
+    }
+}
+
+/// A segment allocated on the heap in a single block, freed along with the values still in
+/// it when dropped.
+pub struct OwnedSegment<T, const SEG: usize> {
+    ptr: NonNull<Segment<T, SEG>>,
+}
+
+impl<T, const SEG: usize> OwnedSegment<T, SEG> {
+    /// Allocates an empty segment of `len` slots, at most `SEG`.
+    pub fn new(len: usize) -> Self {
+        let layout = Segment::<T, SEG>::layout(len);
+        // An all-zero slot is an empty `READY` one, so zeroed memory only needs its header
+        // written. Loom's cells and atomics carry tracking state and are built one by one.
+        #[cfg(not(all(loom, test)))]
+        let ptr = unsafe { alloc_zeroed(layout) };
+        #[cfg(all(loom, test))]
+        let ptr = unsafe { alloc(layout) };
+        let ptr: NonNull<Segment<T, SEG>> = NonNull::new(ptr)
+            .unwrap_or_else(|| handle_alloc_error(layout))
+            .cast();
+        unsafe { ptr.write(Segment::header(len)) };
+        #[cfg(all(loom, test))]
+        for idx in 0..len {
+            unsafe {
+                ptr.as_ptr()
+                    .add(1)
+                    .cast::<Slot<T>>()
+                    .add(idx)
+                    .write(Slot::new())
+            };
+        }
+        Self { ptr }
     }
 
     #[inline]
-    pub fn unset(&self, index: usize) -> Option<T> {
-        debug_assert!(index<SEGMENT_SIZE);
-        let ptr = self.buff.as_ptr();
-        let slot = unsafe { &*ptr.add(index) };
-        slot.unset().ok()
+    pub fn segment(&self) -> SegmentRef<'_, T, SEG> {
+        // SAFETY: the block lives as long as `self`.
+        unsafe { SegmentRef::new(self.ptr.as_ptr()) }
     }
 
+    /// Gives up ownership, for the segment to be linked into the queue.
     #[inline]
-    pub unsafe fn set_unchecked(&self, index: usize, data: T) {
-        debug_assert!(index<SEGMENT_SIZE);
-        let ptr = self.buff.as_ptr();
-        let slot = unsafe { &*ptr.add(index) };
-        unsafe { slot.unchecked_set(data) };
+    pub fn into_raw(self) -> *mut Segment<T, SEG> {
+        let ptr = self.ptr.as_ptr();
+        mem::forget(self);
+        ptr
     }
 
+    /// # Safety
+    ///
+    /// `ptr` must come from [`into_raw`](Self::into_raw), and not be owned again already.
     #[inline]
-    pub unsafe fn unset_unchecked(&self, index: usize) -> T {
-        debug_assert!(index<SEGMENT_SIZE);
-        let ptr = self.buff.as_ptr();
-        let slot = unsafe { &*ptr.add(index) };
-        let data = unsafe { slot.unchecked_unset() };
-        data
+    pub unsafe fn from_raw(ptr: *mut Segment<T, SEG>) -> Self {
+        Self {
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+        }
+    }
+}
+
+impl<T, const SEG: usize> Drop for OwnedSegment<T, SEG> {
+    /// Drops every value still registered between `tail` and `next_head`, then frees the
+    /// block.
+    fn drop(&mut self) {
+        // `&mut self` already orders this after every producer and the consumer.
+        self.segment().drop_values();
+        let layout = Segment::<T, SEG>::layout(self.segment().len);
+        unsafe { dealloc(self.ptr.as_ptr().cast(), layout) };
     }
 }
 
+/// A segment of `SEG` slots laid out like a heap one, to be stored inline in a queue.
+#[repr(C)]
+pub struct EmbeddedSegment<T, const SEG: usize> {
+    header: Segment<T, SEG>,
+    slots: [Slot<T>; SEG],
+}
+
+impl<T, const SEG: usize> EmbeddedSegment<T, SEG> {
+    pub fn new() -> Self {
+        let _ = Segment::<T, SEG>::MAX_SLOTS;
+        Self {
+            header: Segment::header(SEG),
+            slots: core::array::from_fn(|_| Slot::new()),
+        }
+    }
+
+    #[inline]
+    pub fn segment(&self) -> SegmentRef<'_, T, SEG> {
+        // SAFETY: derived from a reference to the whole struct, whose slots directly follow
+        // the header as `repr(C)` lays them out.
+        unsafe { SegmentRef::new(NonNull::from(self).cast().as_ptr()) }
+    }
+}
 
-impl<T> Drop for Segment<T>{
+impl<T, const SEG: usize> Drop for EmbeddedSegment<T, SEG> {
+    /// Drops every value still registered between `tail` and `next_head`.
     fn drop(&mut self) {
-        let layout = Self::layout();
-        let ptr = self.buff.as_ptr();
-        unsafe { dealloc(ptr as _, layout) };
+        self.segment().drop_values();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering::Relaxed;
+
+    struct DropCounter<'a>(&'a AtomicUsize);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_drop_releases_live_values_once() {
+        let dropped = AtomicUsize::new(0);
+        let owned = OwnedSegment::<_, SEGMENT_SIZE>::new(SEGMENT_SIZE);
+        let segment = owned.segment();
+        for idx in 0..10 {
+            assert!(segment.set(idx, DropCounter(&dropped)).is_ok());
+        }
+        segment.next_head.store(10, Relaxed);
+
+        // Consume the first three, leaving seven live values behind
+        for idx in 0..3 {
+            drop(segment.unset(idx));
+        }
+        segment.tail.store(3, Relaxed);
+        assert_eq!(dropped.load(Relaxed), 3);
+
+        drop(owned);
+        assert_eq!(dropped.load(Relaxed), 10);
+    }
+
+    #[test]
+    fn test_zeroed_slots_start_ready() {
+        let owned = OwnedSegment::<u64, SEGMENT_SIZE>::new(SEGMENT_SIZE);
+        let segment = owned.segment();
+        // `READY` is 0
+        for idx in 0..SEGMENT_SIZE {
+            assert_eq!(segment.slot(idx).state.load(Relaxed), 0, "slot {idx}");
+        }
+        // Only a `READY` slot accepts a value
+        for idx in [0, 1, SEGMENT_SIZE / 2, SEGMENT_SIZE - 1] {
+            assert!(segment.set(idx, idx as u64).is_ok());
+            assert_eq!(segment.unset(idx), Some(idx as u64));
+        }
+    }
+
+    #[test]
+    fn test_small_segment_size() {
+        let owned = OwnedSegment::<u8, 2>::new(2);
+        let segment = owned.segment();
+        assert!(segment.set(0, 1).is_ok());
+        assert!(segment.set(1, 2).is_ok());
+        assert_eq!(segment.unset(1), Some(2));
+        assert_eq!(segment.unset(0), Some(1));
+    }
+
+    #[test]
+    fn test_drop_empty_segment() {
+        drop(OwnedSegment::<String, SEGMENT_SIZE>::new(SEGMENT_SIZE));
+        drop(EmbeddedSegment::<String, 4>::new());
+    }
+
+    #[test]
+    fn test_embedded_segment_matches_the_heap_layout() {
+        let dropped = AtomicUsize::new(0);
+        let embedded = EmbeddedSegment::<_, 3>::new();
+        let segment = embedded.segment();
+        assert_eq!(segment.len, 3);
+        for idx in 0..3 {
+            assert!(segment.set(idx, DropCounter(&dropped)).is_ok());
+        }
+        segment.next_head.store(3, Relaxed);
+        assert!(unsafe { segment.peek(2) }.is_some());
+        drop(embedded);
+        assert_eq!(dropped.load(Relaxed), 3);
+    }
+}
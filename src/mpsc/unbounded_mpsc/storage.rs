@@ -0,0 +1,85 @@
+//! Where the unbounded queue keeps its first segment.
+//!
+//! By default every segment of a [`RawMpsc`](super::RawMpsc) is allocated on the heap, the
+//! first one when the queue is created. With [`InlineSegment`] the first segment is stored in
+//! the queue itself instead, so creating a queue allocates nothing, and one that never
+//! buffers more than a segment's worth of values settles on taking turns between the inline
+//! segment and a single heap one. In exchange the queue is as large as a whole segment, which
+//! suits small messages and small segment sizes:
+//!
+//! ```
+//! use lock_free_mpsc::mpsc::unbounded_mpsc::{InlineSegment, RawMpsc};
+//!
+//! let q = RawMpsc::<u32, 16, InlineSegment>::with_segment_size();
+//! q.push(1);
+//! assert_eq!(q.pop(), Some(1));
+//! ```
+//!
+//! The consumer moves off the inline segment like off any other once it is drained, and
+//! producers link it again behind the tail in preference to any other drained segment. It is
+//! never freed on its own, only dropped with the queue.
+
+use super::segment_arr::{EmbeddedSegment, SegmentRef};
+
+pub(crate) mod sealed {
+    use super::super::segment_arr::SegmentRef;
+
+    /// The hooks [`RawMpsc`](super::super::RawMpsc) needs from a [`SegmentStorage`]
+    /// choice; not implementable outside this crate.
+    ///
+    /// [`SegmentStorage`]: super::SegmentStorage
+    pub trait Sealed {
+        /// What the queue holds in place of its first segment.
+        type Inline<T, const SEG: usize>;
+
+        fn new_inline<T, const SEG: usize>() -> Self::Inline<T, SEG>;
+
+        /// Returns the inline segment, if this storage keeps one.
+        fn inline<T, const SEG: usize>(
+            inline: &Self::Inline<T, SEG>,
+        ) -> Option<SegmentRef<'_, T, SEG>>;
+    }
+}
+
+/// Where an unbounded queue keeps its first segment: [`HeapSegment`] or [`InlineSegment`].
+pub trait SegmentStorage: sealed::Sealed {}
+
+/// Every segment is allocated on the heap. The default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapSegment;
+
+/// The first segment is stored inline in the queue; only overflow segments are allocated.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InlineSegment;
+
+impl sealed::Sealed for HeapSegment {
+    type Inline<T, const SEG: usize> = ();
+
+    #[inline]
+    fn new_inline<T, const SEG: usize>() -> Self::Inline<T, SEG> {}
+
+    #[inline]
+    fn inline<T, const SEG: usize>(_: &Self::Inline<T, SEG>) -> Option<SegmentRef<'_, T, SEG>> {
+        None
+    }
+}
+
+impl SegmentStorage for HeapSegment {}
+
+impl sealed::Sealed for InlineSegment {
+    type Inline<T, const SEG: usize> = EmbeddedSegment<T, SEG>;
+
+    #[inline]
+    fn new_inline<T, const SEG: usize>() -> Self::Inline<T, SEG> {
+        EmbeddedSegment::new()
+    }
+
+    #[inline]
+    fn inline<T, const SEG: usize>(
+        inline: &Self::Inline<T, SEG>,
+    ) -> Option<SegmentRef<'_, T, SEG>> {
+        Some(inline.segment())
+    }
+}
+
+impl SegmentStorage for InlineSegment {}
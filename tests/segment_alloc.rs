@@ -0,0 +1,126 @@
+//! Allocation counts of the unbounded queue's segments.
+//!
+//! Counting needs a `#[global_allocator]`, which would apply to every unit test of the
+//! library, so these live in a test binary of their own.
+
+#![cfg(feature = "alloc")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use lock_free_mpsc::mpsc::unbounded_mpsc::{InlineSegment, RawMpsc, SEGMENT_SIZE};
+
+/// Wraps the system allocator, counting allocations and live bytes per thread so that tests
+/// running in parallel do not see each other's.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        let _ = LIVE_BYTES.try_with(|n| n.set(n.get() + layout.size() as isize));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE_BYTES.try_with(|n| n.set(n.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+fn live_bytes() -> isize {
+    LIVE_BYTES.with(Cell::get)
+}
+
+// Reserved segments absorb a burst that would otherwise allocate on the push path
+#[test]
+fn test_reserved_segments_avoid_allocating_on_push() {
+    const ITEMS: usize = 4 * SEGMENT_SIZE;
+
+    let q = RawMpsc::new();
+    let before = allocations();
+    for i in 0..ITEMS {
+        q.push(i);
+    }
+    let unreserved = allocations() - before;
+
+    let reserved = RawMpsc::with_segments(4);
+    let before = allocations();
+    for i in 0..ITEMS {
+        reserved.push(i);
+    }
+    assert_eq!(allocations() - before, 0);
+    assert!(unreserved > 0);
+
+    // The reserved segments are drained in order, then the queue grows as usual
+    reserved.push(ITEMS);
+    for i in 0..=ITEMS {
+        assert_eq!(reserved.pop(), Some(i));
+    }
+    assert_eq!(reserved.pop(), None);
+}
+
+// Every reserved segment is freed, whether it was used, drained, or never reached
+#[test]
+fn test_drop_frees_reserved_segments() {
+    let before = live_bytes();
+    {
+        let q = RawMpsc::with_segments(4);
+        for i in 0..2 * SEGMENT_SIZE + 10 {
+            q.push(i);
+        }
+        for _ in 0..SEGMENT_SIZE + 5 {
+            q.pop();
+        }
+    }
+    assert_eq!(live_bytes(), before);
+    assert_eq!(RawMpsc::<u8>::with_segments(0).pop(), None);
+}
+
+// The inline segment takes the first SEG values without any allocation; only the overflow
+// goes to the heap, and is freed again like any other segment
+#[test]
+fn test_inline_segment_fills_before_allocating() {
+    let before = allocations();
+    let bytes = live_bytes();
+    {
+        let q = RawMpsc::<usize, 4, InlineSegment>::with_segment_size();
+        for i in 0..4 {
+            q.push(i);
+        }
+        assert_eq!(allocations(), before);
+
+        q.push(4);
+        assert_eq!(allocations(), before + 1);
+        for i in 0..5 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+
+        // Once both segments have been drained, and the reclaimer's lists have room, the
+        // queue takes turns between them without allocating again
+        for i in 5..12 {
+            q.push(i);
+            assert_eq!(q.pop(), Some(i));
+        }
+        let settled = allocations();
+        for i in 0..1000 {
+            q.push(i);
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(allocations(), settled);
+        q.push(1000);
+    }
+    assert_eq!(live_bytes(), bytes);
+}